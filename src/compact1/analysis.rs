@@ -0,0 +1,277 @@
+//! The analysis of subroutine usage.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::compact1::font_set::Record;
+use crate::compact1::{FontSet, GlyphID};
+use crate::type2::{self, bias, Call, Operator, Token};
+use crate::Result;
+
+/// An analysis of a font set.
+#[derive(Clone, Debug, Default)]
+pub struct Analysis {
+    /// The usage of each subroutine.
+    pub subroutines: BTreeMap<Subroutine, Usage>,
+    /// The number of occurrences of each operator in the character strings and
+    /// subroutines, with each subroutine counted once.
+    pub operators: HashMap<Operator, usize>,
+}
+
+/// A subroutine.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Subroutine {
+    /// A global subroutine given by its index.
+    Global(usize),
+    /// A local subroutine given by the indices of its font, dictionary, and
+    /// itself.
+    Local(usize, usize, usize),
+}
+
+/// A caller of a subroutine.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Caller {
+    /// A glyph given by the index of its font and its identifier.
+    Glyph(usize, GlyphID),
+    /// A subroutine.
+    Subroutine(Subroutine),
+}
+
+/// The usage of a subroutine.
+#[derive(Clone, Debug, Default)]
+pub struct Usage {
+    /// The callers making direct calls.
+    pub callers: Vec<Caller>,
+    /// The maximal nesting depth at which the subroutine is called, with calls
+    /// from glyphs having depth one, or `None` if it is not reachable from any
+    /// glyph.
+    pub depth: Option<usize>,
+    /// The size in bytes.
+    pub size: usize,
+}
+
+impl Analysis {
+    /// Analyze a font set.
+    pub fn new(font_set: &FontSet) -> Result<Self> {
+        let global = &font_set.subroutines;
+        let mut subroutines = BTreeMap::new();
+        for (k, code) in global.iter().enumerate() {
+            subroutines.insert(Subroutine::Global(k), usage(code));
+        }
+        let mut locals = vec![];
+        for (i, record) in font_set.records.iter().enumerate() {
+            let local = match record {
+                Record::CharacterIDKeyed(record) => record
+                    .records
+                    .iter()
+                    .map(|record| &record.subroutines[..])
+                    .collect::<Vec<_>>(),
+                Record::CharacterNameKeyed(record) => vec![&record.subroutines[..]],
+            };
+            for (j, local) in local.iter().enumerate() {
+                for (k, code) in local.iter().enumerate() {
+                    subroutines.insert(Subroutine::Local(i, j, k), usage(code));
+                }
+            }
+            locals.push(local);
+        }
+
+        let mut operators = HashMap::new();
+        let mut contexts = HashMap::new();
+        let mut edges = vec![];
+        for (i, record) in font_set.records.iter().enumerate() {
+            for (glyph_id, code) in font_set.character_strings[i].iter().enumerate() {
+                let glyph_id = glyph_id as GlyphID;
+                let j = match record {
+                    Record::CharacterIDKeyed(record) => match record.encoding.get(glyph_id) {
+                        Some(j) if (j as usize) < locals[i].len() => j as usize,
+                        _ => raise!("found no dictionary for glyph {glyph_id}"),
+                    },
+                    Record::CharacterNameKeyed(_) => 0,
+                };
+                let local = locals[i][j];
+                let tokens = type2::tokenize(code, global, local)?;
+                count(&tokens, &mut operators);
+                for callee in callees(&tokens, global.len(), Some((i, j, local.len()))) {
+                    edges.push((Caller::Glyph(i, glyph_id), callee));
+                }
+                for call in type2::trace(code, global, local)? {
+                    let key = match call.operator {
+                        Operator::CallSubr => Subroutine::Local(i, j, call.index),
+                        _ => Subroutine::Global(call.index),
+                    };
+                    let calls: &mut Vec<(Call, usize, usize)> = contexts.entry(key).or_default();
+                    if !calls
+                        .iter()
+                        .any(|&(_, other_i, other_j)| (other_i, other_j) == (i, j))
+                    {
+                        calls.push((call, i, j));
+                    }
+                }
+            }
+        }
+        // Global subroutines are tokenized in the context of every font and
+        // dictionary calling them, as they call different local subroutines.
+        for &key in subroutines.keys() {
+            let code = match key {
+                Subroutine::Global(k) => &global[k],
+                Subroutine::Local(i, j, k) => &locals[i][j][k],
+            };
+            let calls = match contexts.get(&key) {
+                Some(calls) => calls
+                    .iter()
+                    .map(|&(call, i, j)| (call, Some((i, j))))
+                    .collect::<Vec<_>>(),
+                _ => {
+                    let scope = match key {
+                        Subroutine::Local(i, j, _) => Some((i, j)),
+                        _ => None,
+                    };
+                    let call = Call {
+                        operator: Operator::CallSubr,
+                        index: 0,
+                        stems: 0,
+                        depth: 0,
+                    };
+                    vec![(call, scope)]
+                }
+            };
+            let mut counted = false;
+            for (call, scope) in calls {
+                let local = scope.map_or(&[][..], |(i, j)| locals[i][j]);
+                let tokens = match type2::tokenize_subroutine(code, global, local, &call) {
+                    Ok(tokens) => tokens,
+                    Err(_) if !contexts.contains_key(&key) => continue,
+                    Err(error) => return Err(error),
+                };
+                if !std::mem::replace(&mut counted, true) {
+                    count(&tokens, &mut operators);
+                }
+                let scope = scope.map(|(i, j)| (i, j, local.len()));
+                for callee in callees(&tokens, global.len(), scope) {
+                    edges.push((Caller::Subroutine(key), callee));
+                }
+            }
+        }
+        for (caller, callee) in edges {
+            if let Some(usage) = subroutines.get_mut(&callee) {
+                usage.callers.push(caller);
+            }
+        }
+        for usage in subroutines.values_mut() {
+            usage.callers.sort();
+            usage.callers.dedup();
+        }
+        for (key, depth) in depths(&subroutines)? {
+            if let Some(usage) = subroutines.get_mut(&key) {
+                usage.depth = depth;
+            }
+        }
+        Ok(Self {
+            subroutines,
+            operators,
+        })
+    }
+
+    /// Return the subroutines that are not reachable from any glyph.
+    pub fn unused(&self) -> Vec<Subroutine> {
+        self.subroutines
+            .iter()
+            .filter(|(_, usage)| usage.depth.is_none())
+            .map(|(&key, _)| key)
+            .collect()
+    }
+}
+
+fn callees(
+    tokens: &[Token],
+    global_count: usize,
+    scope: Option<(usize, usize, usize)>,
+) -> Vec<Subroutine> {
+    let mut callees = vec![];
+    for pair in tokens.windows(2) {
+        let (value, operator) = match pair {
+            [Token::Operand(value), Token::Operator(operator)] => (*value, *operator),
+            _ => continue,
+        };
+        let count = match (operator, scope) {
+            (Operator::CallGSubr, _) => global_count,
+            (Operator::CallSubr, Some((_, _, count))) => count,
+            _ => continue,
+        };
+        let k = value as i32 + bias(count);
+        if k < 0 || k as usize >= count {
+            continue;
+        }
+        callees.push(match (operator, scope) {
+            (Operator::CallSubr, Some((i, j, _))) => Subroutine::Local(i, j, k as usize),
+            _ => Subroutine::Global(k as usize),
+        });
+    }
+    callees
+}
+
+fn count(tokens: &[Token], operators: &mut HashMap<Operator, usize>) {
+    for token in tokens {
+        if let Token::Operator(operator) = token {
+            *operators.entry(*operator).or_default() += 1;
+        }
+    }
+}
+
+/// Compute the nesting depths by visiting subroutines after all of their
+/// callers.
+fn depths(
+    subroutines: &BTreeMap<Subroutine, Usage>,
+) -> Result<BTreeMap<Subroutine, Option<usize>>> {
+    let mut callees: BTreeMap<Subroutine, Vec<Subroutine>> = BTreeMap::new();
+    let mut pending = BTreeMap::new();
+    let mut depths = BTreeMap::new();
+    for (&key, usage) in subroutines.iter() {
+        let mut count = 0;
+        let mut depth = None;
+        for caller in usage.callers.iter() {
+            match *caller {
+                Caller::Glyph(..) => depth = Some(1),
+                Caller::Subroutine(caller) => {
+                    callees.entry(caller).or_default().push(key);
+                    count += 1;
+                }
+            }
+        }
+        pending.insert(key, count);
+        depths.insert(key, depth);
+    }
+    let mut queue = pending
+        .iter()
+        .filter(|(_, &count)| count == 0)
+        .map(|(&key, _)| key)
+        .collect::<Vec<_>>();
+    let mut visited = 0;
+    while let Some(key) = queue.pop() {
+        visited += 1;
+        let depth = depths[&key];
+        for &callee in callees.get(&key).into_iter().flatten() {
+            if let Some(value) = depths.get_mut(&callee) {
+                *value = (*value).max(depth.map(|depth| depth + 1));
+            }
+            if let Some(count) = pending.get_mut(&callee) {
+                *count -= 1;
+                if *count == 0 {
+                    queue.push(callee);
+                }
+            }
+        }
+    }
+    if visited < subroutines.len() {
+        raise!("found a cycle of subroutine calls");
+    }
+    Ok(depths)
+}
+
+#[inline]
+fn usage(code: &[u8]) -> Usage {
+    Usage {
+        size: code.len(),
+        ..Default::default()
+    }
+}
@@ -1,5 +1,6 @@
 //! The character sets.
 
+use crate::compact1::index::find_standard_string;
 use crate::compact1::{GlyphID, StringID};
 use crate::Result;
 
@@ -12,6 +13,8 @@ pub enum CharacterSet {
     Format0(CharacterSet0),
     Format1(CharacterSet1),
     Format2(CharacterSet2),
+    /// A character set that was damaged and left out during recovery.
+    Missing,
 }
 
 /// A character set in format 0.
@@ -36,6 +39,7 @@ pub struct CharacterSet2 {
 }
 
 table! {
+    @write
     /// A range of a character set in format 1.
     #[derive(Copy)]
     pub Range1 {
@@ -45,6 +49,7 @@ table! {
 }
 
 table! {
+    @write
     /// A range of a character set in format 2.
     #[derive(Copy)]
     pub Range2 {
@@ -54,6 +59,75 @@ table! {
 }
 
 impl CharacterSet {
+    /// Create a character set in the most compact format given the string
+    /// identifiers of all glyphs, starting with `.notdef`.
+    pub fn new(string_ids: &[StringID]) -> Self {
+        let glyphs = string_ids.get(1..).unwrap_or_default();
+        let mut ranges1: Vec<Range1> = vec![];
+        let mut ranges2: Vec<Range2> = vec![];
+        for &string_id in glyphs.iter() {
+            match ranges1.last_mut() {
+                Some(range)
+                    if range.left_count < u8::MAX
+                        && range.first_string_id as usize + range.left_count as usize + 1
+                            == string_id as usize =>
+                {
+                    range.left_count += 1
+                }
+                _ => ranges1.push(Range1 {
+                    first_string_id: string_id,
+                    left_count: 0,
+                }),
+            }
+            match ranges2.last_mut() {
+                Some(range)
+                    if range.left_count < u16::MAX
+                        && range.first_string_id as usize + range.left_count as usize + 1
+                            == string_id as usize =>
+                {
+                    range.left_count += 1
+                }
+                _ => ranges2.push(Range2 {
+                    first_string_id: string_id,
+                    left_count: 0,
+                }),
+            }
+        }
+        let sizes = [2 * glyphs.len(), 3 * ranges1.len(), 4 * ranges2.len()];
+        if sizes[0] <= sizes[1] && sizes[0] <= sizes[2] {
+            CharacterSet::Format0(CharacterSet0 {
+                format: 0,
+                glyphs: glyphs.to_vec(),
+            })
+        } else if sizes[1] <= sizes[2] {
+            CharacterSet::Format1(CharacterSet1 {
+                format: 1,
+                ranges: ranges1,
+            })
+        } else {
+            CharacterSet::Format2(CharacterSet2 {
+                format: 2,
+                ranges: ranges2,
+            })
+        }
+    }
+
+    /// Return the predefined character set matching the string identifiers
+    /// of all glyphs, starting with `.notdef`, if any.
+    pub(crate) fn predefined(string_ids: &[StringID]) -> Option<Self> {
+        [
+            CharacterSet::ISOAdobe,
+            CharacterSet::Expert,
+            CharacterSet::ExpertSubset,
+        ]
+        .into_iter()
+        .find(|character_set| {
+            string_ids.iter().enumerate().all(|(glyph_id, &string_id)| {
+                character_set.get_string_id(glyph_id as GlyphID) == Some(string_id)
+            })
+        })
+    }
+
     /// Return the name of a glyph.
     #[inline]
     pub fn get(&self, glyph_id: GlyphID) -> Option<&'static str> {
@@ -64,6 +138,41 @@ impl CharacterSet {
             CharacterSet::Format0(ref char_set) => char_set.get(glyph_id),
             CharacterSet::Format1(ref char_set) => char_set.get(glyph_id),
             CharacterSet::Format2(ref char_set) => char_set.get(glyph_id),
+            CharacterSet::Missing => None,
+        }
+    }
+
+    /// Return the string identifier of a glyph.
+    ///
+    /// In character-ID-keyed fonts, the identifier is the character identifier.
+    pub fn get_string_id(&self, glyph_id: GlyphID) -> Option<StringID> {
+        if glyph_id == 0 {
+            return Some(0);
+        }
+        match self {
+            CharacterSet::ISOAdobe => get_iso_adobe(glyph_id).map(|_| glyph_id),
+            CharacterSet::Expert => get_expert(glyph_id).and_then(find_standard_string),
+            CharacterSet::ExpertSubset => {
+                get_expert_subset(glyph_id).and_then(find_standard_string)
+            }
+            CharacterSet::Format0(ref char_set) => {
+                char_set.glyphs.get(glyph_id as usize - 1).copied()
+            }
+            CharacterSet::Format1(ref char_set) => find(
+                char_set
+                    .ranges
+                    .iter()
+                    .map(|range| (range.first_string_id, range.left_count as usize)),
+                glyph_id,
+            ),
+            CharacterSet::Format2(ref char_set) => find(
+                char_set
+                    .ranges
+                    .iter()
+                    .map(|range| (range.first_string_id, range.left_count as usize)),
+                glyph_id,
+            ),
+            CharacterSet::Missing => None,
         }
     }
 }
@@ -81,6 +190,18 @@ impl crate::walue::Read<'static> for CharacterSet {
     }
 }
 
+impl crate::value::Write for CharacterSet {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        match self {
+            CharacterSet::Format0(ref char_set) => tape.give(char_set),
+            CharacterSet::Format1(ref char_set) => tape.give(char_set),
+            CharacterSet::Format2(ref char_set) => tape.give(char_set),
+            CharacterSet::Missing => raise!("found a missing character set"),
+            _ => raise!("found a predefined character set, which cannot be written"),
+        }
+    }
+}
+
 impl CharacterSet0 {
     #[inline]
     fn get(&self, _: GlyphID) -> Option<&'static str> {
@@ -103,6 +224,13 @@ impl crate::walue::Read<'static> for CharacterSet0 {
     }
 }
 
+impl crate::value::Write for CharacterSet0 {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        tape.give(&self.format)?;
+        tape.give(&self.glyphs[..])
+    }
+}
+
 impl CharacterSet1 {
     #[inline]
     fn get(&self, _: GlyphID) -> Option<&'static str> {
@@ -133,6 +261,13 @@ impl crate::walue::Read<'static> for CharacterSet1 {
     }
 }
 
+impl crate::value::Write for CharacterSet1 {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        tape.give(&self.format)?;
+        tape.give(&self.ranges[..])
+    }
+}
+
 impl CharacterSet2 {
     #[inline]
     fn get(&self, _: GlyphID) -> Option<&'static str> {
@@ -164,6 +299,27 @@ impl crate::walue::Read<'static> for CharacterSet2 {
     }
 }
 
+impl crate::value::Write for CharacterSet2 {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        tape.give(&self.format)?;
+        tape.give(&self.ranges[..])
+    }
+}
+
+fn find<T>(ranges: T, glyph_id: GlyphID) -> Option<StringID>
+where
+    T: Iterator<Item = (StringID, usize)>,
+{
+    let mut offset = glyph_id as usize - 1;
+    for (first_string_id, left_count) in ranges {
+        if offset <= left_count {
+            return Some(first_string_id + offset as StringID);
+        }
+        offset -= left_count + 1;
+    }
+    None
+}
+
 fn get_iso_adobe(glyph_id: GlyphID) -> Option<&'static str> {
     Some(match glyph_id {
         1 => "space",
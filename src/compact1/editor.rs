@@ -0,0 +1,454 @@
+//! The editing of font sets.
+
+use std::collections::HashMap;
+
+use crate::compact1::font_set::{character_id_keyed, Record};
+use crate::compact1::index::{CharacterStrings, Subroutines};
+use crate::compact1::{
+    CharacterSet, Encoding, FontSet, GlyphID, Number, Operand, Operator, StringID,
+};
+use crate::type2::Program;
+use crate::Result;
+
+const GLYPH_LIMIT: usize = 65535;
+
+/// An editor of a font in a font set.
+///
+/// The editor keeps the strings, character set, and glyph-to-dictionary
+/// encoding of the font consistent with the glyphs, and it refuses character
+/// strings calling subroutines that do not exist. Operations whose operands
+/// are offsets are maintained by the writer and cannot be edited.
+pub struct Editor<'l> {
+    font_set: &'l mut FontSet,
+    index: usize,
+    glyph_ids: HashMap<String, GlyphID>,
+}
+
+impl<'l> Editor<'l> {
+    /// Create an editor of a font.
+    pub fn new(font_set: &'l mut FontSet, index: usize) -> Result<Self> {
+        if index >= font_set.records.len() {
+            raise!("found no font with index {index}");
+        }
+        let mut editor = Self {
+            font_set,
+            index,
+            glyph_ids: HashMap::new(),
+        };
+        editor.index_glyphs();
+        Ok(editor)
+    }
+
+    /// Return the number of glyphs.
+    #[inline]
+    pub fn glyph_count(&self) -> usize {
+        self.font_set.character_strings[self.index].len()
+    }
+
+    /// Return the name of a glyph.
+    ///
+    /// In character-ID-keyed fonts, glyphs other than `.notdef` are named
+    /// `cidNNNNN` after their character identifiers.
+    pub fn glyph_name(&self, glyph_id: GlyphID) -> Option<String> {
+        if glyph_id as usize >= self.glyph_count() {
+            return None;
+        }
+        let string_id = self.font_set.character_sets[self.index].get_string_id(glyph_id)?;
+        match glyph_id {
+            0 => Some(".notdef".into()),
+            _ if self.is_keyed() => Some(format!("cid{string_id:05}")),
+            _ => self.font_set.strings.get(string_id),
+        }
+    }
+
+    /// Return the identifier of a glyph given its name.
+    #[inline]
+    pub fn glyph_id(&self, name: &str) -> Option<GlyphID> {
+        self.glyph_ids.get(name).copied()
+    }
+
+    /// Return the character string of a glyph.
+    #[inline]
+    pub fn character_string(&self, glyph_id: GlyphID) -> Option<&[u8]> {
+        self.font_set.character_strings[self.index]
+            .get(glyph_id as usize)
+            .map(|code| &code[..])
+    }
+
+    /// Replace the character string of a glyph.
+    pub fn set_character_string(&mut self, glyph_id: GlyphID, code: Vec<u8>) -> Result<()> {
+        if glyph_id as usize >= self.glyph_count() {
+            raise!("found no glyph with identifier {glyph_id}");
+        }
+        self.check(
+            &code,
+            self.font_set.records[self.index].subroutines(glyph_id),
+        )?;
+        self.font_set.character_strings[self.index].replace(glyph_id as usize, code);
+        Ok(())
+    }
+
+    /// Add a glyph and return its identifier.
+    ///
+    /// In character-ID-keyed fonts, the name is expected to be of the form
+    /// `cidNNNNN` with an identifier below `CIDCount`, and the glyph is
+    /// assigned to the first dictionary.
+    pub fn add_glyph(&mut self, name: &str, code: Vec<u8>) -> Result<GlyphID> {
+        let glyph_count = self.glyph_count();
+        if glyph_count >= GLYPH_LIMIT {
+            raise!("found too many glyphs ({glyph_count})");
+        }
+        if self.glyph_id(name).is_some() {
+            raise!("found a duplicate glyph name ({name})");
+        }
+        let local = match self.font_set.records[self.index] {
+            Record::CharacterIDKeyed(ref record) => {
+                record.records.first().map(|record| &record.subroutines)
+            }
+            Record::CharacterNameKeyed(ref record) => Some(&record.subroutines),
+        };
+        self.check(&code, local)?;
+        let mut string_ids = self.string_ids()?;
+        if self.is_keyed() {
+            string_ids.push(self.character_id(name)?);
+        }
+        match self.font_set.records[self.index] {
+            Record::CharacterIDKeyed(ref mut record) => {
+                let mut dictionary_ids = dictionary_ids(record, glyph_count)?;
+                dictionary_ids.push(0);
+                record.encoding = character_id_keyed::Encoding::new(&dictionary_ids);
+            }
+            Record::CharacterNameKeyed(_) => string_ids.push(self.font_set.strings.insert(name)),
+        }
+        self.font_set.character_sets[self.index] = CharacterSet::new(&string_ids);
+        self.font_set.character_strings[self.index].push(code);
+        self.glyph_ids.insert(name.into(), glyph_count as GlyphID);
+        Ok(glyph_count as GlyphID)
+    }
+
+    /// Rename a glyph.
+    ///
+    /// In character-ID-keyed fonts, the name is expected to be of the form
+    /// `cidNNNNN` with an identifier below `CIDCount`. The string of the
+    /// previous name is reused if nothing else refers to it.
+    pub fn rename_glyph(&mut self, glyph_id: GlyphID, name: &str) -> Result<()> {
+        if glyph_id == 0 || glyph_id as usize >= self.glyph_count() {
+            raise!("found no glyph with identifier {glyph_id} that can be renamed");
+        }
+        match self.glyph_id(name) {
+            Some(other) if other == glyph_id => return Ok(()),
+            Some(_) => raise!("found a duplicate glyph name ({name})"),
+            _ => {}
+        }
+        let previous_name = self.glyph_name(glyph_id);
+        let mut string_ids = self.string_ids()?;
+        let previous = string_ids[glyph_id as usize];
+        string_ids[glyph_id as usize] = if self.is_keyed() {
+            self.character_id(name)?
+        } else {
+            match self.font_set.strings.position(name) {
+                Some(string_id) => string_id,
+                _ if self.reference_count(previous) == 1 => {
+                    match self.font_set.strings.replace(previous, name) {
+                        Some(_) => previous,
+                        _ => self.font_set.strings.insert(name),
+                    }
+                }
+                _ => self.font_set.strings.insert(name),
+            }
+        };
+        self.font_set.character_sets[self.index] = CharacterSet::new(&string_ids);
+        if let Some(previous_name) = previous_name {
+            self.glyph_ids.remove(&previous_name);
+        }
+        self.glyph_ids.insert(name.into(), glyph_id);
+        Ok(())
+    }
+
+    /// Reorder the glyphs given their identifiers in the new order.
+    ///
+    /// The order is required to include each glyph once, starting with
+    /// `.notdef`. The character set, the encoding, and the glyph-to-dictionary
+    /// encoding are updated accordingly.
+    pub fn reorder_glyphs(&mut self, glyph_ids: &[GlyphID]) -> Result<()> {
+        let glyph_count = self.glyph_count();
+        if glyph_ids.len() != glyph_count || glyph_ids.first() != Some(&0) {
+            raise!("found a malformed glyph order, expecting {glyph_count} glyphs");
+        }
+        let mut seen = vec![false; glyph_count];
+        for &glyph_id in glyph_ids.iter() {
+            match seen.get_mut(glyph_id as usize) {
+                Some(seen) if !*seen => *seen = true,
+                _ => raise!("found a malformed glyph order ({glyph_id})"),
+            }
+        }
+        let string_ids = self.string_ids()?;
+        let string_ids = glyph_ids
+            .iter()
+            .map(|&glyph_id| string_ids[glyph_id as usize])
+            .collect::<Vec<_>>();
+        let encoding = match self.font_set.encodings.get(self.index) {
+            Some(encoding) if !self.is_keyed() => match encoding.codes() {
+                Some(codes) => {
+                    let codes = glyph_ids[1..]
+                        .iter()
+                        .map(|&glyph_id| {
+                            codes
+                                .get(glyph_id as usize - 1)
+                                .copied()
+                                .filter(|&code| code != 0)
+                        })
+                        .collect::<Vec<_>>();
+                    let supplements = encoding.supplements().to_vec();
+                    Some(Encoding::from_glyphs(&codes)?.with_supplements(supplements)?)
+                }
+                _ => None,
+            },
+            _ => None,
+        };
+        if let Record::CharacterIDKeyed(ref mut record) = self.font_set.records[self.index] {
+            let dictionary_ids = dictionary_ids(record, glyph_count)?;
+            let dictionary_ids = glyph_ids
+                .iter()
+                .map(|&glyph_id| dictionary_ids[glyph_id as usize])
+                .collect::<Vec<_>>();
+            record.encoding = character_id_keyed::Encoding::new(&dictionary_ids);
+        }
+        if let Some(encoding) = encoding {
+            self.font_set.encodings[self.index] = encoding;
+        }
+        let character_strings = &self.font_set.character_strings[self.index];
+        let character_strings = glyph_ids
+            .iter()
+            .map(|&glyph_id| character_strings[glyph_id as usize].clone())
+            .collect::<Vec<_>>();
+        self.font_set.character_strings[self.index] = CharacterStrings(character_strings.into());
+        self.font_set.character_sets[self.index] = CharacterSet::new(&string_ids);
+        self.index_glyphs();
+        Ok(())
+    }
+
+    /// Assign a glyph to a dictionary in a character-ID-keyed font.
+    pub fn set_dictionary(&mut self, glyph_id: GlyphID, dictionary_id: u8) -> Result<()> {
+        let glyph_count = self.glyph_count();
+        if glyph_id as usize >= glyph_count {
+            raise!("found no glyph with identifier {glyph_id}");
+        }
+        let record = match self.font_set.records[self.index] {
+            Record::CharacterIDKeyed(ref record) => record,
+            _ => raise!("found a character-name-keyed font, which has no dictionaries"),
+        };
+        let local = match record.records.get(dictionary_id as usize) {
+            Some(record) => &record.subroutines,
+            _ => raise!("found no dictionary with index {dictionary_id}"),
+        };
+        self.check(
+            &self.font_set.character_strings[self.index][glyph_id as usize],
+            Some(local),
+        )?;
+        let mut dictionary_ids = dictionary_ids(record, glyph_count)?;
+        dictionary_ids[glyph_id as usize] = dictionary_id;
+        if let Record::CharacterIDKeyed(ref mut record) = self.font_set.records[self.index] {
+            record.encoding = character_id_keyed::Encoding::new(&dictionary_ids);
+        }
+        Ok(())
+    }
+
+    /// Return the value of an operation whose operand is a string.
+    pub fn get_string(&self, operator: Operator) -> Option<String> {
+        match self.font_set.operations[self.index].get_single(operator) {
+            Some(Number::Integer(value)) => self.font_set.strings.get(value as StringID),
+            _ => None,
+        }
+    }
+
+    /// Set the value of an operation whose operand is a string.
+    ///
+    /// The string of the previous value is reused if nothing else refers to
+    /// it.
+    pub fn set_string(&mut self, operator: Operator, value: &str) -> Result<()> {
+        if !operator.is_string() {
+            raise!("found an operator whose operand is not a string ({operator:?})");
+        }
+        let previous = match self.font_set.operations[self.index].get_single(operator) {
+            Some(Number::Integer(value)) => Some(value as StringID),
+            _ => None,
+        };
+        self.font_set.operations[self.index].remove(&operator);
+        let string_id = match (previous, self.font_set.strings.position(value)) {
+            (_, Some(string_id)) => string_id,
+            (Some(string_id), _) if !self.is_referenced(string_id) => {
+                match self.font_set.strings.replace(string_id, value) {
+                    Some(_) => string_id,
+                    _ => self.font_set.strings.insert(value),
+                }
+            }
+            _ => self.font_set.strings.insert(value),
+        };
+        self.font_set.operations[self.index]
+            .insert(operator, vec![Number::Integer(string_id as i32)]);
+        Ok(())
+    }
+
+    /// Set an operation of the top dictionary.
+    pub fn set_operation(&mut self, operator: Operator, operands: Vec<Operand>) -> Result<()> {
+        check_operator(operator)?;
+        if operator == Operator::ROS {
+            raise!("found an operator that cannot be edited ({operator:?})");
+        }
+        self.font_set.operations[self.index].insert(operator, operands);
+        Ok(())
+    }
+
+    /// Remove an operation of the top dictionary.
+    pub fn remove_operation(&mut self, operator: Operator) -> Result<Option<Vec<Operand>>> {
+        check_operator(operator)?;
+        if operator == Operator::ROS {
+            raise!("found an operator that cannot be edited ({operator:?})");
+        }
+        Ok(self.font_set.operations[self.index].remove(&operator))
+    }
+
+    /// Set an operation of a private dictionary.
+    ///
+    /// Character-name-keyed fonts have a single private dictionary with index
+    /// zero. The operands are given as stored, which means that, for instance,
+    /// the values of `BlueValues` are given as differences.
+    pub fn set_private_operation(
+        &mut self,
+        dictionary_id: usize,
+        operator: Operator,
+        operands: Vec<Operand>,
+    ) -> Result<()> {
+        check_operator(operator)?;
+        let operations = match self.font_set.records[self.index] {
+            Record::CharacterIDKeyed(ref mut record) => record
+                .records
+                .get_mut(dictionary_id)
+                .map(|record| &mut record.operations),
+            Record::CharacterNameKeyed(ref mut record) if dictionary_id == 0 => {
+                Some(&mut record.operations)
+            }
+            _ => None,
+        };
+        match operations {
+            Some(operations) => operations.insert(operator, operands),
+            _ => raise!("found no dictionary with index {dictionary_id}"),
+        };
+        Ok(())
+    }
+
+    fn character_id(&self, name: &str) -> Result<StringID> {
+        let string_id = match name
+            .strip_prefix("cid")
+            .and_then(|value| value.parse::<StringID>().ok())
+        {
+            Some(string_id) => string_id,
+            _ => raise!("found a malformed glyph name ({name})"),
+        };
+        if let Some(Number::Integer(count)) =
+            self.font_set.operations[self.index].get_single(Operator::CIDCount)
+        {
+            if string_id as i32 >= count {
+                raise!("found a character identifier exceeding CIDCount ({string_id})");
+            }
+        }
+        Ok(string_id)
+    }
+
+    fn check(&self, code: &[u8], local: Option<&Subroutines>) -> Result<()> {
+        let local = match local {
+            Some(local) => local,
+            _ => raise!("found no local subroutines for a character string"),
+        };
+        let mut program = Program::new(code, &self.font_set.subroutines, local);
+        while program.next()?.is_some() {}
+        Ok(())
+    }
+
+    fn index_glyphs(&mut self) {
+        self.glyph_ids = (0..self.glyph_count() as GlyphID)
+            .filter_map(|glyph_id| Some((self.glyph_name(glyph_id)?, glyph_id)))
+            .collect();
+    }
+
+    fn is_keyed(&self) -> bool {
+        matches!(
+            self.font_set.records[self.index],
+            Record::CharacterIDKeyed(_)
+        )
+    }
+
+    fn is_referenced(&self, string_id: StringID) -> bool {
+        self.reference_count(string_id) > 0
+    }
+
+    fn reference_count(&self, string_id: StringID) -> usize {
+        let font_set = &*self.font_set;
+        let referenced = |operations: &crate::compact1::Operations| {
+            operations
+                .iter()
+                .filter(|(operator, operands)| {
+                    operator.is_string()
+                        && operands.first() == Some(&Number::Integer(string_id as i32))
+                })
+                .count()
+        };
+        let mut count = 0;
+        for (i, record) in font_set.records.iter().enumerate() {
+            count += referenced(&font_set.operations[i]);
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    count += (record.registry == string_id) as usize
+                        + (record.ordering == string_id) as usize
+                        + record.operations.iter().map(referenced).sum::<usize>();
+                }
+                Record::CharacterNameKeyed(_) => {
+                    let character_set = &font_set.character_sets[i];
+                    count += (1..font_set.character_strings[i].len())
+                        .filter(|&glyph_id| {
+                            character_set.get_string_id(glyph_id as GlyphID) == Some(string_id)
+                        })
+                        .count();
+                    if let Some(Encoding::FormatSupplemental(encoding)) = font_set.encodings.get(i)
+                    {
+                        count += encoding
+                            .supplements
+                            .iter()
+                            .filter(|supplement| supplement.glyph == string_id)
+                            .count();
+                    }
+                }
+            }
+        }
+        count
+    }
+
+    fn string_ids(&self) -> Result<Vec<StringID>> {
+        let character_set = &self.font_set.character_sets[self.index];
+        (0..self.glyph_count())
+            .map(
+                |glyph_id| match character_set.get_string_id(glyph_id as GlyphID) {
+                    Some(string_id) => Ok(string_id),
+                    _ => raise!("found no name for glyph {glyph_id}"),
+                },
+            )
+            .collect()
+    }
+}
+
+fn dictionary_ids(record: &character_id_keyed::Record, glyph_count: usize) -> Result<Vec<u8>> {
+    (0..glyph_count)
+        .map(|glyph_id| match record.encoding.get(glyph_id as GlyphID) {
+            Some(dictionary_id) => Ok(dictionary_id),
+            _ => raise!("found no dictionary for glyph {glyph_id}"),
+        })
+        .collect()
+}
+
+fn check_operator(operator: Operator) -> Result<()> {
+    if operator.is_offset() {
+        raise!("found an operator whose operands are offsets ({operator:?})");
+    }
+    Ok(())
+}
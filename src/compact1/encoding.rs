@@ -11,9 +11,12 @@ pub enum Encoding {
     Format0(Encoding0),
     Format1(Encoding1),
     FormatSupplemental(EncodingSupplemental),
+    /// An encoding that was damaged and left out during recovery.
+    Missing,
 }
 
 table! {
+    @write
     /// An encoding in format 0.
     pub Encoding0 { // Format 0
         format     (u8) = { 0 }, // format
@@ -26,6 +29,7 @@ table! {
 }
 
 table! {
+    @write
     /// An encoding in format 1.
     pub Encoding1 { // Format 1
         format      (u8) = { 1 }, // format
@@ -38,9 +42,27 @@ table! {
 }
 
 table! {
-    /// An encoding in the supplemental format.
+    @write
+    /// An encoding in format 0 or 1 followed by supplements.
     pub EncodingSupplemental { // Supplemental Encoding Data
-        format           (u8),
+        format (u8), // format
+        count  (u8), // nCodes or nRanges
+
+        codes (Vec<u8>) |this, tape| { // code
+            match this.format & 0x7f {
+                0 => tape.take_given(this.count as usize),
+                _ => Ok(vec![]),
+            }
+        },
+
+        ranges (Vec<Range1>) |this, tape| { // Range1
+            match this.format & 0x7f {
+                0 => Ok(vec![]),
+                1 => tape.take_given(this.count as usize),
+                format => raise!("found an unknown format of encodings ({format})"),
+            }
+        },
+
         supplement_count (u8), // nSups
 
         supplements (Vec<Supplement>) |this, tape| { // Supplement
@@ -50,6 +72,7 @@ table! {
 }
 
 table! {
+    @write
     /// A range of an encoding in format 1.
     #[derive(Copy)]
     pub Range1 {
@@ -59,6 +82,7 @@ table! {
 }
 
 table! {
+    @write
     /// A supplement of an encoding in the supplemental format.
     #[derive(Copy)]
     pub Supplement {
@@ -68,6 +92,99 @@ table! {
 }
 
 impl Encoding {
+    /// Create an encoding in the most compact format given the codes of
+    /// consecutive glyphs, starting with the one following `.notdef`.
+    pub fn new(codes: &[u8]) -> Result<Self> {
+        if codes.len() > u8::MAX as usize {
+            raise!("found too many codes ({})", codes.len());
+        }
+        let mut ranges: Vec<Range1> = vec![];
+        for &code in codes.iter() {
+            match ranges.last_mut() {
+                Some(range)
+                    if range.left_count < u8::MAX
+                        && range.first_code as usize + range.left_count as usize + 1
+                            == code as usize =>
+                {
+                    range.left_count += 1
+                }
+                _ => ranges.push(Range1 {
+                    first_code: code,
+                    left_count: 0,
+                }),
+            }
+        }
+        Ok(if codes.len() <= 2 * ranges.len() {
+            Encoding::Format0(Encoding0 {
+                format: 0,
+                code_count: codes.len() as u8,
+                codes: codes.to_vec(),
+            })
+        } else {
+            Encoding::Format1(Encoding1 {
+                format: 1,
+                range_count: ranges.len() as u8,
+                ranges,
+            })
+        })
+    }
+
+    /// Create an encoding given the codes of the glyphs following `.notdef`.
+    ///
+    /// Unencoded glyphs preceding encoded ones are given code zero, which is
+    /// reserved for `.notdef`.
+    pub(crate) fn from_glyphs(codes: &[Option<u8>]) -> Result<Self> {
+        let count = codes.iter().rposition(Option::is_some).map_or(0, |i| i + 1);
+        Encoding::new(
+            &codes[..count]
+                .iter()
+                .map(|code| code.unwrap_or(0))
+                .collect::<Vec<_>>(),
+        )
+    }
+
+    /// Return the codes of consecutive glyphs, starting with the one following
+    /// `.notdef`, excluding the supplements, unless the encoding is predefined.
+    pub(crate) fn codes(&self) -> Option<Vec<u8>> {
+        match self {
+            Encoding::Format0(ref encoding) => Some(encoding.codes.clone()),
+            Encoding::Format1(ref encoding) => Some(expand(&encoding.ranges)),
+            Encoding::FormatSupplemental(ref encoding) => Some(encoding.codes()),
+            _ => None,
+        }
+    }
+
+    /// Return the supplements.
+    pub(crate) fn supplements(&self) -> &[Supplement] {
+        match self {
+            Encoding::FormatSupplemental(ref encoding) => &encoding.supplements,
+            _ => &[],
+        }
+    }
+
+    /// Add supplements to an encoding in format 0 or 1.
+    pub(crate) fn with_supplements(self, supplements: Vec<Supplement>) -> Result<Self> {
+        if supplements.is_empty() {
+            return Ok(self);
+        }
+        if supplements.len() > u8::MAX as usize {
+            raise!("found too many supplements ({})", supplements.len());
+        }
+        let (format, count, codes, ranges) = match self {
+            Encoding::Format0(encoding) => (0x80, encoding.code_count, encoding.codes, vec![]),
+            Encoding::Format1(encoding) => (0x81, encoding.range_count, vec![], encoding.ranges),
+            _ => raise!("found an encoding that cannot be supplemented"),
+        };
+        Ok(Encoding::FormatSupplemental(EncodingSupplemental {
+            format,
+            count,
+            codes,
+            ranges,
+            supplement_count: supplements.len() as u8,
+            supplements,
+        }))
+    }
+
     /// Return the string identifier of a glyph.
     pub fn get(&self, glyph_id: GlyphID) -> Option<StringID> {
         match self {
@@ -76,6 +193,7 @@ impl Encoding {
             Encoding::Format0(ref encoding) => encoding.get(glyph_id),
             Encoding::Format1(ref encoding) => encoding.get(glyph_id),
             Encoding::FormatSupplemental(ref encoding) => encoding.get(glyph_id),
+            Encoding::Missing => None,
         }
     }
 }
@@ -91,6 +209,30 @@ impl crate::value::Read for Encoding {
     }
 }
 
+impl crate::value::Write for Encoding {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        match self {
+            Encoding::Format0(ref encoding) => tape.give(encoding),
+            Encoding::Format1(ref encoding) => tape.give(encoding),
+            Encoding::FormatSupplemental(ref encoding) => {
+                let count = match encoding.format & 0x7f {
+                    0 if encoding.ranges.is_empty() => encoding.codes.len(),
+                    1 if encoding.codes.is_empty() => encoding.ranges.len(),
+                    _ => raise!("found a malformed supplemental encoding"),
+                };
+                if count != encoding.count as usize
+                    || encoding.supplements.len() != encoding.supplement_count as usize
+                {
+                    raise!("found a malformed supplemental encoding");
+                }
+                tape.give(encoding)
+            }
+            Encoding::Missing => raise!("found a missing encoding"),
+            _ => raise!("found a predefined encoding, which cannot be written"),
+        }
+    }
+}
+
 impl Encoding0 {
     #[inline]
     fn get(&self, _: GlyphID) -> Option<StringID> {
@@ -110,6 +252,24 @@ impl EncodingSupplemental {
     fn get(&self, _: GlyphID) -> Option<StringID> {
         None
     }
+
+    /// Return the codes of consecutive glyphs, starting with the one following
+    /// `.notdef`, excluding the supplements.
+    pub(crate) fn codes(&self) -> Vec<u8> {
+        match self.format & 0x7f {
+            0 => self.codes.clone(),
+            _ => expand(&self.ranges),
+        }
+    }
+}
+
+fn expand(ranges: &[Range1]) -> Vec<u8> {
+    ranges
+        .iter()
+        .flat_map(|range| {
+            (0..=range.left_count as usize).map(move |i| (range.first_code as usize + i) as u8)
+        })
+        .collect()
 }
 
 macro_rules! get(
@@ -0,0 +1,186 @@
+//! The errors.
+
+use crate::compact1::GlyphID;
+use crate::{Error, Result};
+
+/// An error located in a font set.
+#[derive(Debug)]
+pub struct ErrorWithLocation {
+    /// The location.
+    pub location: Location,
+    /// The absolute position in bytes at which the failure occurred.
+    pub offset: u64,
+    /// The error.
+    pub source: Error,
+}
+
+/// A location in a font set.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Location {
+    /// The font set as a whole.
+    FontSet,
+    /// The header.
+    Header,
+    /// The index of names.
+    Names,
+    /// The index of top dictionaries.
+    TopDictionaries,
+    /// The index of strings.
+    Strings,
+    /// The index of global subroutines.
+    GlobalSubroutines,
+    /// The top dictionary of a font given by its index.
+    Top(usize),
+    /// The character set of a font given by its index.
+    CharacterSet(usize),
+    /// The encoding of a font given by its index.
+    Encoding(usize),
+    /// The index of character strings of a font given by its index.
+    CharacterStrings(usize),
+    /// The index of font dictionaries of a font given by its index.
+    FontDictionaries(usize),
+    /// The glyph-to-dictionary encoding of a font given by its index.
+    DictionaryEncoding(usize),
+    /// A font dictionary given by the indices of its font and itself.
+    FontDictionary(usize, usize),
+    /// A private dictionary given by the indices of its font and its font
+    /// dictionary, which is zero for character-name-keyed fonts.
+    Private(usize, usize),
+    /// An index of local subroutines given by the indices of its private
+    /// dictionary.
+    LocalSubroutines(usize, usize),
+    /// A glyph given by the index of its font and its identifier.
+    Glyph(usize, GlyphID),
+}
+
+impl ErrorWithLocation {
+    /// Find the location of an error, looking through the errors it is caused
+    /// by.
+    pub fn find(error: &Error) -> Option<&Self> {
+        let mut error = error;
+        loop {
+            let inner = error.get_ref()?;
+            if let Some(value) = inner.downcast_ref::<Self>() {
+                return Some(value);
+            }
+            error = &inner.downcast_ref::<typeface::ErrorWithSource>()?.source;
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorWithLocation {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            formatter,
+            "{} in {} at offset {}",
+            self.source, self.location, self.offset,
+        )
+    }
+}
+
+impl std::error::Error for ErrorWithLocation {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Location::FontSet => write!(formatter, "the font set"),
+            Location::Header => write!(formatter, "the header"),
+            Location::Names => write!(formatter, "the index of names"),
+            Location::TopDictionaries => write!(formatter, "the index of top dictionaries"),
+            Location::Strings => write!(formatter, "the index of strings"),
+            Location::GlobalSubroutines => write!(formatter, "the index of global subroutines"),
+            Location::Top(i) => write!(formatter, "the top dictionary of font {i}"),
+            Location::CharacterSet(i) => write!(formatter, "the character set of font {i}"),
+            Location::Encoding(i) => write!(formatter, "the encoding of font {i}"),
+            Location::CharacterStrings(i) => {
+                write!(formatter, "the index of character strings of font {i}")
+            }
+            Location::FontDictionaries(i) => {
+                write!(formatter, "the index of font dictionaries of font {i}")
+            }
+            Location::DictionaryEncoding(i) => {
+                write!(formatter, "the glyph-to-dictionary encoding of font {i}")
+            }
+            Location::FontDictionary(i, j) => {
+                write!(formatter, "font dictionary {j} of font {i}")
+            }
+            Location::Private(i, j) => write!(
+                formatter,
+                "the private dictionary of font dictionary {j} of font {i}"
+            ),
+            Location::LocalSubroutines(i, j) => write!(
+                formatter,
+                "the index of local subroutines of font dictionary {j} of font {i}"
+            ),
+            Location::Glyph(i, glyph_id) => write!(formatter, "glyph {glyph_id} of font {i}"),
+        }
+    }
+}
+
+pub(crate) trait Locate<T> {
+    /// Attach a location to an error unless it already has one.
+    fn locate(self, location: Location, offset: u64) -> Result<T>;
+}
+
+impl<T> Locate<T> for Result<T> {
+    fn locate(self, location: Location, offset: u64) -> Result<T> {
+        match self {
+            Err(error) if ErrorWithLocation::find(&error).is_none() => {
+                Err(Error::other(ErrorWithLocation {
+                    location,
+                    offset,
+                    source: error,
+                }))
+            }
+            result => result,
+        }
+    }
+}
+
+pub(crate) trait Recover<T> {
+    /// Record an error and fall back to a default value if omissions are
+    /// collected, or propagate the error otherwise.
+    fn recover<F>(self, omissions: Option<&mut Vec<ErrorWithLocation>>, default: F) -> Result<T>
+    where
+        F: FnOnce() -> T;
+}
+
+impl<T> Recover<T> for Result<T> {
+    fn recover<F>(self, omissions: Option<&mut Vec<ErrorWithLocation>>, default: F) -> Result<T>
+    where
+        F: FnOnce() -> T,
+    {
+        match (self, omissions) {
+            (Err(error), Some(omissions)) => {
+                omissions.push(unwrap(error));
+                Ok(default())
+            }
+            (result, _) => result,
+        }
+    }
+}
+
+fn unwrap(error: Error) -> ErrorWithLocation {
+    if error
+        .get_ref()
+        .is_some_and(|inner| inner.is::<ErrorWithLocation>())
+    {
+        if let Some(Ok(value)) = error
+            .into_inner()
+            .map(|inner| inner.downcast::<ErrorWithLocation>())
+        {
+            return *value;
+        }
+        unreachable!();
+    }
+    ErrorWithLocation {
+        location: Location::FontSet,
+        offset: 0,
+        source: error,
+    }
+}
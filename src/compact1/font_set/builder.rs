@@ -0,0 +1,320 @@
+//! The building of font sets.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::compact1::font_set::{character_id_keyed, character_name_keyed, FontSet, Record};
+use crate::compact1::index::{CharacterStrings, Names, Strings, Subroutines};
+use crate::compact1::{
+    CharacterSet, Encoding, GlyphID, Header, Number, Operand, Operations, Operator, StringID,
+};
+use crate::type2::Program;
+use crate::Result;
+
+const GLYPH_LIMIT: usize = 65535;
+const DICTIONARY_LIMIT: usize = 256;
+
+/// A builder of font sets with a single font.
+///
+/// Glyphs are added in order, starting with `.notdef`. In character-ID-keyed
+/// fonts, glyphs other than `.notdef` are named `cidNNNNN` after their
+/// character identifiers. String identifiers are assigned as strings are
+/// encountered, and the formats of the character set and encoding are chosen
+/// to minimize their size.
+#[derive(Clone, Debug)]
+pub struct Builder {
+    name: String,
+    strings: Strings,
+    operations: Operations,
+    registry: Option<(StringID, StringID, Number)>,
+    dictionaries: Vec<(Operations, Operations)>,
+    glyphs: Vec<(StringID, u8)>,
+    names: HashSet<StringID>,
+    character_strings: Vec<Vec<u8>>,
+    codes: HashMap<u8, GlyphID>,
+}
+
+impl Builder {
+    /// Create a builder of a character-name-keyed font.
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.into(),
+            strings: Strings::default(),
+            operations: Operations::default(),
+            registry: None,
+            dictionaries: vec![Default::default()],
+            glyphs: vec![],
+            names: HashSet::new(),
+            character_strings: vec![],
+            codes: HashMap::new(),
+        }
+    }
+
+    /// Create a builder of a character-ID-keyed font with a single
+    /// dictionary.
+    pub fn new_character_id_keyed(
+        name: &str,
+        registry: &str,
+        ordering: &str,
+        supplement: Number,
+    ) -> Self {
+        let mut builder = Self::new(name);
+        let registry = builder.strings.insert(registry);
+        let ordering = builder.strings.insert(ordering);
+        builder.registry = Some((registry, ordering, supplement));
+        builder
+    }
+
+    /// Set an operation of the top dictionary whose operand is a string.
+    pub fn set_string(&mut self, operator: Operator, value: &str) -> Result<()> {
+        let string_id = self.insert_string(operator, value)?;
+        self.operations
+            .insert(operator, vec![Number::Integer(string_id as i32)]);
+        Ok(())
+    }
+
+    /// Set an operation of the top dictionary.
+    pub fn set_operation(&mut self, operator: Operator, operands: Vec<Operand>) -> Result<()> {
+        check_operator(operator)?;
+        self.operations.insert(operator, operands);
+        Ok(())
+    }
+
+    /// Add a dictionary to a character-ID-keyed font and return its index.
+    pub fn add_dictionary(&mut self) -> Result<u8> {
+        if self.registry.is_none() {
+            raise!("found a character-name-keyed font, which has a single dictionary");
+        }
+        if self.dictionaries.len() >= DICTIONARY_LIMIT {
+            raise!("found too many dictionaries ({})", self.dictionaries.len());
+        }
+        self.dictionaries.push(Default::default());
+        Ok((self.dictionaries.len() - 1) as u8)
+    }
+
+    /// Set an operation of a font dictionary whose operand is a string.
+    pub fn set_dictionary_string(
+        &mut self,
+        dictionary_id: u8,
+        operator: Operator,
+        value: &str,
+    ) -> Result<()> {
+        if self.registry.is_none() {
+            raise!("found a character-name-keyed font, which has no font dictionaries");
+        }
+        let string_id = self.insert_string(operator, value)?;
+        match self.dictionaries.get_mut(dictionary_id as usize) {
+            Some((operations, _)) => {
+                operations.insert(operator, vec![Number::Integer(string_id as i32)])
+            }
+            _ => raise!("found no dictionary with index {dictionary_id}"),
+        };
+        Ok(())
+    }
+
+    /// Set an operation of a private dictionary.
+    ///
+    /// Character-name-keyed fonts have a single private dictionary with index
+    /// zero. The operands are given as stored, which means that, for instance,
+    /// the values of `BlueValues` are given as differences.
+    pub fn set_private_operation(
+        &mut self,
+        dictionary_id: u8,
+        operator: Operator,
+        operands: Vec<Operand>,
+    ) -> Result<()> {
+        check_operator(operator)?;
+        match self.dictionaries.get_mut(dictionary_id as usize) {
+            Some((_, operations)) => operations.insert(operator, operands),
+            _ => raise!("found no dictionary with index {dictionary_id}"),
+        };
+        Ok(())
+    }
+
+    /// Add a glyph given its character string and return its identifier.
+    ///
+    /// In character-ID-keyed fonts, the glyph is assigned to the first
+    /// dictionary.
+    pub fn add_glyph(&mut self, name: &str, code: Vec<u8>) -> Result<GlyphID> {
+        let glyph_id = self.glyphs.len();
+        if glyph_id >= GLYPH_LIMIT {
+            raise!("found too many glyphs ({glyph_id})");
+        }
+        if (glyph_id == 0) != (name == ".notdef") {
+            raise!("found a misplaced glyph ({name}), expecting .notdef first");
+        }
+        let mut program = Program::new(&code, &[], &[]);
+        while program.next()?.is_some() {}
+        let string_id = match name {
+            ".notdef" => 0,
+            _ if self.registry.is_some() => {
+                match name
+                    .strip_prefix("cid")
+                    .and_then(|value| value.parse().ok())
+                {
+                    Some(string_id) => string_id,
+                    _ => raise!("found a malformed glyph name ({name})"),
+                }
+            }
+            _ => self.strings.insert(name),
+        };
+        if !self.names.insert(string_id) {
+            raise!("found a duplicate glyph name ({name})");
+        }
+        self.glyphs.push((string_id, 0));
+        self.character_strings.push(code);
+        Ok(glyph_id as GlyphID)
+    }
+
+    /// Add a glyph given its outline and return its identifier.
+    #[inline]
+    pub fn add_outline(&mut self, name: &str, outline: &crate::type2::Builder) -> Result<GlyphID> {
+        self.add_glyph(name, outline.encode()?)
+    }
+
+    /// Assign a glyph to a dictionary in a character-ID-keyed font.
+    pub fn set_dictionary(&mut self, glyph_id: GlyphID, dictionary_id: u8) -> Result<()> {
+        if self.registry.is_none() {
+            raise!("found a character-name-keyed font, which has no font dictionaries");
+        }
+        if dictionary_id as usize >= self.dictionaries.len() {
+            raise!("found no dictionary with index {dictionary_id}");
+        }
+        match self.glyphs.get_mut(glyph_id as usize) {
+            Some((_, value)) => *value = dictionary_id,
+            _ => raise!("found no glyph with identifier {glyph_id}"),
+        }
+        Ok(())
+    }
+
+    /// Map a character code to a glyph in a character-name-keyed font.
+    ///
+    /// Unless codes are mapped, the standard encoding is used.
+    pub fn set_code(&mut self, code: u8, glyph_id: GlyphID) -> Result<()> {
+        if self.registry.is_some() {
+            raise!("found a character-ID-keyed font, which has no encoding");
+        }
+        if glyph_id == 0 || glyph_id as usize >= self.glyphs.len() {
+            raise!("found no glyph with identifier {glyph_id}");
+        }
+        self.codes.insert(code, glyph_id);
+        Ok(())
+    }
+
+    /// Build the font set.
+    pub fn build(self) -> Result<FontSet> {
+        if self.glyphs.is_empty() {
+            raise!("found no glyphs, expecting at least .notdef");
+        }
+        let string_ids = self
+            .glyphs
+            .iter()
+            .map(|&(string_id, _)| string_id)
+            .collect::<Vec<_>>();
+        let mut operations = self.operations;
+        let (character_set, encoding, record) = match self.registry {
+            Some((registry, ordering, supplement)) => {
+                operations.insert(
+                    Operator::ROS,
+                    vec![
+                        Number::Integer(registry as i32),
+                        Number::Integer(ordering as i32),
+                        supplement,
+                    ],
+                );
+                operations.entry(Operator::CIDCount).or_insert_with(|| {
+                    let count = string_ids.iter().max().copied().unwrap_or(0) as i32 + 1;
+                    vec![Number::Integer(count)]
+                });
+                let dictionary_ids = self
+                    .glyphs
+                    .iter()
+                    .map(|&(_, dictionary_id)| dictionary_id)
+                    .collect::<Vec<_>>();
+                let (operations, records) = self
+                    .dictionaries
+                    .into_iter()
+                    .map(|(operations, private)| {
+                        (
+                            operations,
+                            character_id_keyed::RecordInner {
+                                operations: private,
+                                subroutines: Subroutines::default(),
+                            },
+                        )
+                    })
+                    .unzip();
+                (
+                    CharacterSet::new(&string_ids),
+                    Encoding::Standard,
+                    Record::CharacterIDKeyed(character_id_keyed::Record {
+                        registry,
+                        ordering,
+                        supplement,
+                        encoding: character_id_keyed::Encoding::new(&dictionary_ids),
+                        operations,
+                        records,
+                    }),
+                )
+            }
+            _ => {
+                let character_set = CharacterSet::predefined(&string_ids)
+                    .unwrap_or_else(|| CharacterSet::new(&string_ids));
+                let encoding = encoding(&self.codes, string_ids.len())?;
+                let mut dictionaries = self.dictionaries;
+                let (_, operations) = dictionaries.remove(0);
+                (
+                    character_set,
+                    encoding,
+                    Record::CharacterNameKeyed(character_name_keyed::Record {
+                        operations,
+                        subroutines: Subroutines::default(),
+                    }),
+                )
+            }
+        };
+        Ok(FontSet {
+            header: Header {
+                major: 1,
+                minor: 0,
+                header_size: 4,
+                offset_size: 4,
+            },
+            names: Names(vec![self.name.into_bytes()].into()),
+            operations: vec![operations],
+            strings: self.strings,
+            subroutines: Subroutines::default(),
+            encodings: vec![encoding],
+            character_strings: vec![CharacterStrings(self.character_strings.into())],
+            character_sets: vec![character_set],
+            records: vec![record],
+        })
+    }
+
+    fn insert_string(&mut self, operator: Operator, value: &str) -> Result<StringID> {
+        if !operator.is_string() {
+            raise!("found an operator whose operand is not a string ({operator:?})");
+        }
+        Ok(self.strings.insert(value))
+    }
+}
+
+fn encoding(codes: &HashMap<u8, GlyphID>, glyph_count: usize) -> Result<Encoding> {
+    if codes.is_empty() {
+        return Ok(Encoding::Standard);
+    }
+    let mut glyphs = vec![None; glyph_count];
+    for (&code, &glyph_id) in codes.iter() {
+        match glyphs[glyph_id as usize] {
+            None => glyphs[glyph_id as usize] = Some(code),
+            Some(_) => raise!("found a glyph with several codes ({glyph_id})"),
+        }
+    }
+    Encoding::from_glyphs(&glyphs[1..])
+}
+
+fn check_operator(operator: Operator) -> Result<()> {
+    if operator == Operator::ROS || operator.is_offset() {
+        raise!("found an operator that is maintained automatically ({operator:?})");
+    }
+    Ok(())
+}
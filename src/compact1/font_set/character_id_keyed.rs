@@ -1,9 +1,10 @@
 //! The character-ID-keyed fonts.
 
-use std::io::Cursor;
-
+use crate::compact1::error::{Locate, Recover};
 use crate::compact1::index::{CharacterStrings, Dictionaries, Subroutines};
-use crate::compact1::{GlyphID, Number, Operations, Operator, StringID};
+use crate::compact1::{
+    ErrorWithLocation, GlyphID, Location, Number, Operations, Operator, StringID,
+};
 use crate::Result;
 
 /// A character-ID-keyed record in a font set.
@@ -31,6 +32,8 @@ pub enum Encoding {
     Format0(Encoding0),
     /// Format 3.
     Format3(Encoding3),
+    /// An encoding that was damaged and left out during recovery.
+    Missing,
 }
 
 /// A glyph-to-dictionary encoding in format 0.
@@ -41,6 +44,7 @@ pub struct Encoding0 {
 }
 
 table! {
+    @write
     /// A glyph-to-dictionary encoding in format 3.
     pub Encoding3 {
         format      (u8 ) = { 3 }, // format
@@ -55,6 +59,7 @@ table! {
 }
 
 table! {
+    @write
     /// A range of a glyph-to-dictionary encoding in format 3.
     #[derive(Copy)]
     pub Range3 {
@@ -63,34 +68,65 @@ table! {
     }
 }
 
+impl Encoding {
+    /// Create an encoding in the most compact format given the dictionary
+    /// indices of all glyphs.
+    pub fn new(dictionary_ids: &[u8]) -> Self {
+        let mut ranges: Vec<Range3> = vec![];
+        for (glyph_id, &dictionary_id) in dictionary_ids.iter().enumerate() {
+            if ranges.last().map(|range| range.dictionary_id) != Some(dictionary_id) {
+                ranges.push(Range3 {
+                    first_glyph_id: glyph_id as GlyphID,
+                    dictionary_id,
+                });
+            }
+        }
+        if dictionary_ids.len() <= 2 + 3 * ranges.len() + 2 {
+            Encoding::Format0(Encoding0 {
+                format: 0,
+                dictionary_ids: dictionary_ids.to_vec(),
+            })
+        } else {
+            Encoding::Format3(Encoding3 {
+                format: 3,
+                range_count: ranges.len() as u16,
+                ranges,
+                glyph_count: dictionary_ids.len() as u16,
+            })
+        }
+    }
+
+    /// Return the dictionary index of a glyph.
+    pub fn get(&self, glyph_id: GlyphID) -> Option<u8> {
+        match self {
+            Encoding::Format0(ref encoding) => {
+                encoding.dictionary_ids.get(glyph_id as usize).copied()
+            }
+            Encoding::Format3(ref encoding) => {
+                if glyph_id >= encoding.glyph_count {
+                    return None;
+                }
+                encoding
+                    .ranges
+                    .iter()
+                    .take_while(|range| range.first_glyph_id <= glyph_id)
+                    .last()
+                    .map(|range| range.dictionary_id)
+            }
+            Encoding::Missing => None,
+        }
+    }
+}
+
 impl<'l> crate::walue::Read<'l> for Record {
     type Parameter = (u64, &'l Operations, &'l CharacterStrings);
 
+    #[inline]
     fn read<T: crate::tape::Read>(
         tape: &mut T,
         (position, top_operations, character_strings): Self::Parameter,
     ) -> Result<Self> {
-        let operands = match top_operations.get(Operator::ROS) {
-            Some(operands) if operands.len() == 3 => operands,
-            _ => raise!("found a malformed character-ID-keyed record"),
-        };
-        let offset = get!(@single top_operations, FDSelect);
-        let encoding = jump_take_given!(@unwrap tape, position, offset, character_strings);
-        let offset = get!(@single top_operations, FDArray);
-        let operations: Dictionaries = jump_take!(@unwrap tape, position, offset);
-        let operations: Vec<_> = (&operations).try_into()?;
-        let mut records = vec![];
-        for top_operations in operations.iter() {
-            records.push(tape.take_given((position, top_operations))?);
-        }
-        Ok(Self {
-            registry: operands[0].try_into()?,
-            ordering: operands[1].try_into()?,
-            supplement: operands[2],
-            encoding,
-            operations,
-            records,
-        })
+        read(tape, position, top_operations, character_strings, 0, None)
     }
 }
 
@@ -101,15 +137,8 @@ impl<'l> crate::walue::Read<'l> for RecordInner {
         tape: &mut T,
         (position, top_operations): Self::Parameter,
     ) -> Result<Self> {
-        use crate::tape::Read;
-
-        let (size, offset) = get!(@double top_operations, Private);
-        let chunk: Vec<u8> = jump_take_given!(@unwrap tape, position, offset, size as usize);
-        let operations = Cursor::new(chunk).take::<Operations>()?;
-        let subroutines = match get!(@try @single operations, Subrs) {
-            Some(another_offset) => jump_take!(@unwrap tape, position, offset + another_offset),
-            _ => Default::default(),
-        };
+        let (operations, subroutines) =
+            super::read_private(tape, position, top_operations, 0, 0, None)?;
         Ok(Self {
             operations,
             subroutines,
@@ -134,6 +163,16 @@ impl<'l> crate::walue::Read<'l> for Encoding {
     }
 }
 
+impl crate::value::Write for Encoding {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        match self {
+            Encoding::Format0(ref encoding) => tape.give(encoding),
+            Encoding::Format3(ref encoding) => tape.give(encoding),
+            Encoding::Missing => raise!("found a missing glyph-to-dictionary encoding"),
+        }
+    }
+}
+
 impl<'l> crate::walue::Read<'l> for Encoding0 {
     type Parameter = &'l CharacterStrings;
 
@@ -149,3 +188,82 @@ impl<'l> crate::walue::Read<'l> for Encoding0 {
         })
     }
 }
+
+impl crate::value::Write for Encoding0 {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        tape.give(&self.format)?;
+        tape.give_bytes(&self.dictionary_ids)
+    }
+}
+
+/// Read a record, collecting recoverable errors into `omissions` if given.
+pub(crate) fn read<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    top_operations: &Operations,
+    character_strings: &CharacterStrings,
+    font: usize,
+    mut omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<Record> {
+    let operands = match top_operations.get(Operator::ROS) {
+        Some(operands) if operands.len() == 3 => operands,
+        _ => raise!("found a malformed character-ID-keyed record"),
+    };
+    let start = super::offset_position(position, top_operations, Operator::FDSelect);
+    let encoding = read_encoding(tape, position, top_operations, character_strings)
+        .locate(
+            Location::DictionaryEncoding(font),
+            start.unwrap_or(position),
+        )
+        .recover(omissions.as_deref_mut(), || Encoding::Missing)?;
+    let offset = get!(@single top_operations, FDArray);
+    let start = position + offset as u64;
+    tape.jump(start)?;
+    let dictionaries = tape
+        .take::<Dictionaries>()
+        .locate(Location::FontDictionaries(font), start)?;
+    let end = tape.position()?;
+    let mut operations = Vec::with_capacity(dictionaries.len());
+    let mut records = Vec::with_capacity(dictionaries.len());
+    for j in 0..dictionaries.len() {
+        let top_operations = dictionaries
+            .decode(end, j, Location::FontDictionary(font, j))
+            .map(Some)
+            .recover(omissions.as_deref_mut(), || None)?;
+        let (private, subroutines) = match top_operations {
+            Some(ref top_operations) => super::read_private(
+                tape,
+                position,
+                top_operations,
+                font,
+                j,
+                omissions.as_deref_mut(),
+            )?,
+            _ => Default::default(),
+        };
+        operations.push(top_operations.unwrap_or_default());
+        records.push(RecordInner {
+            operations: private,
+            subroutines,
+        });
+    }
+    Ok(Record {
+        registry: operands[0].try_into()?,
+        ordering: operands[1].try_into()?,
+        supplement: operands[2],
+        encoding,
+        operations,
+        records,
+    })
+}
+
+fn read_encoding<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    top_operations: &Operations,
+    character_strings: &CharacterStrings,
+) -> Result<Encoding> {
+    let offset = get!(@single top_operations, FDSelect);
+    tape.jump(position + offset as u64)?;
+    tape.take_given(character_strings)
+}
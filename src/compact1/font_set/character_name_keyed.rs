@@ -1,9 +1,7 @@
 //! The character-name-keyed fonts.
 
-use std::io::Cursor;
-
 use crate::compact1::index::Subroutines;
-use crate::compact1::Operations;
+use crate::compact1::{ErrorWithLocation, Operations};
 use crate::Result;
 
 /// A character-name-keyed record in a font set.
@@ -20,18 +18,22 @@ impl<'l> crate::walue::Read<'l> for Record {
         tape: &mut T,
         (position, top_operations): Self::Parameter,
     ) -> Result<Self> {
-        use crate::tape::Read;
-
-        let (size, offset) = get!(@double top_operations, Private);
-        let chunk: Vec<u8> = jump_take_given!(@unwrap tape, position, offset, size as usize);
-        let operations = Cursor::new(chunk).take::<Operations>()?;
-        let subroutines = match get!(@try @single operations, Subrs) {
-            Some(another_offset) => jump_take!(@unwrap tape, position, offset + another_offset),
-            _ => Default::default(),
-        };
-        Ok(Self {
-            operations,
-            subroutines,
-        })
+        read(tape, position, top_operations, 0, None)
     }
 }
+
+/// Read a record, collecting recoverable errors into `omissions` if given.
+pub(crate) fn read<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    top_operations: &Operations,
+    font: usize,
+    omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<Record> {
+    let (operations, subroutines) =
+        super::read_private(tape, position, top_operations, font, 0, omissions)?;
+    Ok(Record {
+        operations,
+        subroutines,
+    })
+}
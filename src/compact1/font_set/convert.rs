@@ -0,0 +1,260 @@
+//! The conversion of fonts between keying kinds.
+
+use crate::compact1::font_set::width::{reencode, widths};
+use crate::compact1::font_set::{
+    character_id_keyed, character_name_keyed, font_matrix, insert_font_matrix, same_font_matrix,
+    FontSet, Record,
+};
+use crate::compact1::index::{CharacterStrings, Subroutines};
+use crate::compact1::{CharacterSet, Encoding, GlyphID, Number, Operations, Operator, StringID};
+use crate::type2::{self, bias, Call, Token};
+use crate::Result;
+
+const SUBROUTINE_LIMIT: usize = 65535;
+
+impl FontSet {
+    /// Convert a character-name-keyed font into a character-ID-keyed one.
+    ///
+    /// The font gets a single font dictionary and the `Adobe-Identity-0`
+    /// ordering with glyph identifiers as character identifiers. Glyph names
+    /// are discarded, and accented characters are decomposed.
+    pub fn convert_to_character_id_keyed(&mut self, index: usize) -> Result<()> {
+        let record = match self.records.get(index) {
+            Some(Record::CharacterNameKeyed(_)) => {
+                self.decompose(index)?;
+                match &self.records[index] {
+                    Record::CharacterNameKeyed(record) => record.clone(),
+                    _ => unreachable!(),
+                }
+            }
+            Some(_) => return Ok(()),
+            _ => raise!("found no font with index {index}"),
+        };
+        let glyph_count = self.character_strings[index].len();
+        let registry = self.strings.insert("Adobe");
+        let ordering = self.strings.insert("Identity");
+        let name = String::from_utf8_lossy(&self.names[index]).into_owned();
+        let name = self.strings.insert(&name);
+        let supplement = Number::Integer(0);
+        let operations = &mut self.operations[index];
+        for operator in [Operator::CharSet, Operator::Encoding, Operator::Private] {
+            operations.remove(&operator);
+        }
+        operations.insert(
+            Operator::ROS,
+            vec![
+                Number::Integer(registry as i32),
+                Number::Integer(ordering as i32),
+                supplement,
+            ],
+        );
+        operations.insert(
+            Operator::CIDCount,
+            vec![Number::Integer(glyph_count as i32)],
+        );
+        let mut dictionary = Operations::default();
+        dictionary.insert(Operator::FontName, vec![Number::Integer(name as i32)]);
+        let string_ids = (0..glyph_count as StringID).collect::<Vec<_>>();
+        self.character_sets[index] = CharacterSet::new(&string_ids);
+        self.encodings[index] = Encoding::Standard;
+        self.records[index] = Record::CharacterIDKeyed(character_id_keyed::Record {
+            registry,
+            ordering,
+            supplement,
+            encoding: character_id_keyed::Encoding::new(&vec![0; glyph_count]),
+            operations: vec![dictionary],
+            records: vec![character_id_keyed::RecordInner {
+                operations: record.operations,
+                subroutines: record.subroutines,
+            }],
+        });
+        self.remove_unused_strings()
+    }
+
+    /// Convert a character-ID-keyed font into a character-name-keyed one.
+    ///
+    /// Glyphs other than `.notdef` are named `cidNNNNN` after their character
+    /// identifiers. The private dictionaries are required to be identical
+    /// apart from their subroutines, which are combined, and their default and
+    /// nominal widths, in terms of which the widths of the glyphs are
+    /// re-encoded according to the first dictionary. The font matrices of the
+    /// font dictionaries concatenated with the one of the top dictionary are
+    /// required to be identical, since the outlines are not transformed, and
+    /// the common one becomes the font matrix of the font.
+    pub fn convert_to_character_name_keyed(&mut self, index: usize) -> Result<()> {
+        let record = match self.records.get(index) {
+            Some(Record::CharacterIDKeyed(record)) => record.clone(),
+            Some(_) => return Ok(()),
+            _ => raise!("found no font with index {index}"),
+        };
+        let private = |j: usize| {
+            let mut operations = record.records[j].operations.clone();
+            operations.remove(&Operator::Subrs);
+            operations
+        };
+        let compared = |j: usize| {
+            let mut operations = private(j);
+            operations.remove(&Operator::DefaultWidthX);
+            operations.remove(&Operator::NominalWidthX);
+            operations
+        };
+        if (1..record.records.len()).any(|j| compared(j).0 != compared(0).0) {
+            raise!("found incompatible private dictionaries");
+        }
+        let matrices = record
+            .operations
+            .iter()
+            .map(|operations| font_matrix(&self.operations[index], Some(operations)))
+            .collect::<Result<Vec<_>>>()?;
+        if matrices
+            .iter()
+            .any(|matrix| !same_font_matrix(matrix, &matrices[0]))
+        {
+            raise!("found incompatible font matrices");
+        }
+        let glyph_count = self.character_strings[index].len();
+        let mut dictionaries = Vec::with_capacity(glyph_count);
+        let mut string_ids = Vec::with_capacity(glyph_count);
+        for glyph_id in 0..glyph_count as GlyphID {
+            match record.encoding.get(glyph_id) {
+                Some(j) if (j as usize) < record.records.len() => dictionaries.push(j as usize),
+                _ => raise!("found no dictionary for glyph {glyph_id}"),
+            }
+            let string_id = match self.character_sets[index].get_string_id(glyph_id) {
+                Some(string_id) => string_id,
+                _ => raise!("found no name for glyph {glyph_id}"),
+            };
+            string_ids.push(match glyph_id {
+                0 => 0,
+                _ => self.strings.insert(&format!("cid{string_id:05}")),
+            });
+        }
+        let mut character_strings = self.character_strings[index].to_vec();
+        if let Some(first) = record.records.first() {
+            let target = widths(&first.operations);
+            for (glyph_id, code) in character_strings.iter_mut().enumerate() {
+                let inner = &record.records[dictionaries[glyph_id]];
+                let source = widths(&inner.operations);
+                if source != target {
+                    *code = reencode(code, &self.subroutines, &inner.subroutines, source, target)?;
+                }
+            }
+        }
+        let (character_strings, subroutines) = combine(
+            &character_strings,
+            &dictionaries,
+            &self.subroutines,
+            &record.records,
+        )?;
+
+        let operations = &mut self.operations[index];
+        for operator in [
+            Operator::ROS,
+            Operator::CIDFontVersion,
+            Operator::CIDFontRevision,
+            Operator::CIDFontType,
+            Operator::CIDCount,
+            Operator::UIDBase,
+            Operator::FDArray,
+            Operator::FDSelect,
+        ] {
+            operations.remove(&operator);
+        }
+        if let Some(&matrix) = matrices.first() {
+            insert_font_matrix(operations, matrix)?;
+        }
+        self.character_strings[index] = CharacterStrings(character_strings.into());
+        self.character_sets[index] =
+            CharacterSet::predefined(&string_ids).unwrap_or_else(|| CharacterSet::new(&string_ids));
+        self.encodings[index] = Encoding::Standard;
+        self.records[index] = Record::CharacterNameKeyed(character_name_keyed::Record {
+            operations: match record.records.is_empty() {
+                true => Operations::default(),
+                _ => private(0),
+            },
+            subroutines,
+        });
+        self.remove_unused_strings()
+    }
+}
+
+fn combine(
+    character_strings: &[Vec<u8>],
+    dictionaries: &[usize],
+    global: &[Vec<u8>],
+    records: &[character_id_keyed::RecordInner],
+) -> Result<(Vec<Vec<u8>>, Subroutines)> {
+    let mut offsets = Vec::with_capacity(records.len());
+    let mut count = 0;
+    for record in records.iter() {
+        offsets.push(count);
+        count += record.subroutines.len();
+    }
+    if count > SUBROUTINE_LIMIT {
+        raise!("found too many local subroutines ({count})");
+    }
+    if records
+        .iter()
+        .filter(|record| !record.subroutines.is_empty())
+        .count()
+        <= 1
+    {
+        let subroutines = records
+            .iter()
+            .find(|record| !record.subroutines.is_empty())
+            .map(|record| record.subroutines.clone())
+            .unwrap_or_default();
+        return Ok((character_strings.to_vec(), subroutines));
+    }
+    let rewrite = |tokens: &[Token], j: usize| -> Result<Vec<u8>> {
+        type2::renumber(tokens, |operator, value| match operator {
+            type2::Operator::CallSubr => {
+                let k = value as i32 + bias(records[j].subroutines.len());
+                Ok((offsets[j] as i32 + k - bias(count)) as type2::Operand)
+            }
+            _ => Ok(value),
+        })
+    };
+    let mut global_calls: Vec<Option<(Call, usize)>> = vec![None; global.len()];
+    let mut local_calls = records
+        .iter()
+        .map(|record| vec![None; record.subroutines.len()])
+        .collect::<Vec<_>>();
+    let mut data = Vec::with_capacity(character_strings.len());
+    for (code, &j) in character_strings.iter().zip(dictionaries) {
+        let local = &records[j].subroutines;
+        for call in type2::trace(code, global, local)? {
+            match call.operator {
+                type2::Operator::CallGSubr => {
+                    global_calls[call.index].get_or_insert((call, j));
+                }
+                _ => {
+                    local_calls[j][call.index].get_or_insert(call);
+                }
+            }
+        }
+        data.push(rewrite(&type2::tokenize(code, global, local)?, j)?);
+    }
+    for (k, call) in global_calls.iter().enumerate() {
+        if let Some((call, j)) = call {
+            let local = &records[*j].subroutines;
+            let tokens = type2::tokenize_subroutine(&global[k], global, local, call)?;
+            if tokens.contains(&Token::Operator(type2::Operator::CallSubr)) {
+                raise!("found a global subroutine calling local ones ({k})");
+            }
+        }
+    }
+    let mut subroutines = Vec::with_capacity(count);
+    for (j, record) in records.iter().enumerate() {
+        for (k, code) in record.subroutines.iter().enumerate() {
+            subroutines.push(match local_calls[j][k] {
+                Some(call) => rewrite(
+                    &type2::tokenize_subroutine(code, global, &record.subroutines, &call)?,
+                    j,
+                )?,
+                _ => code.clone(),
+            });
+        }
+    }
+    Ok((data, Subroutines(subroutines.into())))
+}
@@ -0,0 +1,106 @@
+//! The decomposition of accented characters in font sets.
+
+use std::collections::HashMap;
+
+use crate::compact1::font_set::{FontSet, Record};
+use crate::compact1::{Encoding, GlyphID, StringID};
+use crate::type2::{self, Operand, Program};
+use crate::Result;
+
+impl FontSet {
+    /// Decompose the accented characters of a character-name-keyed font.
+    ///
+    /// Glyphs composed by `endchar` in the manner of `seac` are replaced with
+    /// the outlines of their components, keeping their widths but not their
+    /// hints. Character-ID-keyed fonts are left intact.
+    pub fn decompose(&mut self, index: usize) -> Result<()> {
+        let local = match self.records.get(index) {
+            Some(Record::CharacterNameKeyed(record)) => &record.subroutines,
+            Some(_) => return Ok(()),
+            _ => raise!("found no font with index {index}"),
+        };
+        let global = &self.subroutines;
+        let character_strings = &self.character_strings[index];
+        let mut glyphs: Option<HashMap<StringID, GlyphID>> = None;
+        let mut data = vec![];
+        for (glyph_id, code) in character_strings.iter().enumerate() {
+            let operands = super::synthesize::accent(&type2::desubroutinize(code, global, local)?)?;
+            if operands.len() < 4 {
+                continue;
+            }
+            let glyphs = glyphs.get_or_insert_with(|| {
+                let character_set = &self.character_sets[index];
+                (0..character_strings.len() as GlyphID)
+                    .filter_map(|glyph_id| Some((character_set.get_string_id(glyph_id)?, glyph_id)))
+                    .collect()
+            });
+            let find = |code: Operand| -> Result<&[u8]> {
+                let glyph_id = Some(code)
+                    .filter(|code| code.fract() == 0.0 && (0.0..=255.0).contains(code))
+                    .and_then(|code| Encoding::Standard.get(code as GlyphID))
+                    .filter(|&string_id| string_id > 0)
+                    .and_then(|string_id| glyphs.get(&string_id));
+                match glyph_id {
+                    Some(&glyph_id) => Ok(&character_strings[glyph_id as usize]),
+                    _ => raise!("found no glyph for an accent component ({code})"),
+                }
+            };
+            let (mut operations, width) = outline(code, global, local)?;
+            let (base, _) = outline(find(operands[2])?, global, local)?;
+            append(&mut operations, base, (0.0, 0.0))?;
+            let (accent, _) = outline(find(operands[3])?, global, local)?;
+            append(&mut operations, accent, (operands[0], operands[1]))?;
+            operations.push((type2::Operator::EndChar, vec![]));
+            data.push((
+                glyph_id,
+                type2::encode(&type2::specialize(&operations)?, width)?,
+            ));
+        }
+        for (glyph_id, code) in data {
+            self.character_strings[index].replace(glyph_id, code);
+        }
+        Ok(())
+    }
+}
+
+fn outline(
+    code: &[u8],
+    global: &[Vec<u8>],
+    local: &[Vec<u8>],
+) -> Result<(type2::Operations, Option<Operand>)> {
+    use crate::type2::Operator::*;
+
+    let mut program = Program::new(code, global, local);
+    let mut operations = vec![];
+    while let Some(operation) = program.next()? {
+        operations.push(operation);
+    }
+    let operations = type2::generalize(&operations)?
+        .into_iter()
+        .filter(|(operator, _)| matches!(operator, RMoveTo | RLineTo | RRCurveTo))
+        .collect();
+    Ok((operations, program.width()))
+}
+
+fn append(
+    operations: &mut type2::Operations,
+    mut other: type2::Operations,
+    origin: (Operand, Operand),
+) -> Result<()> {
+    let mut position = (0.0, 0.0);
+    for (_, operands) in operations.iter() {
+        for chunk in operands.chunks_exact(2) {
+            position = (position.0 + chunk[0], position.1 + chunk[1]);
+        }
+    }
+    match other.first_mut() {
+        Some((type2::Operator::RMoveTo, operands)) => {
+            operands[0] += origin.0 - position.0;
+            operands[1] += origin.1 - position.1;
+        }
+        Some(_) => raise!("found a contour without a starting point"),
+        _ => {}
+    }
+    operations.extend(other);
+    Ok(())
+}
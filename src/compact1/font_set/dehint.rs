@@ -0,0 +1,68 @@
+//! The removal of hints from font sets.
+
+use crate::compact1::font_set::{FontSet, Record};
+use crate::compact1::Operator;
+use crate::type2::Edit;
+use crate::Result;
+
+const PRIVATE_OPERATORS: [Operator; 14] = [
+    Operator::BlueValues,
+    Operator::OtherBlues,
+    Operator::FamilyBlues,
+    Operator::FamilyOtherBlues,
+    Operator::BlueScale,
+    Operator::BlueShift,
+    Operator::BlueFuzz,
+    Operator::StdHW,
+    Operator::StdVW,
+    Operator::StemSnapH,
+    Operator::StemSnapV,
+    Operator::ForceBold,
+    Operator::LanguageGroup,
+    Operator::ExpansionFactor,
+];
+
+impl FontSet {
+    /// Remove the hints of all glyphs and subroutines.
+    ///
+    /// Stem hints and hint and counter masks are removed along with their
+    /// operands, including those given in subroutines, and the widths of the
+    /// glyphs are retained. If `private` is set, the hint-related operations
+    /// of the private dictionaries are removed as well.
+    pub fn dehint(&mut self, private: bool) -> Result<()> {
+        self.rewrite(|operator, operands, cleared| {
+            use crate::type2::Operator::*;
+
+            Ok(match operator {
+                HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask | DotSection => {
+                    let mut edits = vec![Edit::Delete; operands.len()];
+                    if operator != DotSection && !cleared && operands.len() % 2 == 1 {
+                        edits[0] = Edit::Keep;
+                    }
+                    (edits, Edit::Delete)
+                }
+                _ => (vec![Edit::Keep; operands.len()], Edit::Keep),
+            })
+        })?;
+        if !private {
+            return Ok(());
+        }
+        for record in self.records.iter_mut() {
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        record
+                            .operations
+                            .retain(|operator, _| !PRIVATE_OPERATORS.contains(operator));
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    record
+                        .operations
+                        .retain(|operator, _| !PRIVATE_OPERATORS.contains(operator));
+                }
+            }
+        }
+        Ok(())
+    }
+}
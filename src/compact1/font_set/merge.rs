@@ -0,0 +1,294 @@
+//! The merging of font sets.
+
+use crate::compact1::font_set::{
+    character_id_keyed, font_matrix, insert_font_matrix, map_strings, same_font_matrix, FontSet,
+    Record,
+};
+use crate::compact1::index::{CharacterStrings, Names, Strings, Subroutines};
+use crate::compact1::{
+    CharacterSet, Encoding, GlyphID, Header, Number, Operations, Operator, StringID,
+};
+use crate::type2::{self, bias, Call, Token};
+use crate::Result;
+
+const GLYPH_LIMIT: usize = 65535;
+const SUBROUTINE_LIMIT: usize = 65535;
+const DICTIONARY_LIMIT: usize = 256;
+
+impl FontSet {
+    /// Create a character-ID-keyed font set by merging fonts.
+    ///
+    /// The glyphs of each font follow those of the previous ones, and only the
+    /// `.notdef` of the first font is retained. Each private dictionary
+    /// becomes a separate font dictionary along with the subroutines the
+    /// glyphs use. Glyphs of character-ID-keyed fonts keep their character
+    /// identifiers unless taken, and the other glyphs take their glyph
+    /// identifiers unless taken, falling back to the next free identifier.
+    /// Accented characters of character-name-keyed fonts are decomposed. The
+    /// top dictionary is based on the one of the first font and carries the
+    /// font matrix, which is required to be the same for all glyphs.
+    pub fn merge(name: &str, fonts: &[(&FontSet, usize)]) -> Result<FontSet> {
+        if fonts.is_empty() {
+            raise!("found no fonts to merge");
+        }
+        let mut fonts = fonts
+            .iter()
+            .map(|&(font_set, index)| {
+                let glyph_ids = match font_set.character_strings.get(index) {
+                    Some(character_strings) => 0..character_strings.len() as GlyphID,
+                    _ => raise!("found no font with index {index}"),
+                };
+                font_set.subset(index, &glyph_ids.collect::<Vec<_>>(), false)
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let mut matrices = vec![];
+        for font_set in fonts.iter_mut() {
+            let top = &font_set.operations[0];
+            match &font_set.records[0] {
+                Record::CharacterIDKeyed(record) if !record.operations.is_empty() => {
+                    for operations in record.operations.iter() {
+                        matrices.push(font_matrix(top, Some(operations))?);
+                    }
+                }
+                _ => matrices.push(font_matrix(top, None)?),
+            }
+            font_set.decompose(0)?;
+        }
+        if matrices
+            .iter()
+            .any(|matrix| !same_font_matrix(matrix, &matrices[0]))
+        {
+            raise!("found fonts with different font matrices");
+        }
+        let global_count = fonts
+            .iter()
+            .map(|font_set| font_set.subroutines.len())
+            .sum();
+        if global_count > SUBROUTINE_LIMIT {
+            raise!("found too many global subroutines ({global_count})");
+        }
+
+        let mut strings = Strings::default();
+        let mut global = Vec::with_capacity(global_count);
+        let mut operations = vec![];
+        let mut records = vec![];
+        let mut character_strings = vec![];
+        let mut string_ids: Vec<StringID> = vec![];
+        let mut dictionary_ids = vec![];
+        let mut taken = vec![];
+        for (i, font_set) in fonts.iter().enumerate() {
+            let mut map = |string_id: StringID| -> Result<StringID> {
+                match font_set.strings.get(string_id) {
+                    Some(value) => Ok(strings.insert(&value)),
+                    _ => raise!("found no string with identifier {string_id}"),
+                }
+            };
+            let (locals, dictionaries, keyed) = match &font_set.records[0] {
+                Record::CharacterIDKeyed(record) => {
+                    for other in record.operations.iter() {
+                        let mut other = map_strings(other, &mut map)?;
+                        other.remove(&Operator::FontMatrix);
+                        operations.push(other);
+                    }
+                    let dictionaries = (0..font_set.character_strings[0].len())
+                        .map(|glyph_id| match record.encoding.get(glyph_id as GlyphID) {
+                            Some(j) if (j as usize) < record.records.len() => Ok(j as usize),
+                            _ => raise!("found no dictionary for glyph {glyph_id}"),
+                        })
+                        .collect::<Result<Vec<_>>>()?;
+                    (record.records.clone(), dictionaries, true)
+                }
+                Record::CharacterNameKeyed(record) => {
+                    let string_id = strings.insert(&String::from_utf8_lossy(&font_set.names[0]));
+                    let mut other = Operations::default();
+                    other.insert(Operator::FontName, vec![Number::Integer(string_id as i32)]);
+                    operations.push(other);
+                    let record = character_id_keyed::RecordInner {
+                        operations: record.operations.clone(),
+                        subroutines: record.subroutines.clone(),
+                    };
+                    let dictionaries = vec![0; font_set.character_strings[0].len()];
+                    (vec![record], dictionaries, false)
+                }
+            };
+            let dictionary_offset = records.len();
+            let global_offset = global.len();
+            let rewrite = |tokens: &[Token]| -> Result<Vec<u8>> {
+                type2::renumber(tokens, |operator, value| match operator {
+                    type2::Operator::CallGSubr => {
+                        let k = value as i32 + bias(font_set.subroutines.len());
+                        Ok((global_offset as i32 + k - bias(global_count)) as type2::Operand)
+                    }
+                    _ => Ok(value),
+                })
+            };
+
+            let mut global_calls: Vec<Option<(Call, usize)>> =
+                vec![None; font_set.subroutines.len()];
+            let mut local_calls = locals
+                .iter()
+                .map(|record| vec![None; record.subroutines.len()])
+                .collect::<Vec<_>>();
+            for (glyph_id, code) in font_set.character_strings[0].iter().enumerate() {
+                let j = dictionaries[glyph_id];
+                let local = &locals[j].subroutines;
+                for call in type2::trace(code, &font_set.subroutines, local)? {
+                    match call.operator {
+                        type2::Operator::CallGSubr => {
+                            global_calls[call.index].get_or_insert((call, j));
+                        }
+                        _ => {
+                            local_calls[j][call.index].get_or_insert(call);
+                        }
+                    }
+                }
+            }
+            for (k, code) in font_set.subroutines.iter().enumerate() {
+                global.push(match global_calls[k] {
+                    Some((call, j)) => rewrite(&type2::tokenize_subroutine(
+                        code,
+                        &font_set.subroutines,
+                        &locals[j].subroutines,
+                        &call,
+                    )?)?,
+                    _ => code.clone(),
+                });
+            }
+            let mut new_records = vec![];
+            for (j, record) in locals.iter().enumerate() {
+                let mut local = Vec::with_capacity(record.subroutines.len());
+                for (k, code) in record.subroutines.iter().enumerate() {
+                    local.push(match local_calls[j][k] {
+                        Some(call) => rewrite(&type2::tokenize_subroutine(
+                            code,
+                            &font_set.subroutines,
+                            &record.subroutines,
+                            &call,
+                        )?)?,
+                        _ => code.clone(),
+                    });
+                }
+                new_records.push(character_id_keyed::RecordInner {
+                    operations: record.operations.clone(),
+                    subroutines: Subroutines(local.into()),
+                });
+            }
+            records.extend(new_records);
+            if records.len() > DICTIONARY_LIMIT {
+                raise!("found too many dictionaries ({})", records.len());
+            }
+
+            for (glyph_id, code) in font_set.character_strings[0].iter().enumerate() {
+                if glyph_id == 0 && i > 0 {
+                    continue;
+                }
+                let j = dictionaries[glyph_id];
+                let tokens = type2::tokenize(code, &font_set.subroutines, &locals[j].subroutines)?;
+                let preferred = match keyed {
+                    true => match font_set.character_sets[0].get_string_id(glyph_id as GlyphID) {
+                        Some(string_id) => string_id as usize,
+                        _ => raise!("found no name for glyph {glyph_id}"),
+                    },
+                    _ => character_strings.len(),
+                };
+                let string_id = match taken.get(preferred) {
+                    Some(true) => taken.len(),
+                    _ => preferred,
+                };
+                if string_id >= taken.len() {
+                    taken.resize(string_id + 1, false);
+                }
+                taken[string_id] = true;
+                string_ids.push(string_id as StringID);
+                dictionary_ids.push((dictionary_offset + j) as u8);
+                character_strings.push(rewrite(&tokens)?);
+            }
+            if character_strings.len() > GLYPH_LIMIT {
+                raise!("found too many glyphs ({})", character_strings.len());
+            }
+        }
+
+        let mut top = Operations::default();
+        for (operator, operands) in fonts[0].operations[0].iter() {
+            match operator {
+                Operator::ROS
+                | Operator::CIDCount
+                | Operator::UniqueID
+                | Operator::XUID
+                | Operator::UIDBase
+                | Operator::FontMatrix => {}
+                _ if operator.is_offset() => {}
+                _ if operator.is_string() => {
+                    if let Some(Number::Integer(string_id)) = operands.first() {
+                        match fonts[0].strings.get(*string_id as StringID) {
+                            Some(value) => top.insert(
+                                *operator,
+                                vec![Number::Integer(strings.insert(&value) as i32)],
+                            ),
+                            _ => raise!("found no string with identifier {string_id}"),
+                        };
+                    }
+                }
+                _ => {
+                    top.insert(*operator, operands.clone());
+                }
+            }
+        }
+        let boxes = fonts
+            .iter()
+            .filter_map(|font_set| font_set.operations[0].get(Operator::FontBBox))
+            .filter(|operands| operands.len() == 4)
+            .collect::<Vec<_>>();
+        if let Some(first) = boxes.first() {
+            let mut bounds = first.to_vec();
+            for operands in boxes.iter().skip(1) {
+                for (k, (bound, &value)) in bounds.iter_mut().zip(operands.iter()).enumerate() {
+                    let (one, other) = (f32::from(*bound), f32::from(value));
+                    if (k < 2 && other < one) || (k >= 2 && other > one) {
+                        *bound = value;
+                    }
+                }
+            }
+            top.insert(Operator::FontBBox, bounds);
+        }
+        insert_font_matrix(&mut top, matrices[0])?;
+        let registry = strings.insert("Adobe");
+        let ordering = strings.insert("Identity");
+        let supplement = Number::Integer(0);
+        top.insert(
+            Operator::ROS,
+            vec![
+                Number::Integer(registry as i32),
+                Number::Integer(ordering as i32),
+                supplement,
+            ],
+        );
+        top.insert(
+            Operator::CIDCount,
+            vec![Number::Integer(taken.len() as i32)],
+        );
+        Ok(FontSet {
+            header: Header {
+                major: 1,
+                minor: 0,
+                header_size: 4,
+                offset_size: 4,
+            },
+            names: Names(vec![name.as_bytes().to_vec()].into()),
+            operations: vec![top],
+            strings,
+            subroutines: Subroutines(global.into()),
+            encodings: vec![Encoding::Standard],
+            character_strings: vec![CharacterStrings(character_strings.into())],
+            character_sets: vec![CharacterSet::new(&string_ids)],
+            records: vec![Record::CharacterIDKeyed(character_id_keyed::Record {
+                registry,
+                ordering,
+                supplement,
+                encoding: character_id_keyed::Encoding::new(&dictionary_ids),
+                operations,
+                records,
+            })],
+        })
+    }
+}
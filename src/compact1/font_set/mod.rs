@@ -27,10 +27,35 @@ macro_rules! get(
 pub mod character_id_keyed;
 pub mod character_name_keyed;
 
-use crate::compact1::index::{CharacterStrings, Dictionaries, Names, Strings, Subroutines};
-use crate::compact1::{CharacterSet, Encoding, Header, Operations, Operator};
+mod builder;
+mod convert;
+mod decompose;
+mod dehint;
+mod merge;
+mod optimize;
+mod scale;
+mod subset;
+mod synthesize;
+mod width;
+
+pub use builder::Builder;
+
+use std::collections::HashMap;
+
+use crate::compact1::error::{Locate, Recover};
+use crate::compact1::index::{CharacterStrings, Dictionaries, Index, Names, Strings, Subroutines};
+use crate::compact1::offset::minimal_size;
+use crate::compact1::{
+    CharacterSet, Encoding, ErrorWithLocation, GlyphID, Header, Location, Number, Operations,
+    Operator, StringID,
+};
 use crate::Result;
 
+const NAME_LIMIT: usize = 127;
+const STRING_LIMIT: usize = 65000 - 391;
+const CHARACTER_STRING_LIMIT: usize = 65535;
+const DICTIONARY_LIMIT: usize = 256;
+
 /// A font set.
 #[derive(Clone, Debug)]
 pub struct FontSet {
@@ -60,59 +85,607 @@ impl FontSet {
         let count: u16 = jump_take!(@unwrap tape, position, header.header_size);
         Ok(count as usize)
     }
+
+    /// Inline all subroutines into the character strings and remove the
+    /// subroutines.
+    pub fn desubroutinize(&mut self) -> Result<()> {
+        for (i, record) in self.records.iter_mut().enumerate() {
+            let mut data = Vec::with_capacity(self.character_strings[i].len());
+            for (glyph_id, code) in self.character_strings[i].iter().enumerate() {
+                let local = match record.subroutines(glyph_id as GlyphID) {
+                    Some(local) => local,
+                    _ => raise!("found no local subroutines for glyph {glyph_id}"),
+                };
+                data.push(crate::type2::desubroutinize(
+                    code,
+                    &self.subroutines,
+                    local,
+                )?);
+            }
+            self.character_strings[i] = CharacterStrings(data.into());
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        record.operations.remove(&Operator::Subrs);
+                        record.subroutines = Default::default();
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    record.operations.remove(&Operator::Subrs);
+                    record.subroutines = Default::default();
+                }
+            }
+        }
+        self.subroutines = Default::default();
+        Ok(())
+    }
+
+    /// Move repeated operations of the character strings into subroutines.
+    ///
+    /// Existing subroutines are inlined first. Operations shared by several
+    /// fonts or font dictionaries are moved into global subroutines, and the
+    /// rest into the local subroutines of the corresponding dictionaries.
+    pub fn subroutinize(&mut self) -> Result<()> {
+        self.desubroutinize()?;
+        let mut groups = vec![];
+        let mut assignments = vec![];
+        for (i, record) in self.records.iter().enumerate() {
+            let offset = groups.len();
+            let count = match record {
+                Record::CharacterIDKeyed(record) => record.records.len(),
+                Record::CharacterNameKeyed(_) => 1,
+            };
+            groups.extend((0..count).map(|_| vec![]));
+            let mut assignment = Vec::with_capacity(self.character_strings[i].len());
+            for (glyph_id, code) in self.character_strings[i].iter().enumerate() {
+                let j = match record {
+                    Record::CharacterIDKeyed(record) => {
+                        match record.encoding.get(glyph_id as GlyphID) {
+                            Some(j) if (j as usize) < count => j as usize,
+                            _ => raise!("found no dictionary for glyph {glyph_id}"),
+                        }
+                    }
+                    Record::CharacterNameKeyed(_) => 0,
+                };
+                groups[offset + j].push(&code[..]);
+                assignment.push(offset + j);
+            }
+            assignments.push(assignment);
+        }
+        let crate::type2::Subroutinized {
+            character_strings,
+            global,
+            locals,
+        } = crate::type2::subroutinize(&groups)?;
+        let mut character_strings = character_strings
+            .into_iter()
+            .map(Vec::into_iter)
+            .collect::<Vec<_>>();
+        let mut locals = locals.into_iter();
+        for (i, record) in self.records.iter_mut().enumerate() {
+            let data = assignments[i]
+                .iter()
+                .map(|&group| character_strings[group].next().unwrap_or_default())
+                .collect::<Vec<_>>();
+            self.character_strings[i] = CharacterStrings(data.into());
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        record.subroutines = Subroutines(locals.next().unwrap_or_default().into());
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    record.subroutines = Subroutines(locals.next().unwrap_or_default().into());
+                }
+            }
+        }
+        self.subroutines = Subroutines(global.into());
+        Ok(())
+    }
+}
+
+impl FontSet {
+    fn remove_unused_strings(&mut self) -> Result<()> {
+        let mut used = std::collections::BTreeSet::new();
+        self.map_strings(|string_id| {
+            used.insert(string_id);
+            Ok(string_id)
+        })?;
+        let mut strings = Strings::default();
+        let mut mapping = HashMap::new();
+        for string_id in used.into_iter().filter(|&string_id| string_id >= 391) {
+            match self.strings.get(string_id) {
+                Some(value) => mapping.insert(string_id, strings.insert(&value)),
+                _ => raise!("found no string with identifier {string_id}"),
+            };
+        }
+        self.map_strings(|string_id| Ok(*mapping.get(&string_id).unwrap_or(&string_id)))?;
+        self.strings = strings;
+        Ok(())
+    }
+
+    fn rewrite<F>(&mut self, edit: F) -> Result<()>
+    where
+        F: FnMut(
+            crate::type2::Operator,
+            &[crate::type2::Operand],
+            bool,
+        ) -> Result<(Vec<crate::type2::Edit>, crate::type2::Edit)>,
+    {
+        let mut locals = vec![];
+        let mut character_strings = vec![];
+        for (i, record) in self.records.iter().enumerate() {
+            let offset = locals.len();
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    locals.extend(record.records.iter().map(|record| &record.subroutines[..]))
+                }
+                Record::CharacterNameKeyed(record) => locals.push(&record.subroutines[..]),
+            }
+            for (glyph_id, code) in self.character_strings[i].iter().enumerate() {
+                let j = match record {
+                    Record::CharacterIDKeyed(record) => {
+                        match record.encoding.get(glyph_id as GlyphID) {
+                            Some(j) if (j as usize) < record.records.len() => j as usize,
+                            _ => raise!("found no dictionary for glyph {glyph_id}"),
+                        }
+                    }
+                    Record::CharacterNameKeyed(_) => 0,
+                };
+                character_strings.push((&code[..], offset + j));
+            }
+        }
+        let crate::type2::Rewritten {
+            character_strings,
+            global,
+            locals,
+        } = crate::type2::rewrite(&character_strings, &self.subroutines, &locals, edit)?;
+        let mut character_strings = character_strings.into_iter();
+        let mut locals = locals.into_iter();
+        for (i, record) in self.records.iter_mut().enumerate() {
+            let data = character_strings
+                .by_ref()
+                .take(self.character_strings[i].len())
+                .collect::<Vec<_>>();
+            self.character_strings[i] = CharacterStrings(data.into());
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        record.subroutines = Subroutines(locals.next().unwrap_or_default().into());
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    record.subroutines = Subroutines(locals.next().unwrap_or_default().into());
+                }
+            }
+        }
+        self.subroutines = Subroutines(global.into());
+        Ok(())
+    }
+
+    fn map_strings<F>(&mut self, mut map: F) -> Result<()>
+    where
+        F: FnMut(StringID) -> Result<StringID>,
+    {
+        for (i, record) in self.records.iter_mut().enumerate() {
+            self.operations[i] = map_strings(&self.operations[i], &mut map)?;
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    record.registry = map(record.registry)?;
+                    record.ordering = map(record.ordering)?;
+                    for operations in record.operations.iter_mut() {
+                        *operations = map_strings(operations, &mut map)?;
+                    }
+                }
+                Record::CharacterNameKeyed(_) => {
+                    let character_set = &self.character_sets[i];
+                    let mut string_ids = Vec::with_capacity(self.character_strings[i].len());
+                    for glyph_id in 0..self.character_strings[i].len() {
+                        match character_set.get_string_id(glyph_id as GlyphID) {
+                            Some(string_id) => string_ids.push(string_id),
+                            _ => raise!("found no name for glyph {glyph_id}"),
+                        }
+                    }
+                    let other = string_ids
+                        .iter()
+                        .map(|&string_id| map(string_id))
+                        .collect::<Result<Vec<_>>>()?;
+                    if other != string_ids {
+                        self.character_sets[i] = CharacterSet::predefined(&other)
+                            .unwrap_or_else(|| CharacterSet::new(&other));
+                    }
+                    if let Encoding::FormatSupplemental(ref mut encoding) = self.encodings[i] {
+                        for supplement in encoding.supplements.iter_mut() {
+                            supplement.glyph = map(supplement.glyph)?;
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Record {
+    /// Return the local subroutines of a glyph.
+    pub fn subroutines(&self, glyph_id: GlyphID) -> Option<&Subroutines> {
+        match self {
+            Record::CharacterIDKeyed(record) => record
+                .encoding
+                .get(glyph_id)
+                .and_then(|i| record.records.get(i as usize))
+                .map(|record| &record.subroutines),
+            Record::CharacterNameKeyed(record) => Some(&record.subroutines),
+        }
+    }
 }
 
 impl crate::value::Read for FontSet {
+    #[inline]
     fn read<T: crate::tape::Read>(tape: &mut T) -> Result<Self> {
-        let position = tape.position()?;
-        let header = tape.take::<Header>()?;
-        let names: Names = jump_take!(@unwrap tape, position, header.header_size);
-        let operations: Vec<_> = (&tape.take::<Dictionaries>()?).try_into()?;
-        let strings = tape.take::<Strings>()?;
-        let subroutines = tape.take::<Subroutines>()?;
-        let mut encodings = vec![];
-        let mut character_sets = vec![];
-        let mut character_strings: Vec<CharacterStrings> = vec![];
-        let mut records = vec![];
-        for (i, operations) in operations.iter().enumerate() {
-            character_strings.push(jump_take_given!(
-                @unwrap
-                tape,
-                position,
-                get!(@single operations, CharStrings),
-                get!(@single operations, CharStringType)
-            ));
-            character_sets.push(match get!(@single operations, CharSet) {
-                0 => CharacterSet::ISOAdobe,
-                1 => CharacterSet::Expert,
-                2 => CharacterSet::ExpertSubset,
-                offset => jump_take_given!(
-                    @unwrap
-                    tape,
-                    position,
-                    offset,
-                    character_strings[i].count as usize
-                ),
-            });
-            encodings.push(match get!(@single operations, Encoding) {
-                0 => Encoding::Standard,
-                1 => Encoding::Expert,
-                offset => jump_take!(@unwrap tape, position, offset),
-            });
-            records.push(tape.take_given((position, operations, &character_strings[i]))?);
+        read(tape, None)
+    }
+}
+
+impl crate::value::Write for FontSet {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        self.check()?;
+        let names = encode(&self.names)?;
+        let strings = encode(&self.strings)?;
+        let subroutines = encode(&self.subroutines)?;
+        let fonts = (0..self.records.len())
+            .map(|i| Font::new(self, i))
+            .collect::<Result<Vec<_>>>()?;
+        let mut positions = vec![0; fonts.len()];
+        let (top, tails) = loop {
+            let mut operations = Vec::with_capacity(fonts.len());
+            let mut tails = Vec::with_capacity(fonts.len());
+            for (i, font) in fonts.iter().enumerate() {
+                let (top, tail) = font.lay_out(&self.operations[i], positions[i])?;
+                operations.push(encode(&top)?);
+                tails.push(tail);
+            }
+            let top = encode(&Dictionaries(Index::from(operations)))?;
+            let mut position = 4 + names.len() + top.len() + strings.len() + subroutines.len();
+            let mut stable = true;
+            for (i, tail) in tails.iter().enumerate() {
+                stable &= positions[i] == position;
+                positions[i] = position;
+                position += tail.len();
+            }
+            if stable {
+                break (top, tails);
+            }
+        };
+        let size = positions.last().copied().unwrap_or(0) + tails.last().map_or(0, Vec::len);
+        tape.give(&Header {
+            major: self.header.major,
+            minor: self.header.minor,
+            header_size: 4,
+            offset_size: minimal_size(offset(size)? as u32),
+        })?;
+        tape.give_bytes(&names)?;
+        tape.give_bytes(&top)?;
+        tape.give_bytes(&strings)?;
+        tape.give_bytes(&subroutines)?;
+        for tail in tails {
+            tape.give_bytes(&tail)?;
+        }
+        Ok(())
+    }
+}
+
+impl FontSet {
+    fn check(&self) -> Result<()> {
+        let count = self.records.len();
+        if self.names.len() != count
+            || self.operations.len() != count
+            || self.encodings.len() != count
+            || self.character_strings.len() != count
+            || self.character_sets.len() != count
+        {
+            raise!("found an inconsistent number of fonts");
         }
+        for name in self.names.iter() {
+            if name.is_empty() || name.len() > NAME_LIMIT {
+                raise!("found a font name of invalid length ({})", name.len());
+            }
+        }
+        if self.strings.len() > STRING_LIMIT {
+            raise!("found too many strings ({})", self.strings.len());
+        }
+        for (i, record) in self.records.iter().enumerate() {
+            let character_strings = &self.character_strings[i];
+            let glyph_count = character_strings.len();
+            if glyph_count == 0 {
+                raise!("found a font without glyphs");
+            }
+            for code in character_strings.iter() {
+                if code.len() > CHARACTER_STRING_LIMIT {
+                    raise!(
+                        "found a character string of excessive size ({})",
+                        code.len()
+                    );
+                }
+            }
+            let last_glyph_id = (glyph_count - 1) as GlyphID;
+            match self.character_sets[i] {
+                CharacterSet::ISOAdobe | CharacterSet::Expert | CharacterSet::ExpertSubset => {}
+                CharacterSet::Missing => raise!("found a missing character set"),
+                ref character_set => {
+                    if character_set.get_string_id(last_glyph_id).is_none()
+                        || character_set.get_string_id(last_glyph_id + 1).is_some()
+                    {
+                        raise!("found a character set inconsistent with the glyphs");
+                    }
+                }
+            }
+            if let Record::CharacterIDKeyed(record) = record {
+                if record.records.is_empty() || record.records.len() > DICTIONARY_LIMIT {
+                    raise!(
+                        "found an invalid number of font dictionaries ({})",
+                        record.records.len(),
+                    );
+                }
+                if record.operations.len() != record.records.len() {
+                    raise!("found an inconsistent number of font dictionaries");
+                }
+                for glyph_id in 0..glyph_count {
+                    match record.encoding.get(glyph_id as GlyphID) {
+                        Some(j) if (j as usize) < record.records.len() => {}
+                        _ => raise!("found no dictionary for glyph {glyph_id}"),
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The encoded parts of a font that follow the top dictionaries.
+struct Font {
+    character_set: Option<Vec<u8>>,
+    encoding: Option<Vec<u8>>,
+    character_strings: Vec<u8>,
+    dictionaries: Option<(Vec<u8>, Vec<Operations>)>,
+    privates: Vec<(Vec<u8>, usize)>,
+    predefined: (i32, i32),
+}
+
+impl Font {
+    fn new(font_set: &FontSet, i: usize) -> Result<Self> {
+        let (character_set, character_set_id) = match font_set.character_sets[i] {
+            CharacterSet::ISOAdobe => (None, 0),
+            CharacterSet::Expert => (None, 1),
+            CharacterSet::ExpertSubset => (None, 2),
+            ref character_set => (Some(encode(character_set)?), 0),
+        };
+        let (encoding, encoding_id) = match font_set.encodings[i] {
+            Encoding::Standard => (None, 0),
+            Encoding::Expert => (None, 1),
+            ref encoding => (Some(encode(encoding)?), 0),
+        };
+        let (dictionaries, privates) = match &font_set.records[i] {
+            Record::CharacterIDKeyed(record) => {
+                let privates = record
+                    .records
+                    .iter()
+                    .map(|record| encode_private(&record.operations, &record.subroutines))
+                    .collect::<Result<Vec<_>>>()?;
+                (
+                    Some((encode(&record.encoding)?, record.operations.clone())),
+                    privates,
+                )
+            }
+            Record::CharacterNameKeyed(record) => (
+                None,
+                vec![encode_private(&record.operations, &record.subroutines)?],
+            ),
+        };
         Ok(Self {
-            header,
-            names,
-            operations,
-            strings,
-            subroutines,
-            encodings,
-            character_strings,
-            character_sets,
-            records,
+            character_set,
+            encoding,
+            character_strings: encode(&font_set.character_strings[i])?,
+            dictionaries,
+            privates,
+            predefined: (character_set_id, encoding_id),
         })
     }
+
+    /// Return the top operations and the encoded parts given the position of
+    /// the parts.
+    fn lay_out(&self, operations: &Operations, position: usize) -> Result<(Operations, Vec<u8>)> {
+        let mut operations = operations.clone();
+        let mut tail = vec![];
+        macro_rules! set(
+            ($operator:ident, $operands:expr) => ({
+                operations.insert(Operator::$operator, $operands);
+            });
+        );
+        let mut push = |chunk: &[u8]| -> Result<i32> {
+            let value = offset(position + tail.len())?;
+            tail.extend_from_slice(chunk);
+            Ok(value)
+        };
+        let (character_set_id, encoding_id) = self.predefined;
+        match self.character_set {
+            Some(ref chunk) => set!(CharSet, vec![Number::Integer(push(chunk)?)]),
+            _ if character_set_id == 0 => {
+                operations.remove(&Operator::CharSet);
+            }
+            _ => set!(CharSet, vec![Number::Integer(character_set_id)]),
+        }
+        match self.encoding {
+            Some(ref chunk) => set!(Encoding, vec![Number::Integer(push(chunk)?)]),
+            _ if encoding_id == 0 => {
+                operations.remove(&Operator::Encoding);
+            }
+            _ => set!(Encoding, vec![Number::Integer(encoding_id)]),
+        }
+        set!(
+            CharStrings,
+            vec![Number::Integer(push(&self.character_strings)?)]
+        );
+        let mut privates = Vec::with_capacity(self.privates.len());
+        for (chunk, size) in self.privates.iter() {
+            privates.push(vec![
+                Number::Integer(*size as i32),
+                Number::Integer(push(chunk)?),
+            ]);
+        }
+        match self.dictionaries {
+            Some((ref encoding, ref dictionaries)) => {
+                set!(FDSelect, vec![Number::Integer(push(encoding)?)]);
+                let mut data = Vec::with_capacity(dictionaries.len());
+                for (operations, operands) in dictionaries.iter().zip(privates) {
+                    let mut operations = operations.clone();
+                    operations.insert(Operator::Private, operands);
+                    data.push(encode(&operations)?);
+                }
+                set!(
+                    FDArray,
+                    vec![Number::Integer(push(&encode(&Index::from(data))?)?)]
+                );
+                operations.remove(&Operator::Private);
+            }
+            _ => set!(Private, privates.pop().unwrap_or_default()),
+        }
+        Ok((operations, tail))
+    }
+}
+
+fn encode<T: crate::value::Write + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    use crate::tape::Write;
+
+    let mut data = vec![];
+    data.give(value)?;
+    Ok(data)
+}
+
+fn encode_private(operations: &Operations, subroutines: &Subroutines) -> Result<(Vec<u8>, usize)> {
+    let mut operations = operations.clone();
+    operations.remove(&Operator::Subrs);
+    let mut data = encode(&operations)?;
+    if !subroutines.is_empty() {
+        loop {
+            let size = data.len();
+            operations.insert(Operator::Subrs, vec![Number::Integer(size as i32)]);
+            data = encode(&operations)?;
+            if data.len() == size {
+                break;
+            }
+        }
+    }
+    let size = data.len();
+    data.extend(encode(subroutines)?);
+    Ok((data, size))
+}
+
+fn font_matrix(top: &Operations, dictionary: Option<&Operations>) -> Result<[f32; 6]> {
+    let matrix = |operands: Option<&[Number]>| -> Result<[f32; 6]> {
+        match operands {
+            Some(operands) if operands.len() == 6 => {
+                let mut matrix = [0.0; 6];
+                for (value, &operand) in matrix.iter_mut().zip(operands) {
+                    *value = f32::from(operand);
+                }
+                Ok(matrix)
+            }
+            _ => raise!("found a malformed font matrix"),
+        }
+    };
+    let one = matrix(
+        top.get(Operator::FontMatrix)
+            .or(Operator::FontMatrix.default()),
+    )?;
+    let other = match dictionary.and_then(|operations| operations.0.get(&Operator::FontMatrix)) {
+        Some(operands) => matrix(Some(operands))?,
+        _ => return Ok(one),
+    };
+    Ok([
+        other[0] * one[0] + other[1] * one[2],
+        other[0] * one[1] + other[1] * one[3],
+        other[2] * one[0] + other[3] * one[2],
+        other[2] * one[1] + other[3] * one[3],
+        other[4] * one[0] + other[5] * one[2] + one[4],
+        other[4] * one[1] + other[5] * one[3] + one[5],
+    ])
+}
+
+fn insert_font_matrix(operations: &mut Operations, matrix: [f32; 6]) -> Result<()> {
+    operations.remove(&Operator::FontMatrix);
+    if !same_font_matrix(&matrix, &font_matrix(operations, None)?) {
+        let operands = matrix
+            .iter()
+            .map(|&value| match value.fract() == 0.0 {
+                true => Number::Integer(value as i32),
+                _ => Number::Real(value),
+            })
+            .collect();
+        operations.insert(Operator::FontMatrix, operands);
+    }
+    Ok(())
+}
+
+fn same_font_matrix(one: &[f32; 6], other: &[f32; 6]) -> bool {
+    one.iter()
+        .zip(other)
+        .all(|(one, other)| (one - other).abs() <= 1e-6 * one.abs().max(other.abs()).max(1e-3))
+}
+
+fn map_strings<F>(operations: &Operations, map: &mut F) -> Result<Operations>
+where
+    F: FnMut(StringID) -> Result<StringID>,
+{
+    let mut operations = operations.clone();
+    for (operator, operands) in operations.iter_mut() {
+        let count = match operator {
+            Operator::ROS => 2,
+            _ if operator.is_string() => 1,
+            _ => continue,
+        };
+        for operand in operands.iter_mut().take(count) {
+            if let Number::Integer(value) = operand {
+                *value = map(*value as StringID)? as i32;
+            }
+        }
+    }
+    Ok(operations)
+}
+
+fn element_position(index: &Index, start: u64, k: usize) -> u64 {
+    let header = 3 + (index.count as u64 + 1) * index.offset_size as u64;
+    match index.offsets.get(k) {
+        Some(offset) => start + header + offset.0 as u64 - 1,
+        _ => start,
+    }
+}
+
+fn offset_position(position: u64, operations: &Operations, operator: Operator) -> Option<u64> {
+    match operations
+        .0
+        .get(&operator)
+        .and_then(|operands| operands.last())
+    {
+        Some(&Number::Integer(offset)) => position.checked_add_signed(offset as i64),
+        _ => None,
+    }
+}
+
+fn offsets(operations: &Operations) -> Result<[i32; 4]> {
+    Ok([
+        get!(@single operations, CharStrings),
+        get!(@single operations, CharStringType),
+        get!(@single operations, CharSet),
+        get!(@single operations, Encoding),
+    ])
+}
+
+fn offset(position: usize) -> Result<i32> {
+    match i32::try_from(position) {
+        Ok(value) => Ok(value),
+        _ => raise!("found an offset that is too large ({position})"),
+    }
 }
 
 impl<'l> crate::walue::Read<'l> for Record {
@@ -135,3 +708,279 @@ impl<'l> crate::walue::Read<'l> for Record {
         }
     }
 }
+
+/// Read a font set, replacing damaged parts and collecting their errors into
+/// `omissions` if given, or failing otherwise. Glyphs are interpreted only when
+/// collecting omissions.
+pub(crate) fn read<T: crate::tape::Read>(
+    tape: &mut T,
+    mut omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<FontSet> {
+    let position = tape.position()?;
+    let header = tape.take::<Header>().locate(Location::Header, position)?;
+    let start = position + header.header_size as u64;
+    tape.jump(start)?;
+    let names = tape.take::<Names>().locate(Location::Names, start)?;
+    let start = tape.position()?;
+    let dictionaries = tape
+        .take::<Dictionaries>()
+        .locate(Location::TopDictionaries, start)?;
+    let end = tape.position()?;
+    let strings = tape.take::<Strings>().locate(Location::Strings, end)?;
+    let subroutines_start = tape.position()?;
+    let subroutines = tape
+        .take::<Subroutines>()
+        .locate(Location::GlobalSubroutines, subroutines_start)
+        .recover(omissions.as_deref_mut(), Default::default)?;
+    let mut font_set = FontSet {
+        header,
+        names: Names(Index::default()),
+        operations: vec![],
+        strings,
+        subroutines,
+        encodings: vec![],
+        character_strings: vec![],
+        character_sets: vec![],
+        records: vec![],
+    };
+    let mut kept = vec![];
+    for i in 0..dictionaries.len() {
+        let font = read_font(
+            tape,
+            position,
+            &dictionaries,
+            end,
+            (&font_set.subroutines, subroutines_start),
+            i,
+            omissions.as_deref_mut(),
+        )
+        .map(Some)
+        .recover(omissions.as_deref_mut(), || None)?;
+        if let Some((operations, character_strings, character_set, encoding, record)) = font {
+            kept.push(names.get(i).cloned().unwrap_or_default());
+            font_set.operations.push(operations);
+            font_set.character_strings.push(character_strings);
+            font_set.character_sets.push(character_set);
+            font_set.encodings.push(encoding);
+            font_set.records.push(record);
+        }
+    }
+    font_set.names = if kept.len() == names.len() {
+        names
+    } else {
+        Names(kept.into())
+    };
+    Ok(font_set)
+}
+
+fn read_font<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    dictionaries: &Dictionaries,
+    end: u64,
+    global: (&Subroutines, u64),
+    i: usize,
+    mut omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<(Operations, CharacterStrings, CharacterSet, Encoding, Record)> {
+    let operations = dictionaries.decode(end, i, Location::Top(i))?;
+    let [character_strings_offset, character_string_type, character_set_offset, encoding_offset] =
+        offsets(&operations).locate(Location::Top(i), dictionaries.position(end, i))?;
+    let start = position + character_strings_offset as u64;
+    tape.jump(start)?;
+    let character_strings: CharacterStrings = tape
+        .take_given(character_string_type)
+        .locate(Location::CharacterStrings(i), start)?;
+    let character_strings_start = start;
+    let character_set = match character_set_offset {
+        0 => CharacterSet::ISOAdobe,
+        1 => CharacterSet::Expert,
+        2 => CharacterSet::ExpertSubset,
+        offset => {
+            let start = position + offset as u64;
+            tape.jump(start)?;
+            tape.take_given(character_strings.count as usize)
+                .locate(Location::CharacterSet(i), start)
+                .recover(omissions.as_deref_mut(), || CharacterSet::Missing)?
+        }
+    };
+    let encoding = match encoding_offset {
+        0 => Encoding::Standard,
+        1 => Encoding::Expert,
+        offset => {
+            let start = position + offset as u64;
+            tape.jump(start)?;
+            tape.take()
+                .locate(Location::Encoding(i), start)
+                .recover(omissions.as_deref_mut(), || Encoding::Missing)?
+        }
+    };
+    let record = if operations.contains_key(&Operator::ROS) {
+        character_id_keyed::read(
+            tape,
+            position,
+            &operations,
+            &character_strings,
+            i,
+            omissions.as_deref_mut(),
+        )
+        .map(Record::CharacterIDKeyed)
+    } else {
+        character_name_keyed::read(tape, position, &operations, i, omissions.as_deref_mut())
+            .map(Record::CharacterNameKeyed)
+    }
+    .locate(Location::Top(i), dictionaries.position(end, i))?;
+    if omissions.is_some() {
+        read_glyphs(
+            position,
+            &operations,
+            (&character_strings, character_strings_start),
+            global,
+            &record,
+            i,
+            omissions,
+        )?;
+    }
+    Ok((
+        operations,
+        character_strings,
+        character_set,
+        encoding,
+        record,
+    ))
+}
+
+fn read_glyphs(
+    position: u64,
+    top_operations: &Operations,
+    (character_strings, start): (&CharacterStrings, u64),
+    (global, global_start): (&Subroutines, u64),
+    record: &Record,
+    font: usize,
+    mut omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<()> {
+    let local_start = |top_operations: &Operations, operations: &Operations| {
+        offset_position(position, top_operations, Operator::Private)
+            .and_then(|start| offset_position(start, operations, Operator::Subrs))
+            .unwrap_or(position)
+    };
+    let locals = match record {
+        Record::CharacterIDKeyed(record) => record
+            .records
+            .iter()
+            .zip(&record.operations)
+            .map(|(inner, top_operations)| {
+                (
+                    &inner.subroutines,
+                    local_start(top_operations, &inner.operations),
+                )
+            })
+            .collect::<Vec<_>>(),
+        Record::CharacterNameKeyed(record) => vec![(
+            &record.subroutines,
+            local_start(top_operations, &record.operations),
+        )],
+    };
+    for (glyph_id, code) in character_strings.iter().enumerate() {
+        let (local, local_start) = match record {
+            Record::CharacterIDKeyed(record) => match record.encoding.get(glyph_id as GlyphID) {
+                Some(j) if (j as usize) < locals.len() => locals[j as usize],
+                _ => continue,
+            },
+            Record::CharacterNameKeyed(_) => locals[0],
+        };
+        let mut program = crate::type2::Program::new(code, global, local);
+        let result = loop {
+            match program.next() {
+                Ok(Some(_)) => {}
+                Ok(None) => break Ok(()),
+                Err(error) => break Err(error),
+            }
+        };
+        let offset = match result
+            .as_ref()
+            .err()
+            .and_then(crate::type2::ErrorWithLocation::find)
+        {
+            Some(error) => {
+                error.offset
+                    + match error.location {
+                        crate::type2::Location::CharacterString => {
+                            element_position(character_strings, start, glyph_id)
+                        }
+                        crate::type2::Location::GlobalSubroutine(k) => {
+                            element_position(global, global_start, k)
+                        }
+                        crate::type2::Location::LocalSubroutine(k) => {
+                            element_position(local, local_start, k)
+                        }
+                    }
+            }
+            _ => element_position(character_strings, start, glyph_id),
+        };
+        result
+            .locate(Location::Glyph(font, glyph_id as GlyphID), offset)
+            .recover(omissions.as_deref_mut(), || ())?;
+    }
+    Ok(())
+}
+
+fn read_private<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    top_operations: &Operations,
+    font: usize,
+    dictionary: usize,
+    mut omissions: Option<&mut Vec<ErrorWithLocation>>,
+) -> Result<(Operations, Subroutines)> {
+    let location = Location::Private(font, dictionary);
+    let (offset, mut operations) =
+        read_private_operations(tape, position, top_operations, location)
+            .locate(
+                location,
+                offset_position(position, top_operations, Operator::Private).unwrap_or(position),
+            )
+            .recover(omissions.as_deref_mut(), || (0, Default::default()))?;
+    let start = position + offset as u64;
+    let subroutines = read_local_subroutines(tape, start, &operations)
+        .locate(
+            Location::LocalSubroutines(font, dictionary),
+            offset_position(start, &operations, Operator::Subrs).unwrap_or(start),
+        )
+        .recover(omissions, || {
+            operations.remove(&Operator::Subrs);
+            Default::default()
+        })?;
+    Ok((operations, subroutines))
+}
+
+fn read_private_operations<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    top_operations: &Operations,
+    location: Location,
+) -> Result<(i32, Operations)> {
+    use crate::tape::Read;
+
+    let (size, offset) = get!(@double top_operations, Private);
+    let start = position + offset as u64;
+    tape.jump(start)?;
+    let chunk: Vec<u8> = tape.take_given(size as usize).locate(location, start)?;
+    let operations = std::io::Cursor::new(chunk)
+        .take::<Operations>()
+        .locate(location, start)?;
+    Ok((offset, operations))
+}
+
+fn read_local_subroutines<T: crate::tape::Read>(
+    tape: &mut T,
+    position: u64,
+    operations: &Operations,
+) -> Result<Subroutines> {
+    match get!(@try @single operations, Subrs) {
+        Some(offset) => {
+            tape.jump(position + offset as u64)?;
+            tape.take()
+        }
+        _ => Ok(Default::default()),
+    }
+}
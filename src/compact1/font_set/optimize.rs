@@ -0,0 +1,345 @@
+//! The optimization of font sets.
+
+use std::collections::{BTreeMap, HashMap};
+
+use crate::compact1::font_set::{character_id_keyed, FontSet, Record};
+use crate::compact1::index::{CharacterStrings, Subroutines};
+use crate::compact1::{CharacterSet, Encoding, GlyphID, Operations};
+use crate::type2::{self, bias, Call, Token};
+use crate::Result;
+
+const SUBROUTINE_LIMIT: usize = 65535;
+
+type Context = (usize, usize);
+
+impl FontSet {
+    /// Rewrite the font set into a smaller equivalent one.
+    ///
+    /// Unused strings and subroutines are removed; identical font
+    /// dictionaries and subroutines are merged; character strings shared by
+    /// several glyphs are moved into global subroutines when it saves space;
+    /// and the character sets, encodings, and glyph-to-dictionary encodings are
+    /// rebuilt in their most compact formats.
+    pub fn optimize(&mut self) -> Result<()> {
+        self.remove_unused_strings()?;
+        for i in 0..self.records.len() {
+            self.merge_dictionaries(i)?;
+            self.compact_tables(i)?;
+        }
+        self.compact_subroutines()
+    }
+
+    fn merge_dictionaries(&mut self, i: usize) -> Result<()> {
+        let record = match self.records[i] {
+            Record::CharacterIDKeyed(ref mut record) => record,
+            _ => return Ok(()),
+        };
+        let strip = |operations: &Operations| {
+            let mut operations = operations.clone();
+            operations.retain(|operator, _| !operator.is_offset());
+            operations
+        };
+        let mut mapping = Vec::with_capacity(record.records.len());
+        let mut operations: Vec<Operations> = vec![];
+        let mut records: Vec<character_id_keyed::RecordInner> = vec![];
+        for (j, inner) in record.records.iter().enumerate() {
+            let (one, other) = (strip(&record.operations[j]), strip(&inner.operations));
+            let position = (0..records.len()).find(|&k| {
+                operations[k].0 == one.0
+                    && strip(&records[k].operations).0 == other.0
+                    && records[k].subroutines.data == inner.subroutines.data
+            });
+            mapping.push(match position {
+                Some(k) => k,
+                _ => {
+                    operations.push(one);
+                    records.push(inner.clone());
+                    records.len() - 1
+                }
+            });
+        }
+        if records.len() == record.records.len() {
+            return Ok(());
+        }
+        let mut dictionary_ids = Vec::with_capacity(self.character_strings[i].len());
+        for glyph_id in 0..self.character_strings[i].len() {
+            match record.encoding.get(glyph_id as GlyphID) {
+                Some(j) if (j as usize) < mapping.len() => {
+                    dictionary_ids.push(mapping[j as usize] as u8)
+                }
+                _ => raise!("found no dictionary for glyph {glyph_id}"),
+            }
+        }
+        record.encoding = character_id_keyed::Encoding::new(&dictionary_ids);
+        record.operations = operations;
+        record.records = records;
+        Ok(())
+    }
+
+    fn compact_tables(&mut self, i: usize) -> Result<()> {
+        let glyph_count = self.character_strings[i].len();
+        let mut string_ids = Vec::with_capacity(glyph_count);
+        for glyph_id in 0..glyph_count {
+            match self.character_sets[i].get_string_id(glyph_id as GlyphID) {
+                Some(string_id) => string_ids.push(string_id),
+                _ => raise!("found no name for glyph {glyph_id}"),
+            }
+        }
+        match self.records[i] {
+            Record::CharacterIDKeyed(ref mut record) => {
+                let mut dictionary_ids = Vec::with_capacity(glyph_count);
+                for glyph_id in 0..glyph_count {
+                    match record.encoding.get(glyph_id as GlyphID) {
+                        Some(j) => dictionary_ids.push(j),
+                        _ => raise!("found no dictionary for glyph {glyph_id}"),
+                    }
+                }
+                record.encoding = character_id_keyed::Encoding::new(&dictionary_ids);
+                self.character_sets[i] = CharacterSet::new(&string_ids);
+            }
+            Record::CharacterNameKeyed(_) => {
+                self.character_sets[i] = CharacterSet::predefined(&string_ids)
+                    .unwrap_or_else(|| CharacterSet::new(&string_ids));
+                if let Some(codes) = self.encodings[i].codes() {
+                    let supplements = self.encodings[i].supplements().to_vec();
+                    self.encodings[i] = Encoding::new(&codes)?.with_supplements(supplements)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn compact_subroutines(&mut self) -> Result<()> {
+        let mut dictionaries = vec![];
+        let mut locals = HashMap::new();
+        for (i, record) in self.records.iter().enumerate() {
+            let mut values = Vec::with_capacity(self.character_strings[i].len());
+            for glyph_id in 0..self.character_strings[i].len() {
+                values.push(match record {
+                    Record::CharacterIDKeyed(record) => {
+                        match record.encoding.get(glyph_id as GlyphID) {
+                            Some(j) if (j as usize) < record.records.len() => j as usize,
+                            _ => raise!("found no dictionary for glyph {glyph_id}"),
+                        }
+                    }
+                    Record::CharacterNameKeyed(_) => 0,
+                });
+            }
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for (j, record) in record.records.iter().enumerate() {
+                        locals.insert((i, j), &record.subroutines[..]);
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    locals.insert((i, 0), &record.subroutines[..]);
+                }
+            }
+            dictionaries.push(values);
+        }
+
+        let global = &self.subroutines[..];
+        let mut global_calls: Vec<Option<(Call, Context)>> = vec![None; global.len()];
+        let mut local_calls = locals
+            .iter()
+            .map(|(&context, local)| (context, vec![None; local.len()]))
+            .collect::<HashMap<Context, Vec<Option<Call>>>>();
+        let mut glyph_tokens = Vec::with_capacity(self.character_strings.len());
+        let mut glyphs = HashMap::<(&[u8], Option<Context>), usize>::new();
+        let mut glyph_order = vec![];
+        for (i, character_strings) in self.character_strings.iter().enumerate() {
+            let mut values = Vec::with_capacity(character_strings.len());
+            for (glyph_id, code) in character_strings.iter().enumerate() {
+                let context = (i, dictionaries[i][glyph_id]);
+                let local = locals[&context];
+                for call in type2::trace(code, global, local)? {
+                    match call.operator {
+                        type2::Operator::CallGSubr => {
+                            global_calls[call.index].get_or_insert((call, context));
+                        }
+                        _ => {
+                            local_calls.get_mut(&context).unwrap()[call.index].get_or_insert(call);
+                        }
+                    }
+                }
+                let tokens = type2::tokenize(code, global, local)?;
+                let context = calls_local(&tokens).then_some(context);
+                let count = glyphs.entry((&code[..], context)).or_default();
+                if *count == 0 {
+                    glyph_order.push((&code[..], context));
+                }
+                *count += 1;
+                values.push(tokens);
+            }
+            glyph_tokens.push(values);
+        }
+        let mut tokens = BTreeMap::new();
+        let mut shared = false;
+        for (k, call) in global_calls.iter().enumerate() {
+            if let Some((call, context)) = call {
+                let value = type2::tokenize_subroutine(&global[k], global, locals[context], call)?;
+                shared |= locals.len() > 1 && calls_local(&value);
+                tokens.insert(k, value);
+            }
+        }
+
+        let mut global_mapping = Mapping::new(global.len());
+        for (k, call) in global_calls.iter().enumerate() {
+            if call.is_some() {
+                global_mapping.insert(k, &global[k]);
+            }
+        }
+        let mut local_mappings = HashMap::new();
+        for (&context, calls) in local_calls.iter() {
+            let local = locals[&context];
+            let mut mapping = Mapping::new(local.len());
+            for (k, call) in calls.iter().enumerate() {
+                if shared {
+                    mapping.indices[k] = Some(k);
+                    mapping.count += 1;
+                } else if call.is_some() {
+                    mapping.insert(k, &local[k]);
+                }
+            }
+            local_mappings.insert(context, mapping);
+        }
+        let mut bodies = HashMap::new();
+        let mut body_count = 0;
+        for &(code, context) in glyph_order.iter() {
+            let count = glyphs[&(code, context)];
+            if count > 1 && (count - 1) * code.len() > 3 * count + 2 {
+                bodies.insert((code, context), global_mapping.count + body_count);
+                body_count += 1;
+            }
+        }
+        let global_count = global_mapping.count + body_count;
+        if global_count > SUBROUTINE_LIMIT {
+            raise!("found too many global subroutines ({global_count})");
+        }
+
+        let rewrite = |tokens: &[Token], context: Context| -> Result<Vec<u8>> {
+            type2::renumber(tokens, |operator, value| match operator {
+                type2::Operator::CallGSubr => global_mapping.get(value, global_count),
+                _ => {
+                    let mapping = &local_mappings[&context];
+                    mapping.get(value, mapping.count)
+                }
+            })
+        };
+        let mut new_global = vec![vec![]; global_count];
+        for (k, value) in tokens.iter() {
+            let (_, context) = global_calls[*k].unwrap();
+            if let Some(l) = global_mapping.indices[*k] {
+                if new_global[l].is_empty() {
+                    new_global[l] = rewrite(value, context)?;
+                }
+            }
+        }
+        let mut new_locals = HashMap::new();
+        for (&context, calls) in local_calls.iter() {
+            let local = locals[&context];
+            let mapping = &local_mappings[&context];
+            let mut data = vec![vec![]; mapping.count];
+            for (k, code) in local.iter().enumerate() {
+                let l = match mapping.indices[k] {
+                    Some(l) if data[l].is_empty() => l,
+                    _ => continue,
+                };
+                data[l] = match calls[k] {
+                    Some(ref call) => rewrite(
+                        &type2::tokenize_subroutine(code, global, local, call)?,
+                        context,
+                    )?,
+                    _ => code.clone(),
+                };
+            }
+            new_locals.insert(context, data);
+        }
+        let mut new_character_strings = Vec::with_capacity(self.character_strings.len());
+        for (i, character_strings) in self.character_strings.iter().enumerate() {
+            let mut data = Vec::with_capacity(character_strings.len());
+            for (glyph_id, tokens) in glyph_tokens[i].iter().enumerate() {
+                let context = (i, dictionaries[i][glyph_id]);
+                let code = rewrite(tokens, context)?;
+                let key = (
+                    &character_strings[glyph_id][..],
+                    calls_local(tokens).then_some(context),
+                );
+                match bodies.get(&key) {
+                    Some(&l) => {
+                        if new_global[l].is_empty() {
+                            new_global[l] = code;
+                        }
+                        let value = (l as i32 - bias(global_count)) as type2::Operand;
+                        data.push(type2::renumber(
+                            &[
+                                Token::Operand(value),
+                                Token::Operator(type2::Operator::CallGSubr),
+                            ],
+                            |_, value| Ok(value),
+                        )?);
+                    }
+                    _ => data.push(code),
+                }
+            }
+            new_character_strings.push(CharacterStrings(data.into()));
+        }
+
+        for (i, record) in self.records.iter_mut().enumerate() {
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for (j, record) in record.records.iter_mut().enumerate() {
+                        let data = new_locals.remove(&(i, j)).unwrap_or_default();
+                        record.subroutines = Subroutines(data.into());
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    let data = new_locals.remove(&(i, 0)).unwrap_or_default();
+                    record.subroutines = Subroutines(data.into());
+                }
+            }
+        }
+        self.character_strings = new_character_strings;
+        self.subroutines = Subroutines(new_global.into());
+        Ok(())
+    }
+}
+
+fn calls_local(tokens: &[Token]) -> bool {
+    tokens.contains(&Token::Operator(type2::Operator::CallSubr))
+}
+
+/// A mapping of subroutine indices merging identical subroutines.
+struct Mapping<'l> {
+    indices: Vec<Option<usize>>,
+    bodies: HashMap<&'l [u8], usize>,
+    count: usize,
+    old_count: usize,
+}
+
+impl<'l> Mapping<'l> {
+    fn new(old_count: usize) -> Self {
+        Self {
+            indices: vec![None; old_count],
+            bodies: HashMap::new(),
+            count: 0,
+            old_count,
+        }
+    }
+
+    fn insert(&mut self, k: usize, code: &'l [u8]) {
+        let count = &mut self.count;
+        self.indices[k] = Some(*self.bodies.entry(code).or_insert_with(|| {
+            *count += 1;
+            *count - 1
+        }));
+    }
+
+    fn get(&self, value: type2::Operand, count: usize) -> Result<type2::Operand> {
+        let k = value as i32 + bias(self.old_count);
+        match usize::try_from(k).ok().and_then(|k| self.indices.get(k)) {
+            Some(Some(l)) => Ok((*l as i32 - bias(count)) as type2::Operand),
+            _ => raise!("found a call to a nonexistent subroutine ({value})"),
+        }
+    }
+}
@@ -0,0 +1,323 @@
+//! The scaling of font sets.
+
+use crate::compact1::font_set::{FontSet, Record};
+use crate::compact1::{Number, Operations, Operator};
+use crate::type2::{Edit, Operand};
+use crate::Result;
+
+const TOP_OPERATORS: [Operator; 4] = [
+    Operator::FontBBox,
+    Operator::UnderlinePosition,
+    Operator::UnderlineThickness,
+    Operator::StrokeWidth,
+];
+
+const PRIVATE_OPERATORS: [Operator; 6] = [
+    Operator::StdHW,
+    Operator::StdVW,
+    Operator::BlueShift,
+    Operator::BlueFuzz,
+    Operator::DefaultWidthX,
+    Operator::NominalWidthX,
+];
+
+const PRIVATE_DELTA_OPERATORS: [Operator; 6] = [
+    Operator::BlueValues,
+    Operator::OtherBlues,
+    Operator::FamilyBlues,
+    Operator::FamilyOtherBlues,
+    Operator::StemSnapH,
+    Operator::StemSnapV,
+];
+
+impl FontSet {
+    /// Change the number of units per em.
+    ///
+    /// The coordinates of the glyphs, their widths, and the metrics of the
+    /// top and private dictionaries are scaled, with integers rounded, and the
+    /// font matrix of the top dictionary is updated to match, while the font
+    /// matrices of font dictionaries, which are concatenated with it, are left
+    /// intact. Relative coordinates are rounded cumulatively, carrying the
+    /// remainder into the next delta so that contours close. Since the
+    /// rounding then depends on the context, subroutines are inlined before
+    /// scaling and recreated afterwards. All glyphs are required to have the
+    /// same number of units per em, since they share global subroutines.
+    pub fn rescale(&mut self, units_per_em: u16) -> Result<()> {
+        if units_per_em == 0 {
+            raise!("found an invalid number of units per em ({units_per_em})");
+        }
+        let mut scales = vec![];
+        for (i, record) in self.records.iter().enumerate() {
+            let top = matrix(&self.operations[i]);
+            match record {
+                Record::CharacterIDKeyed(record) if !record.operations.is_empty() => {
+                    for operations in record.operations.iter() {
+                        match operations.get(Operator::FontMatrix) {
+                            Some(_) => scales.push(top * matrix(operations)),
+                            _ => scales.push(top),
+                        }
+                    }
+                }
+                _ => scales.push(top),
+            }
+        }
+        let scale = match scales.first() {
+            Some(&scale) if scale > 0.0 => scale,
+            _ => raise!("found a malformed font matrix"),
+        };
+        if scales.iter().any(|&other| other != scale) {
+            raise!("found glyphs with different numbers of units per em");
+        }
+        let factor = units_per_em as Operand * scale;
+
+        let subroutinized = !self.subroutines.is_empty()
+            || self.records.iter().any(|record| match record {
+                Record::CharacterIDKeyed(record) => record
+                    .records
+                    .iter()
+                    .any(|record| !record.subroutines.is_empty()),
+                Record::CharacterNameKeyed(record) => !record.subroutines.is_empty(),
+            });
+        if subroutinized {
+            self.desubroutinize()?;
+        }
+        let mut exact = [0.0; 2];
+        let mut rounded = [0.0; 2];
+        self.rewrite(|operator, operands, cleared| {
+            if !cleared {
+                exact = [0.0; 2];
+                rounded = [0.0; 2];
+            }
+            let (axes, restored) = axes(operator, operands, cleared);
+            let saved = restored.map(|i| (i, exact[i], rounded[i]));
+            let mut stem = (0.0, 0.0);
+            let edits = operands
+                .iter()
+                .zip(axes)
+                .map(|(&value, axis)| {
+                    let (exact, rounded) = match axis {
+                        Axis::None => return Edit::Keep,
+                        Axis::Single => return Edit::Replace(round(value * factor, value)),
+                        Axis::Stem => (&mut stem.0, &mut stem.1),
+                        Axis::X => (&mut exact[0], &mut rounded[0]),
+                        Axis::Y => (&mut exact[1], &mut rounded[1]),
+                    };
+                    *exact += value;
+                    let next = round(*exact * factor, value);
+                    let edit = Edit::Replace(next - *rounded);
+                    *rounded = next;
+                    edit
+                })
+                .collect();
+            if let Some((i, one, other)) = saved {
+                (exact[i], rounded[i]) = (one, other);
+            }
+            Ok((edits, Edit::Keep))
+        })?;
+        if subroutinized {
+            self.subroutinize()?;
+        }
+        for (i, record) in self.records.iter_mut().enumerate() {
+            let operations = &mut self.operations[i];
+            let mut matrix = operations
+                .get(Operator::FontMatrix)
+                .or(Operator::FontMatrix.default())
+                .map(<[Number]>::to_vec)
+                .unwrap_or_default();
+            for operand in matrix.iter_mut().take(4) {
+                *operand = Number::Real(Operand::from(*operand) / scale / units_per_em as Operand);
+            }
+            operations.insert(Operator::FontMatrix, matrix);
+            scale_operations(operations, &TOP_OPERATORS, factor);
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        scale_private(&mut record.operations, factor);
+                    }
+                }
+                Record::CharacterNameKeyed(record) => scale_private(&mut record.operations, factor),
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Axis {
+    None,
+    Single,
+    Stem,
+    X,
+    Y,
+}
+
+/// Assign the operands of an operator to the axes along which they are
+/// accumulated, along with the axis to which the operator implicitly returns.
+fn axes(
+    operator: crate::type2::Operator,
+    operands: &[Operand],
+    cleared: bool,
+) -> (Vec<Axis>, Option<usize>) {
+    use crate::type2::Operator::*;
+
+    let count = operands.len();
+    let width = !cleared
+        && match operator {
+            HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask => count % 2 == 1,
+            RMoveTo => count > 2,
+            HMoveTo | VMoveTo => count > 1,
+            EndChar => count == 1 || count == 5,
+            _ => false,
+        };
+    let mut axes = Vec::with_capacity(count);
+    if width {
+        axes.push(Axis::Single);
+    }
+    let count = count - axes.len();
+    let alternate = |first: Axis, second: Axis| (0..count).map(move |i| [first, second][i % 2]);
+    let mut restored = None;
+    match operator {
+        HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask => {
+            axes.extend((0..count).map(|_| Axis::Stem))
+        }
+        RMoveTo | HMoveTo | RLineTo | HLineTo | RRCurveTo | RCurveLine | RLineCurve => {
+            axes.extend(alternate(Axis::X, Axis::Y))
+        }
+        VMoveTo | VLineTo => axes.extend(alternate(Axis::Y, Axis::X)),
+        HHCurveTo | VVCurveTo => {
+            let (along, across) = match operator {
+                HHCurveTo => (Axis::X, Axis::Y),
+                _ => (Axis::Y, Axis::X),
+            };
+            if count % 2 == 1 {
+                axes.push(across);
+            }
+            for _ in 0..count / 4 {
+                axes.extend([along, Axis::X, Axis::Y, along]);
+            }
+        }
+        HVCurveTo | VHCurveTo => {
+            let mut horizontal = operator == HVCurveTo;
+            for _ in 0..count / 4 {
+                match horizontal {
+                    true => axes.extend([Axis::X, Axis::X, Axis::Y, Axis::Y]),
+                    _ => axes.extend([Axis::Y, Axis::X, Axis::Y, Axis::X]),
+                }
+                horizontal = !horizontal;
+            }
+            if count % 4 == 1 {
+                axes.push(if horizontal { Axis::Y } else { Axis::X });
+            }
+        }
+        Flex if count == 13 => {
+            axes.extend(alternate(Axis::X, Axis::Y).take(12));
+            axes.push(Axis::None);
+        }
+        HFlex if count == 7 => {
+            axes.extend([
+                Axis::X,
+                Axis::X,
+                Axis::Y,
+                Axis::X,
+                Axis::X,
+                Axis::X,
+                Axis::X,
+            ]);
+            restored = Some(1);
+        }
+        HFlex1 if count == 9 => {
+            axes.extend(alternate(Axis::X, Axis::Y).take(4));
+            axes.extend([Axis::X, Axis::X, Axis::X, Axis::Y, Axis::X]);
+            restored = Some(1);
+        }
+        Flex1 if count == 11 => {
+            let offset = axes.len();
+            let sum = |start: usize| {
+                (start..10)
+                    .step_by(2)
+                    .map(|i| operands[offset + i])
+                    .sum::<Operand>()
+                    .abs()
+            };
+            axes.extend(alternate(Axis::X, Axis::Y).take(10));
+            if sum(0) > sum(1) {
+                axes.push(Axis::X);
+                restored = Some(1);
+            } else {
+                axes.push(Axis::Y);
+                restored = Some(0);
+            }
+        }
+        EndChar if count == 4 => axes.extend([Axis::Single, Axis::Single, Axis::None, Axis::None]),
+        _ => axes.extend((0..count).map(|_| Axis::Single)),
+    }
+    (axes, restored)
+}
+
+fn matrix(operations: &Operations) -> Operand {
+    match operations
+        .get(Operator::FontMatrix)
+        .or(Operator::FontMatrix.default())
+    {
+        Some(operands) if !operands.is_empty() => Operand::from(operands[0]),
+        _ => 0.0,
+    }
+}
+
+fn scale_private(operations: &mut Operations, factor: Operand) {
+    scale_operations(operations, &PRIVATE_OPERATORS, factor);
+    for operator in PRIVATE_DELTA_OPERATORS {
+        let operands = match operations.get(operator) {
+            Some(operands) => operands.to_vec(),
+            _ => continue,
+        };
+        let mut value = 0.0;
+        let mut previous = 0.0;
+        let mut other = Vec::with_capacity(operands.len());
+        for operand in operands {
+            value += Operand::from(operand);
+            let next = round(value * factor, Operand::from(operand));
+            other.push(number(next - previous, operand));
+            previous = next;
+        }
+        operations.insert(operator, other);
+    }
+    if let Some(value) = operations.get_single(Operator::BlueScale) {
+        operations.insert(
+            Operator::BlueScale,
+            vec![Number::Real(Operand::from(value) / factor)],
+        );
+    }
+}
+
+fn scale_operations(operations: &mut Operations, operators: &[Operator], factor: Operand) {
+    for &operator in operators {
+        if let Some(operands) = operations.get(operator) {
+            let operands = operands
+                .iter()
+                .map(|&operand| {
+                    let value = Operand::from(operand);
+                    number(round(value * factor, value), operand)
+                })
+                .collect();
+            operations.insert(operator, operands);
+        }
+    }
+}
+
+#[inline]
+fn round(value: Operand, original: Operand) -> Operand {
+    if original.fract() == 0.0 {
+        value.round()
+    } else {
+        value
+    }
+}
+
+#[inline]
+fn number(value: Operand, original: Number) -> Number {
+    match original {
+        Number::Integer(_) => Number::Integer(value as i32),
+        Number::Real(_) => Number::Real(value),
+    }
+}
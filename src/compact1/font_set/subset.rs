@@ -0,0 +1,340 @@
+//! The subsetting of font sets.
+
+use std::collections::{BTreeSet, HashMap};
+
+use crate::compact1::encoding::Supplement;
+use crate::compact1::font_set::{
+    character_id_keyed, character_name_keyed, map_strings, FontSet, Record,
+};
+use crate::compact1::index::{CharacterStrings, Names, Strings, Subroutines};
+use crate::compact1::{CharacterSet, Encoding, GlyphID, StringID};
+use crate::type2::{self, bias, Token};
+use crate::Result;
+
+const NUMBER_OF_STANDARD_STRINGS: StringID = 391;
+
+impl FontSet {
+    /// Create a font set with a subset of the glyphs of a font.
+    ///
+    /// The subset includes `.notdef` and the components of accented characters
+    /// composed by `endchar` in the manner of `seac`. If `renumber` is set, the
+    /// glyphs are renumbered consecutively in their original order; otherwise,
+    /// their identifiers are preserved, and the other glyphs are left empty.
+    /// Unused subroutines, font dictionaries, and strings are removed.
+    pub fn subset(&self, index: usize, glyph_ids: &[GlyphID], renumber: bool) -> Result<FontSet> {
+        if index >= self.records.len() {
+            raise!("found no font with index {index}");
+        }
+        let record = &self.records[index];
+        let character_strings = &self.character_strings[index];
+        let character_set = &self.character_sets[index];
+        let glyph_count = character_strings.len();
+        let locals = match record {
+            Record::CharacterIDKeyed(record) => record
+                .records
+                .iter()
+                .map(|record| &record.subroutines)
+                .collect::<Vec<_>>(),
+            Record::CharacterNameKeyed(record) => vec![&record.subroutines],
+        };
+        let dictionary = |glyph_id: GlyphID| -> Result<usize> {
+            match record {
+                Record::CharacterIDKeyed(record) => match record.encoding.get(glyph_id) {
+                    Some(j) if (j as usize) < locals.len() => Ok(j as usize),
+                    _ => raise!("found no dictionary for glyph {glyph_id}"),
+                },
+                Record::CharacterNameKeyed(_) => Ok(0),
+            }
+        };
+        let string_id = |glyph_id: GlyphID| -> Result<StringID> {
+            match character_set.get_string_id(glyph_id) {
+                Some(string_id) => Ok(string_id),
+                _ => raise!("found no name for glyph {glyph_id}"),
+            }
+        };
+
+        let mut included = vec![false; glyph_count];
+        let mut glyphs = None;
+        let mut pending = vec![0];
+        pending.extend_from_slice(glyph_ids);
+        while let Some(glyph_id) = pending.pop() {
+            if glyph_id as usize >= glyph_count {
+                raise!("found no glyph with identifier {glyph_id}");
+            }
+            if std::mem::replace(&mut included[glyph_id as usize], true) {
+                continue;
+            }
+            if let Record::CharacterIDKeyed(_) = record {
+                continue;
+            }
+            let code = &character_strings[glyph_id as usize];
+            let code = type2::desubroutinize(code, &self.subroutines, locals[0])?;
+            for &code in super::synthesize::accent(&code)?.iter().skip(2) {
+                if code.fract() != 0.0 || !(0.0..=255.0).contains(&code) {
+                    raise!("found a malformed accent component");
+                }
+                if glyphs.is_none() {
+                    glyphs = Some(
+                        (0..glyph_count as GlyphID)
+                            .map(|glyph_id| Ok((string_id(glyph_id)?, glyph_id)))
+                            .collect::<Result<HashMap<_, _>>>()?,
+                    );
+                }
+                let glyph_id = Encoding::Standard
+                    .get(code as GlyphID)
+                    .filter(|&string_id| string_id > 0)
+                    .and_then(|string_id| glyphs.as_ref().unwrap().get(&string_id));
+                match glyph_id {
+                    Some(&glyph_id) => pending.push(glyph_id),
+                    _ => raise!("found no glyph for an accent component ({code})"),
+                }
+            }
+        }
+        let glyph_ids = (0..glyph_count as GlyphID)
+            .filter(|&glyph_id| !renumber || included[glyph_id as usize])
+            .collect::<Vec<_>>();
+
+        let mut global_calls = vec![None; self.subroutines.len()];
+        let mut local_calls = locals
+            .iter()
+            .map(|local| vec![None; local.len()])
+            .collect::<Vec<_>>();
+        let mut used_dictionaries = BTreeSet::new();
+        for &glyph_id in glyph_ids.iter() {
+            if !included[glyph_id as usize] {
+                continue;
+            }
+            let j = dictionary(glyph_id)?;
+            used_dictionaries.insert(j);
+            let code = &character_strings[glyph_id as usize];
+            for call in type2::trace(code, &self.subroutines, locals[j])? {
+                match call.operator {
+                    type2::Operator::CallGSubr => {
+                        global_calls[call.index].get_or_insert((call, j));
+                    }
+                    _ => {
+                        local_calls[j][call.index].get_or_insert(call);
+                    }
+                }
+            }
+        }
+        let dictionaries = used_dictionaries.into_iter().collect::<Vec<_>>();
+        let mut shared = false;
+        let mut global_tokens = vec![None; self.subroutines.len()];
+        for (k, calls) in global_calls.iter().enumerate() {
+            if let Some((call, j)) = calls {
+                let tokens = type2::tokenize_subroutine(
+                    &self.subroutines[k],
+                    &self.subroutines,
+                    locals[*j],
+                    call,
+                )?;
+                shared |= tokens.contains(&Token::Operator(type2::Operator::CallSubr));
+                global_tokens[k] = Some(tokens);
+            }
+        }
+        shared &= dictionaries.len() > 1;
+
+        let global_mapping = Mapping::new(&global_calls, false);
+        let local_mappings = local_calls
+            .iter()
+            .map(|calls| Mapping::new(calls, shared))
+            .collect::<Vec<_>>();
+        let rewrite = |tokens: &[Token], j: usize| -> Result<Vec<u8>> {
+            type2::renumber(tokens, |operator, value| {
+                let mapping = match operator {
+                    type2::Operator::CallGSubr => &global_mapping,
+                    _ => &local_mappings[j],
+                };
+                mapping.get(value)
+            })
+        };
+        let mut global = vec![vec![]; global_mapping.count];
+        for (k, tokens) in global_tokens.iter().enumerate() {
+            if let (Some(tokens), Some((_, j))) = (tokens, global_calls[k]) {
+                global[global_mapping.indices[k].unwrap()] = rewrite(tokens, j)?;
+            }
+        }
+        let mut new_locals = vec![];
+        for &j in dictionaries.iter() {
+            let mapping = &local_mappings[j];
+            let mut local = vec![vec![RETURN]; mapping.count];
+            for (k, call) in local_calls[j].iter().enumerate() {
+                if let (Some(call), Some(l)) = (call, mapping.indices[k]) {
+                    let tokens = type2::tokenize_subroutine(
+                        &locals[j][k],
+                        &self.subroutines,
+                        locals[j],
+                        call,
+                    )?;
+                    local[l] = rewrite(&tokens, j)?;
+                }
+            }
+            new_locals.push(local);
+        }
+        let mut codes = Vec::with_capacity(glyph_ids.len());
+        for &glyph_id in glyph_ids.iter() {
+            if !included[glyph_id as usize] {
+                codes.push(vec![END_CHAR]);
+                continue;
+            }
+            let j = dictionary(glyph_id)?;
+            let code = &character_strings[glyph_id as usize];
+            let tokens = type2::tokenize(code, &self.subroutines, locals[j])?;
+            codes.push(rewrite(&tokens, j)?);
+        }
+
+        let mut strings = Strings::default();
+        let mut string_ids = HashMap::new();
+        let mut map = |string_id: StringID| -> Result<StringID> {
+            if string_id < NUMBER_OF_STANDARD_STRINGS {
+                return Ok(string_id);
+            }
+            if let Some(&string_id) = string_ids.get(&string_id) {
+                return Ok(string_id);
+            }
+            let value = match self.strings.get(string_id) {
+                Some(value) => value,
+                _ => raise!("found no string with identifier {string_id}"),
+            };
+            let value = strings.insert(&value);
+            string_ids.insert(string_id, value);
+            Ok(value)
+        };
+        let operations = map_strings(&self.operations[index], &mut map)?;
+        let (character_set, encoding, record) = match record {
+            Record::CharacterIDKeyed(record) => {
+                let mut dictionary_ids = Vec::with_capacity(glyph_ids.len());
+                for &glyph_id in glyph_ids.iter() {
+                    dictionary_ids.push(if included[glyph_id as usize] {
+                        let j = dictionary(glyph_id)?;
+                        dictionaries.iter().position(|&other| other == j).unwrap() as u8
+                    } else {
+                        0
+                    });
+                }
+                let string_ids = glyph_ids
+                    .iter()
+                    .map(|&glyph_id| string_id(glyph_id))
+                    .collect::<Result<Vec<_>>>()?;
+                let mut operations = vec![];
+                let mut records = vec![];
+                for (&j, subroutines) in dictionaries.iter().zip(new_locals) {
+                    operations.push(map_strings(&record.operations[j], &mut map)?);
+                    records.push(character_id_keyed::RecordInner {
+                        operations: record.records[j].operations.clone(),
+                        subroutines: Subroutines(subroutines.into()),
+                    });
+                }
+                (
+                    CharacterSet::new(&string_ids),
+                    Encoding::Standard,
+                    Record::CharacterIDKeyed(character_id_keyed::Record {
+                        registry: map(record.registry)?,
+                        ordering: map(record.ordering)?,
+                        supplement: record.supplement,
+                        encoding: character_id_keyed::Encoding::new(&dictionary_ids),
+                        operations,
+                        records,
+                    }),
+                )
+            }
+            Record::CharacterNameKeyed(record) => {
+                let string_ids = glyph_ids
+                    .iter()
+                    .map(|&glyph_id| string_id(glyph_id))
+                    .collect::<Result<Vec<_>>>()?;
+                let encoding = &self.encodings[index];
+                let supplements = encoding
+                    .supplements()
+                    .iter()
+                    .filter(|supplement| string_ids.contains(&supplement.glyph))
+                    .map(|supplement| {
+                        Ok(Supplement {
+                            code: supplement.code,
+                            glyph: map(supplement.glyph)?,
+                        })
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let string_ids = string_ids
+                    .into_iter()
+                    .map(&mut map)
+                    .collect::<Result<Vec<_>>>()?;
+                let character_set = CharacterSet::predefined(&string_ids)
+                    .unwrap_or_else(|| CharacterSet::new(&string_ids));
+                let encoding = match encoding.codes() {
+                    Some(codes) => {
+                        let codes = glyph_ids[1..]
+                            .iter()
+                            .map_while(|&glyph_id| codes.get(glyph_id as usize - 1).copied())
+                            .collect::<Vec<_>>();
+                        Encoding::new(&codes)?.with_supplements(supplements)?
+                    }
+                    _ => match encoding {
+                        Encoding::Standard | Encoding::Expert => encoding.clone(),
+                        _ => raise!("found an unsupported encoding"),
+                    },
+                };
+                (
+                    character_set,
+                    encoding,
+                    Record::CharacterNameKeyed(character_name_keyed::Record {
+                        operations: record.operations.clone(),
+                        subroutines: Subroutines(new_locals.pop().unwrap_or_default().into()),
+                    }),
+                )
+            }
+        };
+        Ok(FontSet {
+            header: self.header,
+            names: Names(vec![self.names[index].clone()].into()),
+            operations: vec![operations],
+            strings,
+            subroutines: Subroutines(global.into()),
+            encodings: vec![encoding],
+            character_strings: vec![CharacterStrings(codes.into())],
+            character_sets: vec![character_set],
+            records: vec![record],
+        })
+    }
+}
+
+const END_CHAR: u8 = 0x0e;
+const RETURN: u8 = 0x0b;
+
+/// A mapping of subroutine indices.
+struct Mapping {
+    indices: Vec<Option<usize>>,
+    count: usize,
+    bias: i32,
+    new_bias: i32,
+}
+
+impl Mapping {
+    fn new<T>(calls: &[Option<T>], identity: bool) -> Self {
+        let mut indices = Vec::with_capacity(calls.len());
+        let mut count = 0;
+        for call in calls.iter() {
+            if identity || call.is_some() {
+                indices.push(Some(count));
+                count += 1;
+            } else {
+                indices.push(None);
+            }
+        }
+        Self {
+            indices,
+            count,
+            bias: bias(calls.len()),
+            new_bias: bias(count),
+        }
+    }
+
+    fn get(&self, value: type2::Operand) -> Result<type2::Operand> {
+        let i = value as i32 + self.bias;
+        match usize::try_from(i).ok().and_then(|i| self.indices.get(i)) {
+            Some(Some(i)) => Ok((*i as i32 - self.new_bias) as type2::Operand),
+            _ => raise!("found a call to a nonexistent subroutine ({value})"),
+        }
+    }
+}
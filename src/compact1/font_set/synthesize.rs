@@ -0,0 +1,249 @@
+//! The synthesis of styles in font sets.
+
+use crate::compact1::font_set::{FontSet, Record};
+use crate::compact1::index::CharacterStrings;
+use crate::compact1::{Number, Operations, Operator};
+use crate::type2::{self, Operand, Program, Token};
+use crate::Result;
+
+impl FontSet {
+    /// Embolden the glyphs by a stroke amount given in the units of the
+    /// glyphs.
+    ///
+    /// The contours are offset so that the glyphs grow by the amount upward
+    /// and to the right, and the widths grow by the same. The glyphs are
+    /// re-encoded with rounded coordinates and without subroutines and hints,
+    /// removing the hint-related operations of the private dictionaries as
+    /// well, and the bounding box is recomputed.
+    pub fn embolden(&mut self, amount: Operand) -> Result<()> {
+        if !amount.is_finite() || amount < 0.0 {
+            raise!("found an invalid stroke amount ({amount})");
+        }
+        self.synthesize(|operations| type2::embolden(operations, amount))?;
+        for record in self.records.iter_mut() {
+            match record {
+                Record::CharacterIDKeyed(record) => {
+                    for record in record.records.iter_mut() {
+                        widen(&mut record.operations, amount);
+                    }
+                }
+                Record::CharacterNameKeyed(record) => widen(&mut record.operations, amount),
+            }
+        }
+        Ok(())
+    }
+
+    /// Slant the glyphs by an angle given in degrees clockwise from the
+    /// vertical.
+    ///
+    /// The glyphs are sheared horizontally, and the italic angle is updated to
+    /// match. The glyphs are re-encoded with rounded coordinates and without
+    /// subroutines and hints, removing the hint-related operations of the
+    /// private dictionaries as well, and the bounding box is recomputed. The
+    /// widths are left intact.
+    pub fn slant(&mut self, angle: Operand) -> Result<()> {
+        if angle.is_nan() || angle.abs() >= 90.0 {
+            raise!("found an invalid angle ({angle})");
+        }
+        self.synthesize(|operations| type2::slant(operations, angle))?;
+        for operations in self.operations.iter_mut() {
+            let italic_angle = operations
+                .get_single(Operator::ItalicAngle)
+                .map(Operand::from)
+                .unwrap_or(0.0);
+            operations.insert(Operator::ItalicAngle, vec![number(italic_angle - angle)]);
+        }
+        Ok(())
+    }
+}
+
+impl FontSet {
+    fn synthesize<F>(&mut self, mut transform: F) -> Result<()>
+    where
+        F: FnMut(&[type2::Operation]) -> Result<type2::Operations>,
+    {
+        self.desubroutinize()?;
+        for i in 0..self.records.len() {
+            let mut data = Vec::with_capacity(self.character_strings[i].len());
+            let mut bbox: Option<[Operand; 4]> = None;
+            for code in self.character_strings[i].iter() {
+                let mut operations = vec![];
+                let mut program = Program::new(code, &[], &[]);
+                while let Some(operation) = program.next()? {
+                    operations.push(operation);
+                }
+                operations.push((type2::Operator::EndChar, accent(code)?));
+                let operations = round(transform(&operations)?);
+                if let Some(other) = bounds(&operations) {
+                    bbox = Some(match bbox {
+                        Some(one) => [
+                            one[0].min(other[0]),
+                            one[1].min(other[1]),
+                            one[2].max(other[2]),
+                            one[3].max(other[3]),
+                        ],
+                        _ => other,
+                    });
+                }
+                data.push(type2::encode(
+                    &type2::specialize(&operations)?,
+                    program.width(),
+                )?);
+            }
+            self.character_strings[i] = CharacterStrings(data.into());
+            if let Some([x0, y0, x1, y1]) = bbox {
+                self.operations[i].insert(
+                    Operator::FontBBox,
+                    vec![
+                        number(x0.floor()),
+                        number(y0.floor()),
+                        number(x1.ceil()),
+                        number(y1.ceil()),
+                    ],
+                );
+            }
+        }
+        self.dehint(true)
+    }
+}
+
+pub(super) fn accent(code: &[u8]) -> Result<Vec<Operand>> {
+    let tokens = type2::tokenize(code, &[], &[])?;
+    let end = match tokens
+        .iter()
+        .rposition(|token| *token == Token::Operator(type2::Operator::EndChar))
+    {
+        Some(end) => end,
+        _ => return Ok(vec![]),
+    };
+    let operands = tokens[..end]
+        .iter()
+        .rev()
+        .take_while(|token| matches!(token, Token::Operand(_)))
+        .count();
+    if operands < 4 {
+        return Ok(vec![]);
+    }
+    Ok(tokens[(end - 4)..end]
+        .iter()
+        .filter_map(|token| match token {
+            Token::Operand(value) => Some(*value),
+            _ => None,
+        })
+        .collect())
+}
+
+fn bounds(operations: &[type2::Operation]) -> Option<[Operand; 4]> {
+    use crate::type2::Operator::*;
+
+    let mut bbox: Option<[Operand; 4]> = None;
+    let mut include = |x: Operand, y: Operand| {
+        let bbox = bbox.get_or_insert([x, y, x, y]);
+        *bbox = [
+            bbox[0].min(x),
+            bbox[1].min(y),
+            bbox[2].max(x),
+            bbox[3].max(y),
+        ];
+    };
+    let mut position = (0.0, 0.0);
+    for (operator, operands) in operations {
+        match operator {
+            RMoveTo | RLineTo => {
+                position = (position.0 + operands[0], position.1 + operands[1]);
+                include(position.0, position.1);
+            }
+            RRCurveTo => {
+                let mut points = [position; 4];
+                for j in 1..4 {
+                    points[j] = (
+                        points[j - 1].0 + operands[2 * j - 2],
+                        points[j - 1].1 + operands[2 * j - 1],
+                    );
+                }
+                for t in extrema(points.map(|point| point.0))
+                    .into_iter()
+                    .chain(extrema(points.map(|point| point.1)))
+                    .chain([1.0])
+                {
+                    let s = 1.0 - t;
+                    let [a, b, c, d] = [s * s * s, 3.0 * s * s * t, 3.0 * s * t * t, t * t * t];
+                    include(
+                        a * points[0].0 + b * points[1].0 + c * points[2].0 + d * points[3].0,
+                        a * points[0].1 + b * points[1].1 + c * points[2].1 + d * points[3].1,
+                    );
+                }
+                position = points[3];
+            }
+            _ => {}
+        }
+    }
+    bbox
+}
+
+fn extrema([p0, p1, p2, p3]: [Operand; 4]) -> Vec<Operand> {
+    let a = p3 - 3.0 * p2 + 3.0 * p1 - p0;
+    let b = 2.0 * (p2 - 2.0 * p1 + p0);
+    let c = p1 - p0;
+    let roots = if a.abs() < 1e-6 {
+        if b.abs() < 1e-6 {
+            vec![]
+        } else {
+            vec![-c / b]
+        }
+    } else {
+        let discriminant = b * b - 4.0 * a * c;
+        if discriminant < 0.0 {
+            vec![]
+        } else {
+            let root = discriminant.sqrt();
+            vec![(-b + root) / (2.0 * a), (-b - root) / (2.0 * a)]
+        }
+    };
+    roots.into_iter().filter(|t| 0.0 < *t && *t < 1.0).collect()
+}
+
+fn round(operations: type2::Operations) -> type2::Operations {
+    use crate::type2::Operator::*;
+
+    let mut exact = (0.0, 0.0);
+    let mut rounded = (0.0, 0.0);
+    operations
+        .into_iter()
+        .map(|(operator, mut operands)| {
+            match operator {
+                RMoveTo | RLineTo | RRCurveTo => {
+                    for chunk in operands.chunks_exact_mut(2) {
+                        exact = (exact.0 + chunk[0], exact.1 + chunk[1]);
+                        let next = (exact.0.round(), exact.1.round());
+                        chunk[0] = next.0 - rounded.0;
+                        chunk[1] = next.1 - rounded.1;
+                        rounded = next;
+                    }
+                }
+                EndChar => operands.iter_mut().for_each(|value| *value = value.round()),
+                _ => {}
+            }
+            (operator, operands)
+        })
+        .collect()
+}
+
+fn widen(operations: &mut Operations, amount: Operand) {
+    for operator in [Operator::DefaultWidthX, Operator::NominalWidthX] {
+        let value = operations
+            .get_single(operator)
+            .map(Operand::from)
+            .unwrap_or(0.0);
+        operations.insert(operator, vec![number(value + amount)]);
+    }
+}
+
+#[inline]
+fn number(value: Operand) -> Number {
+    if value.fract() == 0.0 {
+        Number::Integer(value as i32)
+    } else {
+        Number::Real(value)
+    }
+}
@@ -0,0 +1,260 @@
+//! The optimization of widths in font sets.
+
+use crate::compact1::font_set::{FontSet, Record};
+use crate::compact1::index::CharacterStrings;
+use crate::compact1::{GlyphID, Number, Operations, Operator};
+use crate::type2::{self, Edit, Operand, Program};
+use crate::Result;
+
+impl FontSet {
+    /// Choose the default and nominal widths minimizing the size of the
+    /// character strings and re-encode the widths of the glyphs accordingly.
+    ///
+    /// The widths are chosen for each private dictionary separately. If the
+    /// width of a glyph is not given in its character string but, for
+    /// instance, in a subroutine or as a computed value, the glyph is left
+    /// intact, and only the default width of the dictionary is changed.
+    /// Likewise, if a width cannot be inserted in front of the operands of the
+    /// first stack-clearing operator, only the nominal width is changed.
+    pub fn optimize_widths(&mut self) -> Result<()> {
+        for i in 0..self.records.len() {
+            let glyph_count = self.character_strings[i].len();
+            let (dictionaries, privates) = match &self.records[i] {
+                Record::CharacterIDKeyed(record) => {
+                    let mut dictionaries = Vec::with_capacity(glyph_count);
+                    for glyph_id in 0..glyph_count as GlyphID {
+                        match record.encoding.get(glyph_id) {
+                            Some(j) if (j as usize) < record.records.len() => {
+                                dictionaries.push(j as usize)
+                            }
+                            _ => raise!("found no dictionary for glyph {glyph_id}"),
+                        }
+                    }
+                    let privates = record
+                        .records
+                        .iter()
+                        .map(|record| widths(&record.operations))
+                        .collect::<Vec<_>>();
+                    (dictionaries, privates)
+                }
+                Record::CharacterNameKeyed(record) => {
+                    (vec![0; glyph_count], vec![widths(&record.operations)])
+                }
+            };
+            let mut values = vec![vec![]; privates.len()];
+            let mut fixed = vec![(false, false); privates.len()];
+            let mut glyphs = Vec::with_capacity(glyph_count);
+            for (glyph_id, code) in self.character_strings[i].iter().enumerate() {
+                let local = match self.records[i].subroutines(glyph_id as GlyphID) {
+                    Some(local) => local,
+                    _ => raise!("found no local subroutines for glyph {glyph_id}"),
+                };
+                let mut program = Program::new(code, &self.subroutines, local);
+                while program.next()?.is_some() {}
+                let j = dictionaries[glyph_id];
+                let (default, nominal) = privates[j];
+                let difference = program.width();
+                let width = match difference {
+                    Some(difference) => nominal + difference,
+                    _ => default,
+                };
+                let movable = edit(code, &self.subroutines, local, difference, Some(0.0)).is_some();
+                match (movable, difference) {
+                    (true, _) => values[j].push(width),
+                    (false, Some(_)) => fixed[j].1 = true,
+                    (false, None) => fixed[j].0 = true,
+                }
+                glyphs.push((difference, movable.then_some(width), local));
+            }
+            let privates = values
+                .iter()
+                .zip(fixed)
+                .zip(privates)
+                .map(
+                    |((values, fixed), (default, nominal))| match values.is_empty() {
+                        true => (default, nominal),
+                        _ => choose(
+                            values,
+                            fixed.0.then_some(default),
+                            fixed.1.then_some(nominal),
+                        ),
+                    },
+                )
+                .collect::<Vec<_>>();
+            let mut data = Vec::with_capacity(glyph_count);
+            for (glyph_id, (difference, width, local)) in glyphs.into_iter().enumerate() {
+                let code = &self.character_strings[i][glyph_id];
+                let (default, nominal) = privates[dictionaries[glyph_id]];
+                let other = width.and_then(|width| (width != default).then_some(width - nominal));
+                data.push(match width {
+                    Some(_) if other != difference => {
+                        match edit(code, &self.subroutines, local, difference, other) {
+                            Some(code) => code,
+                            _ => raise!("found a width that cannot be re-encoded ({glyph_id})"),
+                        }
+                    }
+                    _ => code.clone(),
+                });
+            }
+            self.character_strings[i] = CharacterStrings(data.into());
+            match &mut self.records[i] {
+                Record::CharacterIDKeyed(record) => {
+                    for (record, &(default, nominal)) in record.records.iter_mut().zip(&privates) {
+                        update(&mut record.operations, default, nominal);
+                    }
+                }
+                Record::CharacterNameKeyed(record) => {
+                    update(&mut record.operations, privates[0].0, privates[0].1);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn edit(
+    code: &[u8],
+    global: &[Vec<u8>],
+    local: &[Vec<u8>],
+    width: Option<Operand>,
+    other: Option<Operand>,
+) -> Option<Vec<u8>> {
+    let mut found = false;
+    let rewritten = type2::rewrite(
+        &[(code, 0)],
+        global,
+        &[local],
+        |operator, operands, cleared| {
+            let mut edits = vec![Edit::Keep; operands.len()];
+            let mut edit = Edit::Keep;
+            if cleared || found || operator == type2::Operator::DotSection {
+                return Ok((edits, edit));
+            }
+            found = true;
+            match (width, other) {
+                (Some(width), _) if operands.first() != Some(&width) => found = false,
+                (Some(_), Some(other)) => edits[0] = Edit::Replace(other),
+                (Some(_), None) => edits[0] = Edit::Delete,
+                (None, Some(other)) => match edits.first_mut() {
+                    Some(first) => *first = Edit::Insert(other),
+                    _ => edit = Edit::Insert(other),
+                },
+                (None, None) => {}
+            }
+            Ok((edits, edit))
+        },
+    )
+    .ok()?;
+    if !found || rewritten.global != global || rewritten.locals[0] != local {
+        return None;
+    }
+    rewritten.character_strings.into_iter().next()
+}
+
+/// Re-encode the width of a glyph given in terms of one pair of default and
+/// nominal widths in terms of another.
+pub(super) fn reencode(
+    code: &[u8],
+    global: &[Vec<u8>],
+    local: &[Vec<u8>],
+    (default, nominal): (Operand, Operand),
+    (other_default, other_nominal): (Operand, Operand),
+) -> Result<Vec<u8>> {
+    let mut program = Program::new(code, global, local);
+    while program.next()?.is_some() {}
+    let difference = program.width();
+    let width = match difference {
+        Some(difference) => nominal + difference,
+        _ => default,
+    };
+    let other = (width != other_default).then_some(width - other_nominal);
+    if other == difference {
+        return Ok(code.to_vec());
+    }
+    match edit(code, global, local, difference, other) {
+        Some(code) => Ok(code),
+        _ => raise!("found a width that cannot be re-encoded"),
+    }
+}
+
+pub(super) fn widths(operations: &Operations) -> (Operand, Operand) {
+    let value = |operator: Operator| {
+        operations
+            .get_single(operator)
+            .map(Operand::from)
+            .unwrap_or(0.0)
+    };
+    (
+        value(Operator::DefaultWidthX),
+        value(Operator::NominalWidthX),
+    )
+}
+
+fn update(operations: &mut Operations, default: Operand, nominal: Operand) {
+    for (operator, value) in [
+        (Operator::DefaultWidthX, default),
+        (Operator::NominalWidthX, nominal),
+    ] {
+        if value == 0.0 {
+            operations.remove(&operator);
+        } else if value.fract() == 0.0 {
+            operations.insert(operator, vec![Number::Integer(value as i32)]);
+        } else {
+            operations.insert(operator, vec![Number::Real(value)]);
+        }
+    }
+}
+
+fn choose(
+    values: &[Operand],
+    default: Option<Operand>,
+    nominal: Option<Operand>,
+) -> (Operand, Operand) {
+    let mut values = values.to_vec();
+    values.sort_by(Operand::total_cmp);
+    let mut counts: Vec<(Operand, usize)> = vec![];
+    for value in values {
+        match counts.last_mut() {
+            Some((last, count)) if *last == value => *count += 1,
+            _ => counts.push((value, 1)),
+        }
+    }
+    let candidates = match nominal {
+        Some(nominal) => vec![nominal],
+        _ => {
+            let mut candidates = vec![0.0];
+            for &(value, _) in counts.iter().filter(|(value, _)| value.fract() == 0.0) {
+                candidates.extend([-1131.0, -107.0, 0.0, 108.0, 1132.0].map(|delta| value + delta));
+            }
+            candidates.sort_by(Operand::total_cmp);
+            candidates.dedup();
+            candidates
+        }
+    };
+    let mut best = (usize::MAX, 0.0, 0.0);
+    for nominal in candidates {
+        let mut total = 0;
+        let mut saving = (0, default.unwrap_or(counts[0].0));
+        for &(value, count) in counts.iter() {
+            let size = count * cost(value - nominal);
+            total += size;
+            if default.map_or(size > saving.0, |default| value == default) {
+                saving = (size, value);
+            }
+        }
+        if total - saving.0 < best.0 {
+            best = (total - saving.0, saving.1, nominal);
+        }
+    }
+    (best.1, best.2)
+}
+
+fn cost(value: Operand) -> usize {
+    match value {
+        _ if !(-32768.0..32768.0).contains(&value) => 1 << 24,
+        _ if value.fract() != 0.0 => 5,
+        _ if (-107.0..=107.0).contains(&value) => 1,
+        _ if (-1131.0..=1131.0).contains(&value) => 2,
+        _ => 3,
+    }
+}
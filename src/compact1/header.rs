@@ -1,6 +1,7 @@
 use crate::compact1::OffsetSize;
 
 table! {
+    @write
     /// A header.
     #[derive(Copy)]
     pub Header {
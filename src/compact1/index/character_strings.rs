@@ -6,6 +6,8 @@ index! {
     pub CharacterStrings
 }
 
+index! { @write CharacterStrings }
+
 impl crate::walue::Read<'static> for CharacterStrings {
     type Parameter = i32;
 
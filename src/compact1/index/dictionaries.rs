@@ -1,6 +1,7 @@
 use std::io::Cursor;
 
-use crate::compact1::Operations;
+use crate::compact1::error::Locate;
+use crate::compact1::{Location, Operations};
 use crate::tape::Read;
 use crate::{Error, Result};
 
@@ -16,6 +17,23 @@ impl Dictionaries {
         debug_assert!(index < self.len());
         Cursor::new(&self[index]).take()
     }
+
+    /// Return the operations at a specific position given the position right
+    /// after the index, attaching a location to errors.
+    pub(crate) fn decode(&self, end: u64, index: usize, location: Location) -> Result<Operations> {
+        Cursor::new(&self[index])
+            .take()
+            .locate(location, self.position(end, index))
+    }
+
+    /// Return the position of a dictionary given the position right after the
+    /// index.
+    pub(crate) fn position(&self, end: u64, index: usize) -> u64 {
+        match (self.offsets.get(index), self.offsets.last()) {
+            (Some(offset), Some(last)) => end - last.0 as u64 + offset.0 as u64,
+            _ => end,
+        }
+    }
 }
 
 impl TryFrom<&Dictionaries> for Vec<Operations> {
@@ -1,8 +1,11 @@
 //! The indices.
 
+use crate::compact1::offset::minimal_size;
 use crate::compact1::{Offset, OffsetSize};
 use crate::Result;
 
+const COUNT_LIMIT: usize = 65535;
+
 table! {
     @define
     /// An index.
@@ -16,6 +19,44 @@ table! {
 
 dereference! { Index::data => [Vec<u8>] }
 
+impl Index {
+    /// Append a chunk of data.
+    pub fn push(&mut self, chunk: Vec<u8>) {
+        self.count += 1;
+        if self.offsets.is_empty() && !self.data.is_empty() {
+            self.data.push(chunk);
+            return;
+        }
+        let start = self.offsets.last().map(|offset| offset.0).unwrap_or(1);
+        let end = start + chunk.len() as u32;
+        if self.offsets.is_empty() {
+            self.offsets.push(Offset(1));
+        }
+        self.offsets.push(Offset(end));
+        self.data.push(chunk);
+        self.offset_size = minimal_size(end);
+    }
+
+    /// Replace a chunk of data and return the previous one.
+    ///
+    /// The offsets are discarded and computed when the index is written.
+    pub fn replace(&mut self, i: usize, chunk: Vec<u8>) -> Vec<u8> {
+        self.offsets.clear();
+        self.offset_size = 0;
+        std::mem::replace(&mut self.data[i], chunk)
+    }
+}
+
+impl From<Vec<Vec<u8>>> for Index {
+    fn from(data: Vec<Vec<u8>>) -> Self {
+        let mut index = Index::default();
+        for chunk in data {
+            index.push(chunk);
+        }
+        index
+    }
+}
+
 impl crate::value::Read for Index {
     fn read<T: crate::tape::Read>(tape: &mut T) -> Result<Self> {
         let count = tape.take::<u16>()?;
@@ -53,10 +94,44 @@ impl crate::value::Read for Index {
     }
 }
 
+impl crate::value::Write for Index {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        use crate::walue::Write;
+
+        if self.data.len() > COUNT_LIMIT {
+            raise!(
+                "found an index with too many elements ({})",
+                self.data.len()
+            );
+        }
+        tape.give(&(self.data.len() as u16))?;
+        if self.data.is_empty() {
+            return Ok(());
+        }
+        let end = self.data.iter().map(Vec::len).sum::<usize>() + 1;
+        if end > u32::MAX as usize {
+            raise!("found an index with too much data ({end})");
+        }
+        let offset_size = minimal_size(end as u32);
+        tape.give(&offset_size)?;
+        let mut offset = 1;
+        Offset(offset).write(tape, offset_size)?;
+        for chunk in self.data.iter() {
+            offset += chunk.len() as u32;
+            Offset(offset).write(tape, offset_size)?;
+        }
+        for chunk in self.data.iter() {
+            tape.give_bytes(chunk)?;
+        }
+        Ok(())
+    }
+}
+
 macro_rules! index {
     ($(#[$attribute:meta])* pub $structure:ident) => (
         index! { @define $(#[$attribute])* pub $structure }
         index! { @implement $structure }
+        index! { @write $structure }
     );
     (@define $(#[$attribute:meta])* pub $structure:ident) => (
         $(#[$attribute])*
@@ -72,6 +147,14 @@ macro_rules! index {
             }
         }
     );
+    (@write $structure:ident) => (
+        impl $crate::value::Write for $structure {
+            #[inline]
+            fn write<T: $crate::tape::Write>(&self, tape: &mut T) -> $crate::Result<()> {
+                tape.give(&self.0)
+            }
+        }
+    );
 }
 
 mod character_strings;
@@ -84,4 +167,6 @@ pub use character_strings::CharacterStrings;
 pub use dictionaries::Dictionaries;
 pub use names::Names;
 pub use strings::Strings;
+
+pub(crate) use strings::find_standard_string;
 pub use subroutines::Subroutines;
@@ -4,6 +4,7 @@ const NUMBER_OF_STANDARD_STRINGS: usize = 391;
 
 index! {
     /// A string index.
+    #[derive(Default)]
     pub Strings
 }
 
@@ -20,6 +21,43 @@ impl Strings {
                 .map(|chunk| String::from_utf8_lossy(chunk).into_owned()),
         }
     }
+
+    /// Return the string identifier corresponding to a string.
+    pub fn position(&self, string: &str) -> Option<StringID> {
+        if let Some(string_id) = find_standard_string(string) {
+            return Some(string_id);
+        }
+        self.0
+            .iter()
+            .position(|chunk| chunk == string.as_bytes())
+            .map(|i| (i + NUMBER_OF_STANDARD_STRINGS) as StringID)
+    }
+
+    /// Insert a string unless it is present and return its string identifier.
+    pub fn insert(&mut self, string: &str) -> StringID {
+        match self.position(string) {
+            Some(string_id) => string_id,
+            _ => {
+                self.0.push(string.as_bytes().to_vec());
+                (self.0.len() - 1 + NUMBER_OF_STANDARD_STRINGS) as StringID
+            }
+        }
+    }
+
+    /// Replace a custom string and return the previous one.
+    pub fn replace(&mut self, string_id: StringID, string: &str) -> Option<String> {
+        let i = (string_id as usize).checked_sub(NUMBER_OF_STANDARD_STRINGS)?;
+        if i >= self.0.len() {
+            return None;
+        }
+        let chunk = self.0.replace(i, string.as_bytes().to_vec());
+        Some(String::from_utf8_lossy(&chunk).into_owned())
+    }
+}
+
+pub(crate) fn find_standard_string(string: &str) -> Option<StringID> {
+    (0..NUMBER_OF_STANDARD_STRINGS as StringID)
+        .find(|&string_id| get_standard_string(string_id) == Some(string))
 }
 
 fn get_standard_string(string_id: StringID) -> Option<&'static str> {
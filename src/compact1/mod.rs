@@ -2,11 +2,18 @@
 //!
 //! [1]: https://adobe-type-tools.github.io/font-tech-notes/pdfs/5176.CFF.pdf
 
+pub mod analysis;
 pub mod character_set;
+pub mod editor;
 pub mod encoding;
 pub mod font_set;
 pub mod index;
+pub mod recovery;
+pub mod sanitizer;
+pub mod ttx;
+pub mod validator;
 
+mod error;
 mod header;
 mod number;
 mod offset;
@@ -14,6 +21,7 @@ mod operation;
 
 pub use character_set::CharacterSet;
 pub use encoding::Encoding;
+pub use error::{ErrorWithLocation, Location};
 pub use font_set::FontSet;
 pub use header::Header;
 pub use index::Index;
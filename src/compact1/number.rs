@@ -11,6 +11,68 @@ pub enum Number {
     Real(f32),
 }
 
+impl std::ops::Add for Number {
+    type Output = Self;
+
+    fn add(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Integer(one), Number::Integer(other)) => match one.checked_add(other) {
+                Some(value) => Number::Integer(value),
+                _ => Number::Real(one as f32 + other as f32),
+            },
+            (one, other) => Number::Real(f32::from(one) + f32::from(other)),
+        }
+    }
+}
+
+impl std::ops::Sub for Number {
+    type Output = Self;
+
+    fn sub(self, other: Self) -> Self {
+        match (self, other) {
+            (Number::Integer(one), Number::Integer(other)) => match one.checked_sub(other) {
+                Some(value) => Number::Integer(value),
+                _ => Number::Real(one as f32 - other as f32),
+            },
+            (one, other) => Number::Real(f32::from(one) - f32::from(other)),
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    #[inline]
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Number::Integer(value) => value.fmt(formatter),
+            Number::Real(value) => value.fmt(formatter),
+        }
+    }
+}
+
+impl std::str::FromStr for Number {
+    type Err = crate::Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        if let Ok(value) = value.parse() {
+            return Ok(Number::Integer(value));
+        }
+        match value.parse() {
+            Ok(value) => Ok(Number::Real(value)),
+            _ => reject!(),
+        }
+    }
+}
+
+impl From<Number> for f32 {
+    #[inline]
+    fn from(number: Number) -> Self {
+        match number {
+            Number::Integer(value) => value as f32,
+            Number::Real(value) => value,
+        }
+    }
+}
+
 impl From<f32> for Number {
     #[inline]
     fn from(value: f32) -> Self {
@@ -44,6 +106,34 @@ impl crate::value::Read for Number {
     }
 }
 
+impl crate::value::Write for Number {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        match *self {
+            Number::Integer(value @ -107..=107) => tape.give(&((value + 139) as u8)),
+            Number::Integer(value @ 108..=1131) => {
+                let value = value - 108;
+                tape.give_bytes(&[(value / 256 + 247) as u8, (value % 256) as u8])
+            }
+            Number::Integer(value @ -1131..=-108) => {
+                let value = -value - 108;
+                tape.give_bytes(&[(value / 256 + 251) as u8, (value % 256) as u8])
+            }
+            Number::Integer(value @ -32768..=32767) => {
+                tape.give(&0x1cu8)?;
+                tape.give(&(value as i16))
+            }
+            Number::Integer(value) => {
+                tape.give(&0x1du8)?;
+                tape.give(&value)
+            }
+            Number::Real(value) => {
+                tape.give(&0x1eu8)?;
+                tape.give_bytes(&format(value)?)
+            }
+        }
+    }
+}
+
 fn parse<T: crate::tape::Read>(tape: &mut T) -> Result<f32> {
     let mut buffer = String::new();
     let mut byte = 0;
@@ -73,12 +163,48 @@ fn parse<T: crate::tape::Read>(tape: &mut T) -> Result<f32> {
     }
 }
 
+fn format(value: f32) -> Result<Vec<u8>> {
+    if !value.is_finite() {
+        raise!("found a non-finite number ({value})");
+    }
+    let plain = value.to_string();
+    let scientific = format!("{value:e}");
+    let buffer = if scientific.len() < plain.len() {
+        scientific
+    } else {
+        plain
+    };
+    let mut nibbles = vec![];
+    let mut characters = buffer.chars().peekable();
+    while let Some(character) = characters.next() {
+        nibbles.push(match character {
+            '0'..='9' => character as u8 - b'0',
+            '.' => 0x0a,
+            'e' if characters.peek() == Some(&'-') => {
+                characters.next();
+                0x0c
+            }
+            'e' => 0x0b,
+            '-' => 0x0e,
+            _ => reject!(),
+        });
+    }
+    nibbles.push(0x0f);
+    if nibbles.len() % 2 == 1 {
+        nibbles.push(0x0f);
+    }
+    Ok(nibbles
+        .chunks(2)
+        .map(|pair| (pair[0] << 4) | pair[1])
+        .collect())
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
     use super::Number;
-    use crate::tape::Read;
+    use crate::tape::{Read, Write};
 
     #[test]
     fn integer() {
@@ -136,4 +262,27 @@ mod tests {
         let mut tape = Cursor::new(vec![0x1e, 0x0a, 0x14, 0x05, 0x41, 0xc3, 0xff, 0x0f]);
         assert!((read!(tape) - 0.140541e-3).abs() < 1e-14);
     }
+
+    #[test]
+    fn write() {
+        for value in [
+            0, 100, -100, 107, 108, 1000, 1131, 1132, -1000, -1131, -1132, 10000, -10000, 32767,
+            32768, 100000, -100000,
+        ] {
+            let mut tape = Cursor::new(vec![]);
+            tape.give(&Number::Integer(value)).unwrap();
+            tape.set_position(0);
+            assert_eq!(tape.take::<Number>().unwrap(), Number::Integer(value));
+        }
+        for value in [-2.25, 0.140541e-3, 0.001, 0.039625, 1e10, 0.5] {
+            let mut tape = Cursor::new(vec![]);
+            tape.give(&Number::Real(value)).unwrap();
+            tape.set_position(0);
+            assert_eq!(tape.take::<Number>().unwrap(), Number::Real(value));
+        }
+
+        let mut tape = Cursor::new(vec![]);
+        tape.give(&Number::Real(-2.25)).unwrap();
+        assert_eq!(tape.into_inner(), vec![0x1e, 0xe2, 0xa2, 0x5f]);
+    }
 }
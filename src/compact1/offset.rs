@@ -24,12 +24,40 @@ impl crate::walue::Read<'static> for Offset {
     }
 }
 
+impl crate::walue::Write<'static> for Offset {
+    type Parameter = OffsetSize;
+
+    fn write<T: crate::tape::Write>(&self, tape: &mut T, size: OffsetSize) -> Result<()> {
+        match size {
+            1 if self.0 <= 0xff => tape.give(&(self.0 as u8)),
+            2 if self.0 <= 0xffff => tape.give(&(self.0 as u16)),
+            3 if self.0 <= 0xffffff => {
+                let value = self.0.to_be_bytes();
+                tape.give(&[value[1], value[2], value[3]])
+            }
+            4 => tape.give(&self.0),
+            _ => raise!("found an offset that does not fit its size ({})", self.0),
+        }
+    }
+}
+
+/// Return the minimal size of an offset.
+#[inline]
+pub(crate) fn minimal_size(offset: u32) -> OffsetSize {
+    match offset {
+        0..=0xff => 1,
+        0x100..=0xffff => 2,
+        0x10000..=0xffffff => 3,
+        _ => 4,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
     use crate::compact1::Offset;
-    use crate::walue::Read;
+    use crate::walue::{Read, Write};
 
     #[test]
     fn read() {
@@ -40,4 +68,16 @@ mod tests {
         assert_eq!(Offset::read(&mut tape, 3).unwrap().0, 0x040506);
         assert_eq!(Offset::read(&mut tape, 4).unwrap().0, 0x0708090a);
     }
+
+    #[test]
+    fn write() {
+        let mut tape = Cursor::new(vec![]);
+
+        Offset(0x01).write(&mut tape, 1).unwrap();
+        Offset(0x0203).write(&mut tape, 2).unwrap();
+        Offset(0x040506).write(&mut tape, 3).unwrap();
+        Offset(0x0708090a).write(&mut tape, 4).unwrap();
+        assert!(Offset(0x0100).write(&mut tape, 1).is_err());
+        assert_eq!(tape.into_inner(), vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]);
+    }
 }
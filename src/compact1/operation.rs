@@ -5,6 +5,8 @@ use std::collections::HashMap;
 use crate::compact1::number::Number;
 use crate::Result;
 
+const OPERAND_LIMIT: usize = 48;
+
 /// An operand.
 pub type Operand = Number;
 
@@ -71,6 +73,31 @@ impl crate::value::Read for Operations {
     }
 }
 
+impl crate::value::Write for Operations {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        let mut operators = self.0.keys().copied().collect::<Vec<_>>();
+        operators.sort_by_key(|operator| match operator {
+            Operator::ROS | Operator::SyntheticBase => (0, 0),
+            operator => (1, operator.code()),
+        });
+        for operator in operators {
+            let operands = &self.0[&operator];
+            if operands.len() > OPERAND_LIMIT {
+                raise!(
+                    "found too many operands for operator {operator:?} ({})",
+                    operands.len(),
+                );
+            }
+            tape.give(&operands[..])?;
+            match operator.code() {
+                code @ 0x0c00..=0x0cff => tape.give(&code)?,
+                code => tape.give(&(code as u8))?,
+            }
+        }
+        Ok(())
+    }
+}
+
 dereference! { Operations::0 => HashMap<Operator, Vec<Operand>> }
 
 impl crate::value::Read for Operation {
@@ -92,6 +119,38 @@ impl crate::value::Read for Operation {
     }
 }
 
+impl Operator {
+    /// Check if the operand is a string identifier.
+    pub fn is_string(&self) -> bool {
+        matches!(
+            self,
+            Operator::Version
+                | Operator::Notice
+                | Operator::Copyright
+                | Operator::FullName
+                | Operator::FamilyName
+                | Operator::Weight
+                | Operator::PostScript
+                | Operator::BaseFontName
+                | Operator::FontName
+        )
+    }
+
+    /// Check if the operands are offsets.
+    pub fn is_offset(&self) -> bool {
+        matches!(
+            self,
+            Operator::CharSet
+                | Operator::Encoding
+                | Operator::CharStrings
+                | Operator::Private
+                | Operator::Subrs
+                | Operator::FDArray
+                | Operator::FDSelect
+        )
+    }
+}
+
 macro_rules! default(
     ([$($operand:expr),+ $(,)?]) => ({
         const OPERANDS: &'static [Operand] = &[$($operand),+];
@@ -101,7 +160,7 @@ macro_rules! default(
 );
 
 macro_rules! operator {
-    (pub $name:ident { $($code:pat => $variant:ident $default:tt,)+ }) => (
+    (pub $name:ident { $($code:literal => $variant:ident $default:tt,)+ }) => (
         operator! { @define pub $name { $($variant,)+ } }
         operator! { @implement pub $name { $($code => $variant $default,)+ } }
     );
@@ -110,7 +169,7 @@ macro_rules! operator {
         #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
         pub enum $name { $($variant,)* }
     );
-    (@implement pub $name:ident { $($code:pat => $variant:ident $default:tt,)* }) => (impl $name {
+    (@implement pub $name:ident { $($code:literal => $variant:ident $default:tt,)* }) => (impl $name {
         #[doc(hidden)]
         pub fn from(code: u16) -> Result<Self> {
             use self::$name::*;
@@ -121,6 +180,15 @@ macro_rules! operator {
             })
         }
 
+        /// Return the code.
+        pub fn code(&self) -> u16 {
+            use self::$name::*;
+
+            match *self {
+                $($variant => $code,)+
+            }
+        }
+
         /// Return the default operands.
         pub fn default(&self) -> Option<&'static [Operand]> {
             use self::$name::*;
@@ -0,0 +1,46 @@
+//! The recovery of damaged font sets.
+
+use std::io::Cursor;
+
+use crate::compact1::{ErrorWithLocation, FontSet};
+use crate::Result;
+
+/// A font set recovered on a best-effort basis.
+#[derive(Debug)]
+pub struct Recovered {
+    /// The font set without the damaged parts.
+    pub font_set: FontSet,
+    /// The errors of the damaged parts, with offsets relative to the start of
+    /// the encoded font set.
+    pub omissions: Vec<ErrorWithLocation>,
+}
+
+impl Recovered {
+    /// Read an encoded font set, leaving out the damaged parts instead of
+    /// failing.
+    ///
+    /// Damaged private dictionaries, font dictionaries, and indices of
+    /// subroutines are replaced with empty ones. Damaged character sets,
+    /// encodings, and glyph-to-dictionary encodings are marked as missing,
+    /// which makes the font set impossible to write until they are replaced.
+    /// Glyphs that fail to be interpreted are reported but kept as they are.
+    /// Fonts whose top dictionaries, indices of character strings, or indices
+    /// of font dictionaries are damaged are left out entirely. The header and
+    /// the indices of names, top dictionaries, and strings are required to be
+    /// intact.
+    pub fn new(data: &[u8]) -> Result<Self> {
+        let mut omissions = vec![];
+        let font_set =
+            crate::compact1::font_set::read(&mut Cursor::new(data), Some(&mut omissions))?;
+        Ok(Self {
+            font_set,
+            omissions,
+        })
+    }
+
+    /// Check if nothing has been left out.
+    #[inline]
+    pub fn is_complete(&self) -> bool {
+        self.omissions.is_empty()
+    }
+}
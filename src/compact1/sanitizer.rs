@@ -0,0 +1,185 @@
+//! The sanitization of untrusted font sets.
+
+use std::io::Cursor;
+
+use crate::compact1::font_set::Record;
+use crate::compact1::{FontSet, GlyphID, Number, Operator};
+use crate::tape::{Read, Write};
+use crate::type2::{self, Profile, Program};
+use crate::Result;
+
+const END_CHAR: u8 = 0x0e;
+
+/// A sanitized font set.
+#[derive(Clone, Debug)]
+pub struct Sanitized {
+    /// The font set in the canonical form.
+    pub font_set: FontSet,
+    /// The encoded font set.
+    pub data: Vec<u8>,
+    /// The glyphs replaced with empty ones.
+    pub replacements: Vec<Replacement>,
+}
+
+/// A glyph replaced with an empty one.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Replacement {
+    /// The index of the font.
+    pub font: usize,
+    /// The identifier of the glyph.
+    pub glyph_id: GlyphID,
+    /// The reason for the replacement.
+    pub reason: String,
+}
+
+/// Limits imposed on untrusted font sets.
+#[derive(Clone, Copy, Debug)]
+pub struct Limits {
+    /// The maximal size in bytes.
+    pub size: usize,
+    /// The maximal number of fonts.
+    pub font_count: usize,
+    /// The maximal number of glyphs per font.
+    pub glyph_count: usize,
+    /// The maximal number of subroutines per index.
+    pub subroutine_count: usize,
+    /// The maximal number of font dictionaries per font.
+    pub dictionary_count: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            size: 64 << 20,
+            font_count: 1,
+            glyph_count: 65535,
+            subroutine_count: 65535,
+            dictionary_count: 256,
+        }
+    }
+}
+
+impl Sanitized {
+    /// Sanitize an encoded font set under the default limits.
+    #[inline]
+    pub fn new(data: &[u8]) -> Result<Self> {
+        Self::with_limits(data, Limits::default())
+    }
+
+    /// Sanitize an encoded font set under specific limits.
+    ///
+    /// The structure is read and checked against the limits before any
+    /// character string is decoded. Every character string is then decoded
+    /// strictly, and the glyphs that fail are replaced with empty ones; any
+    /// other failure is fatal. The result is rewritten from scratch without
+    /// unused strings and subroutines, and it is parsed once more in order to
+    /// confirm that it is well formed.
+    pub fn with_limits(data: &[u8], limits: Limits) -> Result<Self> {
+        if data.len() > limits.size {
+            raise!("found a font set of excessive size ({})", data.len());
+        }
+        let mut font_set = Cursor::new(data).take::<FontSet>()?;
+        check(&font_set, &limits)?;
+        let mut replacements = vec![];
+        for i in 0..font_set.records.len() {
+            for glyph_id in 0..font_set.character_strings[i].len() as GlyphID {
+                if let Err(error) = decode(&font_set, i, glyph_id) {
+                    replacements.push(Replacement {
+                        font: i,
+                        glyph_id,
+                        reason: error.to_string(),
+                    });
+                }
+            }
+            for replacement in replacements.iter().filter(|value| value.font == i) {
+                font_set.character_strings[i]
+                    .replace(replacement.glyph_id as usize, vec![END_CHAR]);
+            }
+        }
+        font_set.optimize()?;
+        let mut data = vec![];
+        data.give(&font_set)?;
+        let font_set = Cursor::new(&data[..]).take::<FontSet>()?;
+        Ok(Self {
+            font_set,
+            data,
+            replacements,
+        })
+    }
+}
+
+fn check(font_set: &FontSet, limits: &Limits) -> Result<()> {
+    if font_set.header.major != 1 {
+        raise!("found an unsupported version ({})", font_set.header.major);
+    }
+    let count = font_set.records.len();
+    if count == 0 || count > limits.font_count {
+        raise!("found an invalid number of fonts ({count})");
+    }
+    if font_set.subroutines.len() > limits.subroutine_count {
+        raise!(
+            "found too many global subroutines ({})",
+            font_set.subroutines.len()
+        );
+    }
+    for (i, record) in font_set.records.iter().enumerate() {
+        let glyph_count = font_set.character_strings[i].len();
+        if glyph_count == 0 || glyph_count > limits.glyph_count {
+            raise!("found an invalid number of glyphs ({glyph_count})");
+        }
+        match font_set.operations[i].get_single(Operator::CharStringType) {
+            None | Some(Number::Integer(2)) => {}
+            Some(value) => raise!("found an unsupported character string type ({value})"),
+        }
+        let locals = match record {
+            Record::CharacterIDKeyed(record) => {
+                if record.records.len() > limits.dictionary_count {
+                    raise!("found too many dictionaries ({})", record.records.len());
+                }
+                record
+                    .records
+                    .iter()
+                    .map(|record| record.subroutines.len())
+                    .collect()
+            }
+            Record::CharacterNameKeyed(record) => vec![record.subroutines.len()],
+        };
+        if let Some(count) = locals
+            .into_iter()
+            .find(|&count| count > limits.subroutine_count)
+        {
+            raise!("found too many local subroutines ({count})");
+        }
+        for glyph_id in 0..glyph_count as GlyphID {
+            if record.subroutines(glyph_id).is_none() {
+                raise!("found no dictionary for glyph {glyph_id}");
+            }
+            if font_set.character_sets[i].get_string_id(glyph_id).is_none() {
+                raise!("found no name for glyph {glyph_id}");
+            }
+        }
+    }
+    Ok(())
+}
+
+fn decode(font_set: &FontSet, i: usize, glyph_id: GlyphID) -> Result<()> {
+    let code = &font_set.character_strings[i][glyph_id as usize];
+    let global = &font_set.subroutines;
+    let local = match font_set.records[i].subroutines(glyph_id) {
+        Some(local) => local,
+        _ => raise!("found no local subroutines for glyph {glyph_id}"),
+    };
+    let mut program = Program::with_profile(code, global, local, Profile::Strict);
+    while program.next()?.is_some() {}
+    let tokens = type2::tokenize(code, global, local)?;
+    type2::renumber(&tokens, |_, value| Ok(value))?;
+    for call in type2::trace(code, global, local)? {
+        let code = match call.operator {
+            type2::Operator::CallGSubr => &global[call.index],
+            _ => &local[call.index],
+        };
+        let tokens = type2::tokenize_subroutine(code, global, local, &call)?;
+        type2::renumber(&tokens, |_, value| Ok(value))?;
+    }
+    Ok(())
+}
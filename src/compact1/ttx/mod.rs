@@ -0,0 +1,763 @@
+//! The [TTX][1] representation of font sets.
+//!
+//! The representation follows the layout of the `CFF ` table produced by
+//! fontTools, with character strings given as program text.
+//!
+//! [1]: https://fonttools.readthedocs.io/en/latest/ttx.html
+
+mod xml;
+
+use std::collections::HashMap;
+
+use crate::compact1::character_set::CharacterSet0;
+use crate::compact1::encoding::{Encoding0, Supplement};
+use crate::compact1::font_set::{character_id_keyed, character_name_keyed, Record};
+use crate::compact1::index::{CharacterStrings, Index, Names, Strings, Subroutines};
+use crate::compact1::{
+    CharacterSet, Encoding, FontSet, GlyphID, Header, Number, Operand, Operations, Operator,
+    StringID,
+};
+use crate::type2::{self, Call, Token};
+use crate::Result;
+
+use self::xml::{Element, Writer};
+
+#[derive(Clone, Copy)]
+enum Kind {
+    Number,
+    Delta,
+    String,
+}
+
+const ENTRIES: &[(Operator, &str, Kind)] = &[
+    (Operator::Version, "version", Kind::String),
+    (Operator::Notice, "Notice", Kind::String),
+    (Operator::Copyright, "Copyright", Kind::String),
+    (Operator::FullName, "FullName", Kind::String),
+    (Operator::FamilyName, "FamilyName", Kind::String),
+    (Operator::FontName, "FontName", Kind::String),
+    (Operator::Weight, "Weight", Kind::String),
+    (Operator::IsFixedPitch, "isFixedPitch", Kind::Number),
+    (Operator::ItalicAngle, "ItalicAngle", Kind::Number),
+    (
+        Operator::UnderlinePosition,
+        "UnderlinePosition",
+        Kind::Number,
+    ),
+    (
+        Operator::UnderlineThickness,
+        "UnderlineThickness",
+        Kind::Number,
+    ),
+    (Operator::PaintType, "PaintType", Kind::Number),
+    (Operator::CharStringType, "CharstringType", Kind::Number),
+    (Operator::FontMatrix, "FontMatrix", Kind::Number),
+    (Operator::UniqueID, "UniqueID", Kind::Number),
+    (Operator::FontBBox, "FontBBox", Kind::Number),
+    (Operator::StrokeWidth, "StrokeWidth", Kind::Number),
+    (Operator::XUID, "XUID", Kind::Number),
+    (Operator::SyntheticBase, "SyntheticBase", Kind::Number),
+    (Operator::PostScript, "PostScript", Kind::String),
+    (Operator::BaseFontName, "BaseFontName", Kind::String),
+    (Operator::BaseFontBlend, "BaseFontBlend", Kind::Delta),
+    (Operator::CIDFontVersion, "CIDFontVersion", Kind::Number),
+    (Operator::CIDFontRevision, "CIDFontRevision", Kind::Number),
+    (Operator::CIDFontType, "CIDFontType", Kind::Number),
+    (Operator::CIDCount, "CIDCount", Kind::Number),
+    (Operator::UIDBase, "UIDBase", Kind::Number),
+    (Operator::BlueValues, "BlueValues", Kind::Delta),
+    (Operator::OtherBlues, "OtherBlues", Kind::Delta),
+    (Operator::FamilyBlues, "FamilyBlues", Kind::Delta),
+    (Operator::FamilyOtherBlues, "FamilyOtherBlues", Kind::Delta),
+    (Operator::BlueScale, "BlueScale", Kind::Number),
+    (Operator::BlueShift, "BlueShift", Kind::Number),
+    (Operator::BlueFuzz, "BlueFuzz", Kind::Number),
+    (Operator::StdHW, "StdHW", Kind::Number),
+    (Operator::StdVW, "StdVW", Kind::Number),
+    (Operator::StemSnapH, "StemSnapH", Kind::Delta),
+    (Operator::StemSnapV, "StemSnapV", Kind::Delta),
+    (Operator::ForceBold, "ForceBold", Kind::Number),
+    (Operator::LanguageGroup, "LanguageGroup", Kind::Number),
+    (Operator::ExpansionFactor, "ExpansionFactor", Kind::Number),
+    (
+        Operator::InitialRandomSeed,
+        "initialRandomSeed",
+        Kind::Number,
+    ),
+    (Operator::DefaultWidthX, "defaultWidthX", Kind::Number),
+    (Operator::NominalWidthX, "nominalWidthX", Kind::Number),
+];
+
+/// Export a font set.
+///
+/// Operations whose operands are offsets are omitted, as the corresponding
+/// structures are given in place.
+pub fn export(font_set: &FontSet) -> Result<String> {
+    let mut writer = Writer::default();
+    let mut global_calls = HashMap::new();
+    writer.open("CFF", &[]);
+    writer.empty("major", &[("value", font_set.header.major.to_string())]);
+    writer.empty("minor", &[("value", font_set.header.minor.to_string())]);
+    for (i, name) in font_set.names.iter().enumerate() {
+        let name = String::from_utf8_lossy(name).into_owned();
+        writer.open("CFFFont", &[("name", name)]);
+        export_font(&mut writer, font_set, i, &mut global_calls)?;
+        writer.close("CFFFont");
+    }
+    writer.open("GlobalSubrs", &[]);
+    for (i, code) in font_set.subroutines.iter().enumerate() {
+        let (call, local) = match global_calls.get(&i) {
+            Some(&(call, local)) => (call, local),
+            _ => (uncalled(type2::Operator::CallGSubr, i), &[][..]),
+        };
+        let tokens = type2::tokenize_subroutine(code, &font_set.subroutines, local, &call)?;
+        export_program(&mut writer, &[("index", i.to_string())], &tokens);
+    }
+    writer.close("GlobalSubrs");
+    writer.close("CFF");
+    Ok(writer.finish())
+}
+
+/// Import a font set.
+///
+/// The operations whose operands are offsets are omitted, as they are only
+/// meaningful in the binary form.
+pub fn import(text: &str) -> Result<FontSet> {
+    let root = xml::parse(text)?;
+    let root = match root.name.as_str() {
+        "CFF" => &root,
+        "ttFont" => match root.child("CFF") {
+            Some(element) => element,
+            _ => raise!("found no CFF table"),
+        },
+        name => raise!("found an unknown element ({name})"),
+    };
+    let major = match root
+        .child("major")
+        .and_then(|element| element.attribute("value"))
+    {
+        Some(value) => parse(value)?,
+        _ => 1,
+    };
+    let minor = match root
+        .child("minor")
+        .and_then(|element| element.attribute("value"))
+    {
+        Some(value) => parse(value)?,
+        _ => 0,
+    };
+    let subroutines = match root.child("GlobalSubrs") {
+        Some(element) => import_subroutines(element)?,
+        _ => vec![],
+    };
+    let mut font_set = FontSet {
+        header: Header {
+            major,
+            minor,
+            header_size: 4,
+            offset_size: 4,
+        },
+        names: Names(Index::default()),
+        operations: vec![],
+        strings: Strings::default(),
+        subroutines: Subroutines(subroutines.into()),
+        encodings: vec![],
+        character_strings: vec![],
+        character_sets: vec![],
+        records: vec![],
+    };
+    for element in root.children.iter() {
+        match element.name.as_str() {
+            "CFFFont" => import_font(&mut font_set, element)?,
+            "major" | "minor" | "GlobalSubrs" => {}
+            name => raise!("found an unknown element ({name})"),
+        }
+    }
+    Ok(font_set)
+}
+
+fn export_font<'l>(
+    writer: &mut Writer,
+    font_set: &'l FontSet,
+    i: usize,
+    global_calls: &mut HashMap<usize, (Call, &'l [Vec<u8>])>,
+) -> Result<()> {
+    let strings = &font_set.strings;
+    let global = &font_set.subroutines;
+    let character_strings = &font_set.character_strings[i];
+    let names = glyph_names(font_set, i)?;
+    let (locals, dictionary_ids) = match &font_set.records[i] {
+        Record::CharacterIDKeyed(record) => {
+            let mut dictionary_ids = Vec::with_capacity(character_strings.len());
+            for glyph_id in 0..character_strings.len() {
+                match record.encoding.get(glyph_id as GlyphID) {
+                    Some(id) if (id as usize) < record.records.len() => dictionary_ids.push(id),
+                    _ => raise!("found no dictionary for glyph {glyph_id}"),
+                }
+            }
+            let locals = record
+                .records
+                .iter()
+                .map(|record| &record.subroutines[..])
+                .collect::<Vec<_>>();
+            (locals, Some(dictionary_ids))
+        }
+        Record::CharacterNameKeyed(record) => (vec![&record.subroutines[..]], None),
+    };
+    let mut local_calls = HashMap::new();
+    for (glyph_id, code) in character_strings.iter().enumerate() {
+        let j = dictionary_ids
+            .as_ref()
+            .map_or(0, |ids| ids[glyph_id] as usize);
+        for call in type2::trace(code, global, locals[j])? {
+            if call.operator == type2::Operator::CallSubr {
+                local_calls.entry((j, call.index)).or_insert(call);
+            } else {
+                global_calls.entry(call.index).or_insert((call, locals[j]));
+            }
+        }
+    }
+    match &font_set.records[i] {
+        Record::CharacterIDKeyed(record) => {
+            writer.empty(
+                "ROS",
+                &[
+                    ("Registry", get_string(strings, record.registry)?),
+                    ("Order", get_string(strings, record.ordering)?),
+                    ("Supplement", record.supplement.to_string()),
+                ],
+            );
+            export_operations(writer, &font_set.operations[i], strings)?;
+            let format = match record.encoding {
+                character_id_keyed::Encoding::Format0(_) => 0,
+                character_id_keyed::Encoding::Format3(_) => 3,
+                character_id_keyed::Encoding::Missing => {
+                    raise!("found a missing glyph-to-dictionary encoding")
+                }
+            };
+            writer.empty("FDSelect", &[("format", format.to_string())]);
+            writer.open("FDArray", &[]);
+            for (j, (operations, record)) in
+                record.operations.iter().zip(&record.records).enumerate()
+            {
+                writer.open("FontDict", &[("index", j.to_string())]);
+                export_operations(writer, operations, strings)?;
+                export_private(
+                    writer,
+                    &record.operations,
+                    &record.subroutines,
+                    global,
+                    |index| local_calls.get(&(j, index)).copied(),
+                    strings,
+                )?;
+                writer.close("FontDict");
+            }
+            writer.close("FDArray");
+        }
+        Record::CharacterNameKeyed(record) => {
+            export_operations(writer, &font_set.operations[i], strings)?;
+            export_encoding(writer, &font_set.encodings[i], &names, strings)?;
+            export_private(
+                writer,
+                &record.operations,
+                &record.subroutines,
+                global,
+                |index| local_calls.get(&(0, index)).copied(),
+                strings,
+            )?;
+        }
+    }
+    writer.open("CharStrings", &[]);
+    for (glyph_id, code) in character_strings.iter().enumerate() {
+        let mut attributes = vec![("name", names[glyph_id].clone())];
+        let mut j = 0;
+        if let Some(dictionary_ids) = &dictionary_ids {
+            j = dictionary_ids[glyph_id] as usize;
+            attributes.push(("fdSelectIndex", j.to_string()));
+        }
+        let tokens = type2::tokenize(code, global, locals[j])?;
+        export_program(writer, &attributes, &tokens);
+    }
+    writer.close("CharStrings");
+    Ok(())
+}
+
+fn export_operations(
+    writer: &mut Writer,
+    operations: &Operations,
+    strings: &Strings,
+) -> Result<()> {
+    for &(operator, name, kind) in ENTRIES {
+        let operands = match operations.0.get(&operator) {
+            Some(operands) => operands,
+            _ => continue,
+        };
+        let value = match kind {
+            Kind::Number => join(operands.iter().copied()),
+            Kind::Delta => {
+                let mut value = Number::Integer(0);
+                join(operands.iter().map(|&operand| {
+                    value = value + operand;
+                    value
+                }))
+            }
+            Kind::String => match operands[..] {
+                [operand] => get_string(strings, operand.try_into()?)?,
+                _ => raise!("found a malformed operation with operator {operator:?}"),
+            },
+        };
+        writer.empty(name, &[("value", value)]);
+    }
+    Ok(())
+}
+
+fn export_encoding(
+    writer: &mut Writer,
+    encoding: &Encoding,
+    names: &[String],
+    strings: &Strings,
+) -> Result<()> {
+    let mut mapping = vec![];
+    let mut supplements: &[Supplement] = &[];
+    match encoding {
+        Encoding::Standard => {
+            writer.empty("Encoding", &[("name", "StandardEncoding".into())]);
+            return Ok(());
+        }
+        Encoding::Expert => {
+            writer.empty("Encoding", &[("name", "ExpertEncoding".into())]);
+            return Ok(());
+        }
+        Encoding::Format0(encoding) => {
+            for (i, &code) in encoding.codes.iter().enumerate() {
+                mapping.push((code, i + 1));
+            }
+        }
+        Encoding::Format1(encoding) => {
+            let mut glyph_id = 1;
+            for range in encoding.ranges.iter() {
+                for code in 0..=range.left_count as u16 {
+                    mapping.push(((range.first_code as u16 + code) as u8, glyph_id));
+                    glyph_id += 1;
+                }
+            }
+        }
+        Encoding::FormatSupplemental(encoding) => {
+            for (i, code) in encoding.codes().into_iter().enumerate() {
+                mapping.push((code, i + 1));
+            }
+            supplements = &encoding.supplements;
+        }
+        Encoding::Missing => raise!("found a missing encoding"),
+    }
+    writer.open("Encoding", &[]);
+    for (code, glyph_id) in mapping {
+        match names.get(glyph_id) {
+            Some(name) => export_map(writer, code, name.clone()),
+            _ => raise!("found a malformed encoding"),
+        }
+    }
+    for supplement in supplements.iter() {
+        let name = get_string(strings, supplement.glyph)?;
+        export_map(writer, supplement.code, name);
+    }
+    writer.close("Encoding");
+    Ok(())
+}
+
+#[inline]
+fn export_map(writer: &mut Writer, code: u8, name: String) {
+    writer.empty("map", &[("code", format!("{code:#04x}")), ("name", name)]);
+}
+
+fn export_private<F>(
+    writer: &mut Writer,
+    operations: &Operations,
+    subroutines: &Subroutines,
+    global: &[Vec<u8>],
+    calls: F,
+    strings: &Strings,
+) -> Result<()>
+where
+    F: Fn(usize) -> Option<Call>,
+{
+    writer.open("Private", &[]);
+    export_operations(writer, operations, strings)?;
+    if !subroutines.is_empty() {
+        writer.open("Subrs", &[]);
+        for (i, code) in subroutines.iter().enumerate() {
+            let call = calls(i).unwrap_or_else(|| uncalled(type2::Operator::CallSubr, i));
+            let tokens = type2::tokenize_subroutine(code, global, subroutines, &call)?;
+            export_program(writer, &[("index", i.to_string())], &tokens);
+        }
+        writer.close("Subrs");
+    }
+    writer.close("Private");
+    Ok(())
+}
+
+fn export_program(writer: &mut Writer, attributes: &[(&str, String)], tokens: &[Token]) {
+    writer.open("CharString", attributes);
+    let mut line = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        line.push(token.to_string());
+        let done = match token {
+            Token::Operand(_) => false,
+            Token::Operator(_) => !matches!(tokens.get(i + 1), Some(Token::Mask(_))),
+            Token::Mask(_) => true,
+        };
+        if done {
+            writer.line(&line.join(" "));
+            line.clear();
+        }
+    }
+    if !line.is_empty() {
+        writer.line(&line.join(" "));
+    }
+    writer.close("CharString");
+}
+
+fn import_font(font_set: &mut FontSet, element: &Element) -> Result<()> {
+    let name = match element.attribute("name") {
+        Some(name) => name,
+        _ => raise!("found a font without a name"),
+    };
+    let mut operations = Operations::default();
+    let mut ros = None;
+    let mut encoding = None;
+    let mut private = None;
+    let mut character_strings = None;
+    let mut format = None;
+    let mut dictionaries = vec![];
+    for child in element.children.iter() {
+        match child.name.as_str() {
+            "ROS" => {
+                let (registry, ordering, supplement) = match (
+                    child.attribute("Registry"),
+                    child.attribute("Order"),
+                    child.attribute("Supplement"),
+                ) {
+                    (Some(registry), Some(ordering), Some(supplement)) => {
+                        (registry, ordering, supplement.parse::<Number>()?)
+                    }
+                    _ => raise!("found a malformed ROS element"),
+                };
+                let registry = font_set.strings.insert(registry);
+                let ordering = font_set.strings.insert(ordering);
+                operations.insert(
+                    Operator::ROS,
+                    vec![
+                        Number::Integer(registry as i32),
+                        Number::Integer(ordering as i32),
+                        supplement,
+                    ],
+                );
+                ros = Some((registry, ordering, supplement));
+            }
+            "Encoding" => encoding = Some(child),
+            "Private" => private = Some(import_private(child, &mut font_set.strings)?),
+            "CharStrings" => character_strings = Some(child),
+            "FDSelect" => match child.attribute("format") {
+                Some(value) => format = Some(parse::<u8>(value)?),
+                _ => raise!("found a malformed FDSelect element"),
+            },
+            "FDArray" => {
+                for child in child.children.iter() {
+                    let mut operations = Operations::default();
+                    let mut private = None;
+                    for child in child.children.iter() {
+                        match child.name.as_str() {
+                            "Private" => {
+                                private = Some(import_private(child, &mut font_set.strings)?)
+                            }
+                            _ => import_operation(&mut operations, child, &mut font_set.strings)?,
+                        }
+                    }
+                    match private {
+                        Some(private) => dictionaries.push((operations, private)),
+                        _ => raise!("found a font dictionary without a Private element"),
+                    }
+                }
+            }
+            _ => import_operation(&mut operations, child, &mut font_set.strings)?,
+        }
+    }
+    let character_strings = match character_strings {
+        Some(element) => element,
+        _ => raise!("found a font without a CharStrings element"),
+    };
+    let mut names = vec![];
+    let mut dictionary_ids = vec![];
+    let mut codes = vec![];
+    for child in character_strings.children.iter() {
+        let name = match child.attribute("name") {
+            Some(name) => name,
+            _ => raise!("found a character string without a name"),
+        };
+        let (local, dictionary_id) = match ros {
+            Some(_) => {
+                let dictionary_id = match child.attribute("fdSelectIndex") {
+                    Some(value) => parse::<u8>(value)?,
+                    _ => 0,
+                };
+                match dictionaries.get(dictionary_id as usize) {
+                    Some((_, (_, subroutines))) => (&subroutines[..], dictionary_id),
+                    _ => raise!("found no font dictionary for glyph {name}"),
+                }
+            }
+            _ => match &private {
+                Some((_, subroutines)) => (&subroutines[..], 0),
+                _ => raise!("found a font without a Private element"),
+            },
+        };
+        codes.push(type2::assemble(&child.text, &font_set.subroutines, local)?);
+        names.push(name);
+        dictionary_ids.push(dictionary_id);
+    }
+    if names.first() != Some(&".notdef") {
+        raise!("found no .notdef glyph");
+    }
+    let mut string_ids = vec![0];
+    for &name in &names[1..] {
+        string_ids.push(match ros {
+            Some(_) => match name.strip_prefix("cid").map(str::parse::<StringID>) {
+                Some(Ok(string_id)) => string_id,
+                _ => raise!("found a malformed glyph name ({name})"),
+            },
+            _ => font_set.strings.insert(name),
+        });
+    }
+    let character_set = [
+        CharacterSet::ISOAdobe,
+        CharacterSet::Expert,
+        CharacterSet::ExpertSubset,
+    ]
+    .into_iter()
+    .find(|character_set| {
+        ros.is_none()
+            && string_ids.iter().enumerate().all(|(glyph_id, &string_id)| {
+                character_set.get_string_id(glyph_id as GlyphID) == Some(string_id)
+            })
+    })
+    .unwrap_or_else(|| {
+        CharacterSet::Format0(CharacterSet0 {
+            format: 0,
+            glyphs: string_ids[1..].to_vec(),
+        })
+    });
+    let record = match (ros, format) {
+        (Some((registry, ordering, supplement)), Some(format)) => {
+            let encoding = match format {
+                0 => character_id_keyed::Encoding::Format0(character_id_keyed::Encoding0 {
+                    format,
+                    dictionary_ids,
+                }),
+                3 => {
+                    let mut ranges: Vec<character_id_keyed::Range3> = vec![];
+                    for (glyph_id, &dictionary_id) in dictionary_ids.iter().enumerate() {
+                        if ranges.last().map(|range| range.dictionary_id) != Some(dictionary_id) {
+                            ranges.push(character_id_keyed::Range3 {
+                                first_glyph_id: glyph_id as GlyphID,
+                                dictionary_id,
+                            });
+                        }
+                    }
+                    character_id_keyed::Encoding::Format3(character_id_keyed::Encoding3 {
+                        format,
+                        range_count: ranges.len() as u16,
+                        ranges,
+                        glyph_count: dictionary_ids.len() as u16,
+                    })
+                }
+                _ => {
+                    raise!("found an unknown format of the glyph-to-dictionary encoding ({format})")
+                }
+            };
+            let (operations, records) = dictionaries
+                .into_iter()
+                .map(|(operations, (inner_operations, subroutines))| {
+                    (
+                        operations,
+                        character_id_keyed::RecordInner {
+                            operations: inner_operations,
+                            subroutines,
+                        },
+                    )
+                })
+                .unzip();
+            Record::CharacterIDKeyed(character_id_keyed::Record {
+                registry,
+                ordering,
+                supplement,
+                encoding,
+                operations,
+                records,
+            })
+        }
+        (Some(_), _) => raise!("found a character-ID-keyed font without an FDSelect element"),
+        (_, _) => match private {
+            Some((operations, subroutines)) => {
+                Record::CharacterNameKeyed(character_name_keyed::Record {
+                    operations,
+                    subroutines,
+                })
+            }
+            _ => raise!("found a font without a Private element"),
+        },
+    };
+    let encoding = match encoding {
+        Some(element) => import_encoding(element, &names)?,
+        _ => Encoding::Standard,
+    };
+    font_set.names.0.push(name.as_bytes().to_vec());
+    font_set.operations.push(operations);
+    font_set.encodings.push(encoding);
+    font_set
+        .character_strings
+        .push(CharacterStrings(codes.into()));
+    font_set.character_sets.push(character_set);
+    font_set.records.push(record);
+    Ok(())
+}
+
+fn import_operation(
+    operations: &mut Operations,
+    element: &Element,
+    strings: &mut Strings,
+) -> Result<()> {
+    let (operator, kind) = match ENTRIES.iter().find(|(_, name, _)| *name == element.name) {
+        Some(&(operator, _, kind)) => (operator, kind),
+        _ => raise!("found an unknown element ({})", element.name),
+    };
+    let value = match element.attribute("value") {
+        Some(value) => value,
+        _ => raise!("found an element without a value ({})", element.name),
+    };
+    let operands = match kind {
+        Kind::Number => value
+            .split_whitespace()
+            .map(str::parse)
+            .collect::<Result<Vec<Operand>>>()?,
+        Kind::Delta => {
+            let mut last = Number::Integer(0);
+            let mut operands = vec![];
+            for value in value.split_whitespace() {
+                let value = value.parse::<Number>()?;
+                operands.push(value - last);
+                last = value;
+            }
+            operands
+        }
+        Kind::String => vec![Number::Integer(strings.insert(value) as i32)],
+    };
+    operations.insert(operator, operands);
+    Ok(())
+}
+
+fn import_encoding(element: &Element, names: &[&str]) -> Result<Encoding> {
+    match element.attribute("name") {
+        Some("StandardEncoding") => return Ok(Encoding::Standard),
+        Some("ExpertEncoding") => return Ok(Encoding::Expert),
+        Some(name) => raise!("found an unknown encoding ({name})"),
+        _ => {}
+    }
+    let mut mapping = HashMap::new();
+    for child in element.children.iter() {
+        let (code, name) = match (child.attribute("code"), child.attribute("name")) {
+            (Some(code), Some(name)) => (code, name),
+            _ => raise!("found a malformed encoding"),
+        };
+        let code = match code.strip_prefix("0x") {
+            Some(code) => u8::from_str_radix(code, 16).ok(),
+            _ => code.parse().ok(),
+        };
+        match code {
+            Some(code) => mapping.entry(name).or_insert(code),
+            _ => raise!("found a malformed encoding"),
+        };
+    }
+    let codes = names[1..]
+        .iter()
+        .map_while(|name| mapping.get(name).copied())
+        .collect::<Vec<_>>();
+    if codes.len() != mapping.len() || codes.len() > u8::MAX as usize {
+        raise!("found an unsupported encoding");
+    }
+    Ok(Encoding::Format0(Encoding0 {
+        format: 0,
+        code_count: codes.len() as u8,
+        codes,
+    }))
+}
+
+fn import_private(element: &Element, strings: &mut Strings) -> Result<(Operations, Subroutines)> {
+    let mut operations = Operations::default();
+    let mut subroutines = vec![];
+    for child in element.children.iter() {
+        match child.name.as_str() {
+            "Subrs" => subroutines = import_subroutines(child)?,
+            _ => import_operation(&mut operations, child, strings)?,
+        }
+    }
+    Ok((operations, Subroutines(subroutines.into())))
+}
+
+fn import_subroutines(element: &Element) -> Result<Vec<Vec<u8>>> {
+    let mut subroutines = vec![];
+    for child in element.children.iter() {
+        if child.name != "CharString" {
+            raise!("found an unknown element ({})", child.name);
+        }
+        subroutines.push(type2::assemble(&child.text, &[], &[])?);
+    }
+    Ok(subroutines)
+}
+
+fn glyph_names(font_set: &FontSet, i: usize) -> Result<Vec<String>> {
+    let keyed = matches!(font_set.records[i], Record::CharacterIDKeyed(_));
+    let character_set = &font_set.character_sets[i];
+    let mut names = vec![];
+    for glyph_id in 0..font_set.character_strings[i].len() {
+        let string_id = match character_set.get_string_id(glyph_id as GlyphID) {
+            Some(string_id) => string_id,
+            _ => raise!("found no name for glyph {glyph_id}"),
+        };
+        names.push(match glyph_id {
+            0 => ".notdef".into(),
+            _ if keyed => format!("cid{string_id:05}"),
+            _ => get_string(&font_set.strings, string_id)?,
+        });
+    }
+    Ok(names)
+}
+
+fn get_string(strings: &Strings, string_id: StringID) -> Result<String> {
+    match strings.get(string_id) {
+        Some(string) => Ok(string),
+        _ => raise!("found no string with identifier {string_id}"),
+    }
+}
+
+#[inline]
+fn uncalled(operator: type2::Operator, index: usize) -> Call {
+    Call {
+        operator,
+        index,
+        stems: 0,
+        depth: 0,
+    }
+}
+
+fn join<T: Iterator<Item = Number>>(numbers: T) -> String {
+    numbers
+        .map(|number| number.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn parse<T: std::str::FromStr>(value: &str) -> Result<T> {
+    match value.parse() {
+        Ok(value) => Ok(value),
+        _ => raise!("found a malformed value ({value})"),
+    }
+}
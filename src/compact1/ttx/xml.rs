@@ -0,0 +1,287 @@
+use crate::Result;
+
+/// An element.
+#[derive(Clone, Debug, Default)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub children: Vec<Element>,
+    pub text: String,
+}
+
+/// A writer of elements.
+#[derive(Default)]
+pub struct Writer {
+    buffer: String,
+    depth: usize,
+}
+
+struct Parser<'l> {
+    text: &'l str,
+    position: usize,
+}
+
+impl Element {
+    /// Return the value of an attribute.
+    pub fn attribute(&self, name: &str) -> Option<&str> {
+        self.attributes
+            .iter()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// Return the first child with a name.
+    pub fn child(&self, name: &str) -> Option<&Element> {
+        self.children.iter().find(|child| child.name == name)
+    }
+}
+
+impl Writer {
+    /// Open an element.
+    pub fn open(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.tag(name, attributes, false);
+        self.depth += 1;
+    }
+
+    /// Close an element.
+    pub fn close(&mut self, name: &str) {
+        self.depth -= 1;
+        self.indent();
+        self.buffer.push_str(&format!("</{name}>\n"));
+    }
+
+    /// Write an element without content.
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.tag(name, attributes, true);
+    }
+
+    /// Write a line of text.
+    pub fn line(&mut self, text: &str) {
+        self.indent();
+        self.buffer.push_str(&escape(text));
+        self.buffer.push('\n');
+    }
+
+    /// Return the written text.
+    pub fn finish(self) -> String {
+        self.buffer
+    }
+
+    fn tag(&mut self, name: &str, attributes: &[(&str, String)], empty: bool) {
+        self.indent();
+        self.buffer.push('<');
+        self.buffer.push_str(name);
+        for (key, value) in attributes {
+            self.buffer
+                .push_str(&format!(" {key}=\"{}\"", escape(value)));
+        }
+        self.buffer.push_str(if empty { "/>\n" } else { ">\n" });
+    }
+
+    fn indent(&mut self) {
+        for _ in 0..self.depth {
+            self.buffer.push_str("  ");
+        }
+    }
+}
+
+/// Parse an element.
+pub fn parse(text: &str) -> Result<Element> {
+    let mut parser = Parser { text, position: 0 };
+    parser.skip_miscellany()?;
+    let element = parser.element()?;
+    parser.skip_miscellany()?;
+    if parser.position < text.len() {
+        raise!("found trailing content in XML");
+    }
+    Ok(element)
+}
+
+impl<'l> Parser<'l> {
+    fn element(&mut self) -> Result<Element> {
+        self.expect("<")?;
+        let mut element = Element {
+            name: self.name()?,
+            ..Default::default()
+        };
+        loop {
+            self.skip_whitespace();
+            if self.consume("/>") {
+                return Ok(element);
+            }
+            if self.consume(">") {
+                break;
+            }
+            let key = self.name()?;
+            self.skip_whitespace();
+            self.expect("=")?;
+            self.skip_whitespace();
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => raise!("found a malformed attribute in XML ({key})"),
+            };
+            self.position += 1;
+            let value = match self.rest().find(quote) {
+                Some(end) => &self.text[self.position..self.position + end],
+                _ => raise!("found a malformed attribute in XML ({key})"),
+            };
+            self.position += value.len() + 1;
+            element.attributes.push((key, unescape(value)?));
+        }
+        loop {
+            if self.consume("</") {
+                let name = self.name()?;
+                self.skip_whitespace();
+                self.expect(">")?;
+                if name != element.name {
+                    raise!("found a mismatched closing tag in XML ({name})");
+                }
+                return Ok(element);
+            }
+            if self.rest().starts_with("<!--") {
+                self.skip_comment()?;
+            } else if self.rest().starts_with('<') {
+                element.children.push(self.element()?);
+            } else {
+                let end = match self.rest().find('<') {
+                    Some(end) => end,
+                    _ => raise!("found an unclosed element in XML ({})", element.name),
+                };
+                let text = &self.text[self.position..self.position + end];
+                element.text.push_str(&unescape(text)?);
+                self.position += end;
+            }
+        }
+    }
+
+    fn name(&mut self) -> Result<String> {
+        let end = self
+            .rest()
+            .find(|character: char| {
+                !(character.is_alphanumeric() || matches!(character, '_' | '-' | '.' | ':'))
+            })
+            .unwrap_or(self.rest().len());
+        if end == 0 {
+            raise!("found a malformed name in XML");
+        }
+        let name = self.text[self.position..self.position + end].to_string();
+        self.position += end;
+        Ok(name)
+    }
+
+    fn skip_miscellany(&mut self) -> Result<()> {
+        loop {
+            self.skip_whitespace();
+            if self.rest().starts_with("<?") {
+                match self.rest().find("?>") {
+                    Some(end) => self.position += end + 2,
+                    _ => raise!("found an unclosed declaration in XML"),
+                }
+            } else if self.rest().starts_with("<!--") {
+                self.skip_comment()?;
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    fn skip_comment(&mut self) -> Result<()> {
+        match self.rest().find("-->") {
+            Some(end) => self.position += end + 3,
+            _ => raise!("found an unclosed comment in XML"),
+        }
+        Ok(())
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.position += rest.len() - rest.trim_start().len();
+    }
+
+    fn consume(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.position += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, token: &str) -> Result<()> {
+        if !self.consume(token) {
+            raise!("found malformed XML, expecting {token}");
+        }
+        Ok(())
+    }
+
+    #[inline]
+    fn rest(&self) -> &'l str {
+        &self.text[self.position..]
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    for character in text.chars() {
+        match character {
+            '&' => result.push_str("&amp;"),
+            '<' => result.push_str("&lt;"),
+            '>' => result.push_str("&gt;"),
+            '"' => result.push_str("&quot;"),
+            _ => result.push(character),
+        }
+    }
+    result
+}
+
+fn unescape(text: &str) -> Result<String> {
+    let mut result = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find('&') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start + 1..];
+        let end = match rest.find(';') {
+            Some(end) => end,
+            _ => raise!("found a malformed entity in XML"),
+        };
+        let entity = &rest[..end];
+        let value = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x") {
+                Some(code) => u32::from_str_radix(code, 16).ok(),
+                _ => entity.strip_prefix('#').and_then(|code| code.parse().ok()),
+            }
+            .and_then(char::from_u32),
+        };
+        match value {
+            Some(value) => result.push(value),
+            _ => raise!("found an unknown entity in XML ({entity})"),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse() {
+        let element = super::parse(
+            "<?xml version=\"1.0\"?>\n\
+             <!-- comment -->\n\
+             <a b=\"1 &amp; 2\">x<c/> &#x41;<d e='f'></d></a>\n",
+        )
+        .unwrap();
+        assert_eq!(element.name, "a");
+        assert_eq!(element.attribute("b"), Some("1 & 2"));
+        assert_eq!(element.text, "x A");
+        assert_eq!(element.children.len(), 2);
+        assert_eq!(element.child("d").unwrap().attribute("e"), Some("f"));
+        assert!(super::parse("<a><b></a>").is_err());
+    }
+}
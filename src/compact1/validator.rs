@@ -0,0 +1,500 @@
+//! The validation of font sets.
+
+use crate::compact1::font_set::{character_id_keyed, Record};
+use crate::compact1::offset::minimal_size;
+use crate::compact1::{
+    CharacterSet, Encoding, FontSet, GlyphID, Index, Location, Offset, Operations, Operator,
+};
+use crate::type2::{Profile, Program};
+
+const CHARACTER_STRING_LIMIT: usize = 65535;
+const NAME_LIMIT: usize = 127;
+const STEM_LIMIT: usize = 96;
+
+const PRIVATE_OPERATORS: [Operator; 18] = [
+    Operator::BlueValues,
+    Operator::OtherBlues,
+    Operator::FamilyBlues,
+    Operator::FamilyOtherBlues,
+    Operator::StdHW,
+    Operator::StdVW,
+    Operator::Subrs,
+    Operator::DefaultWidthX,
+    Operator::NominalWidthX,
+    Operator::BlueScale,
+    Operator::BlueShift,
+    Operator::BlueFuzz,
+    Operator::StemSnapH,
+    Operator::StemSnapV,
+    Operator::ForceBold,
+    Operator::LanguageGroup,
+    Operator::ExpansionFactor,
+    Operator::InitialRandomSeed,
+];
+
+const CHARACTER_ID_OPERATORS: [Operator; 8] = [
+    Operator::ROS,
+    Operator::CIDFontVersion,
+    Operator::CIDFontRevision,
+    Operator::CIDFontType,
+    Operator::CIDCount,
+    Operator::UIDBase,
+    Operator::FDArray,
+    Operator::FDSelect,
+];
+
+const ARRAY_LIMITS: [(Operator, usize, bool); 6] = [
+    (Operator::BlueValues, 14, true),
+    (Operator::OtherBlues, 10, true),
+    (Operator::FamilyBlues, 14, true),
+    (Operator::FamilyOtherBlues, 10, true),
+    (Operator::StemSnapH, 12, false),
+    (Operator::StemSnapV, 12, false),
+];
+
+/// A validation report.
+#[derive(Clone, Debug, Default)]
+pub struct Report {
+    /// The findings.
+    pub findings: Vec<Finding>,
+}
+
+/// A finding of a validation.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Finding {
+    /// The severity.
+    pub severity: Severity,
+    /// The location.
+    pub location: Location,
+    /// The description.
+    pub message: String,
+}
+
+/// A severity of a finding.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Severity {
+    /// A deviation that consumers commonly tolerate.
+    Warning,
+    /// A violation of the specification.
+    Error,
+}
+
+#[derive(Clone, Copy, Eq, PartialEq)]
+enum Kind {
+    Top,
+    CharacterIDKeyedTop,
+    FontDictionary,
+    Private,
+}
+
+impl Report {
+    /// Validate a font set.
+    ///
+    /// Unlike parsing, which stops at the first problem, validation inspects
+    /// the whole font set and collects all findings.
+    pub fn new(font_set: &FontSet) -> Self {
+        let mut report = Self::default();
+        report.check_font_set(font_set);
+        report
+    }
+
+    /// Check if there are no errors.
+    pub fn is_valid(&self) -> bool {
+        self.findings
+            .iter()
+            .all(|finding| finding.severity != Severity::Error)
+    }
+
+    fn push<T: Into<String>>(&mut self, severity: Severity, location: Location, message: T) {
+        self.findings.push(Finding {
+            severity,
+            location,
+            message: message.into(),
+        });
+    }
+
+    fn check_font_set(&mut self, font_set: &FontSet) {
+        let count = font_set.records.len();
+        if font_set.names.len() != count
+            || font_set.operations.len() != count
+            || font_set.encodings.len() != count
+            || font_set.character_strings.len() != count
+            || font_set.character_sets.len() != count
+        {
+            self.push(
+                Severity::Error,
+                Location::FontSet,
+                "found an inconsistent number of fonts",
+            );
+            return;
+        }
+        self.check_index(&font_set.names, Location::Names);
+        for name in font_set.names.iter() {
+            if name.is_empty() || name.len() > NAME_LIMIT {
+                self.push(
+                    Severity::Error,
+                    Location::Names,
+                    format!("found a font name of invalid length ({})", name.len()),
+                );
+            }
+        }
+        self.check_index(&font_set.strings, Location::Strings);
+        self.check_index(&font_set.subroutines, Location::GlobalSubroutines);
+        for (i, record) in font_set.records.iter().enumerate() {
+            self.check_font(font_set, i, record);
+        }
+    }
+
+    fn check_font(&mut self, font_set: &FontSet, i: usize, record: &Record) {
+        let character_strings = &font_set.character_strings[i];
+        let glyph_count = character_strings.len();
+        self.check_index(character_strings, Location::CharacterStrings(i));
+        if glyph_count == 0 {
+            self.push(
+                Severity::Error,
+                Location::CharacterStrings(i),
+                "found a font without glyphs",
+            );
+        }
+        let character_set = &font_set.character_sets[i];
+        if matches!(character_set, CharacterSet::Missing) {
+            self.push(
+                Severity::Error,
+                Location::CharacterSet(i),
+                "found a missing character set",
+            );
+        } else if glyph_count > 0
+            && character_set
+                .get_string_id((glyph_count - 1) as GlyphID)
+                .is_none()
+        {
+            self.push(
+                Severity::Error,
+                Location::CharacterSet(i),
+                format!("found a character set shorter than the glyphs ({glyph_count})"),
+            );
+        }
+        if matches!(
+            character_set,
+            CharacterSet::Format0(_) | CharacterSet::Format1(_) | CharacterSet::Format2(_)
+        ) && glyph_count < usize::from(GlyphID::MAX)
+            && character_set
+                .get_string_id(glyph_count as GlyphID)
+                .is_some()
+        {
+            self.push(
+                Severity::Error,
+                Location::CharacterSet(i),
+                format!("found a character set longer than the glyphs ({glyph_count})"),
+            );
+        }
+        if matches!(font_set.encodings[i], Encoding::Missing) {
+            self.push(
+                Severity::Error,
+                Location::Encoding(i),
+                "found a missing encoding",
+            );
+        }
+        match record {
+            Record::CharacterIDKeyed(record) => {
+                self.check_dictionary(
+                    &font_set.operations[i],
+                    Kind::CharacterIDKeyedTop,
+                    Location::Top(i),
+                );
+                if record.operations.len() != record.records.len() {
+                    self.push(
+                        Severity::Error,
+                        Location::Top(i),
+                        "found an inconsistent number of font dictionaries",
+                    );
+                }
+                for (j, operations) in record.operations.iter().enumerate() {
+                    self.check_dictionary(
+                        operations,
+                        Kind::FontDictionary,
+                        Location::FontDictionary(i, j),
+                    );
+                }
+                for (j, record) in record.records.iter().enumerate() {
+                    self.check_dictionary(
+                        &record.operations,
+                        Kind::Private,
+                        Location::Private(i, j),
+                    );
+                    self.check_index(&record.subroutines, Location::LocalSubroutines(i, j));
+                }
+                if matches!(record.encoding, character_id_keyed::Encoding::Missing) {
+                    self.push(
+                        Severity::Error,
+                        Location::DictionaryEncoding(i),
+                        "found a missing glyph-to-dictionary encoding",
+                    );
+                } else {
+                    for glyph_id in 0..glyph_count as GlyphID {
+                        match record.encoding.get(glyph_id) {
+                            Some(j) if (j as usize) < record.records.len() => {}
+                            Some(j) => self.push(
+                                Severity::Error,
+                                Location::Glyph(i, glyph_id),
+                                format!("found a font dictionary out of range ({j})"),
+                            ),
+                            _ => self.push(
+                                Severity::Error,
+                                Location::Glyph(i, glyph_id),
+                                "found no font dictionary",
+                            ),
+                        }
+                    }
+                }
+            }
+            Record::CharacterNameKeyed(record) => {
+                self.check_dictionary(&font_set.operations[i], Kind::Top, Location::Top(i));
+                self.check_dictionary(&record.operations, Kind::Private, Location::Private(i, 0));
+                self.check_index(&record.subroutines, Location::LocalSubroutines(i, 0));
+            }
+        }
+        for (glyph_id, code) in character_strings.iter().enumerate() {
+            let glyph_id = glyph_id as GlyphID;
+            let location = Location::Glyph(i, glyph_id);
+            if code.len() > CHARACTER_STRING_LIMIT {
+                self.push(
+                    Severity::Error,
+                    location,
+                    format!(
+                        "found a character string of excessive size ({})",
+                        code.len()
+                    ),
+                );
+            }
+            if let Some(local) = record.subroutines(glyph_id) {
+                self.check_glyph(code, &font_set.subroutines, local, location);
+            }
+        }
+    }
+
+    fn check_index(&mut self, index: &Index, location: Location) {
+        if index.count as usize != index.data.len() {
+            self.push(
+                Severity::Error,
+                location,
+                format!(
+                    "found an index with an inconsistent count ({})",
+                    index.count
+                ),
+            );
+        }
+        if index.data.is_empty() || index.offsets.is_empty() {
+            return;
+        }
+        let offsets = &index.offsets;
+        if offsets.len() != index.data.len() + 1 {
+            self.push(
+                Severity::Error,
+                location,
+                format!(
+                    "found an index with an inconsistent number of offsets ({})",
+                    offsets.len(),
+                ),
+            );
+            return;
+        }
+        if offsets[0] != Offset(1) {
+            self.push(
+                Severity::Error,
+                location,
+                format!(
+                    "found an index with a malformed first offset ({})",
+                    offsets[0].0
+                ),
+            );
+        }
+        if let Some(i) = offsets.windows(2).position(|pair| pair[0] > pair[1]) {
+            self.push(
+                Severity::Error,
+                location,
+                format!("found an index with non-monotonic offsets ({i})"),
+            );
+            return;
+        }
+        if let Some(i) = index
+            .data
+            .iter()
+            .enumerate()
+            .position(|(i, chunk)| (offsets[i + 1].0 - offsets[i].0) as usize != chunk.len())
+        {
+            self.push(
+                Severity::Error,
+                location,
+                format!("found an index with offsets inconsistent with the data ({i})"),
+            );
+        }
+        let end = offsets[offsets.len() - 1].0;
+        if !(1..=4).contains(&index.offset_size) || index.offset_size < minimal_size(end) {
+            self.push(
+                Severity::Error,
+                location,
+                format!(
+                    "found an index with an invalid offset size ({})",
+                    index.offset_size
+                ),
+            );
+        }
+    }
+
+    fn check_dictionary(&mut self, operations: &Operations, kind: Kind, location: Location) {
+        let mut operators = operations.keys().copied().collect::<Vec<_>>();
+        operators.sort_by_key(Operator::code);
+        for operator in operators {
+            let operands = &operations[&operator];
+            match arity(operator) {
+                Some(count) if operands.len() != count => self.push(
+                    Severity::Error,
+                    location,
+                    format!(
+                        "found a wrong number of operands for operator {operator:?} ({})",
+                        operands.len(),
+                    ),
+                ),
+                None if operands.is_empty() => self.push(
+                    Severity::Error,
+                    location,
+                    format!("found no operands for operator {operator:?}"),
+                ),
+                _ => {}
+            }
+            let private = PRIVATE_OPERATORS.contains(&operator);
+            let character_id = CHARACTER_ID_OPERATORS.contains(&operator);
+            let misplaced = match kind {
+                Kind::Top => private || character_id,
+                Kind::CharacterIDKeyedTop => private,
+                Kind::FontDictionary => private || character_id,
+                Kind::Private => !private,
+            };
+            if misplaced {
+                let kind = match kind {
+                    Kind::Top | Kind::CharacterIDKeyedTop => "a top dictionary",
+                    Kind::FontDictionary => "a font dictionary",
+                    Kind::Private => "a private dictionary",
+                };
+                self.push(
+                    Severity::Warning,
+                    location,
+                    format!("found operator {operator:?} misplaced in {kind}"),
+                );
+            }
+        }
+        if kind != Kind::Private {
+            return;
+        }
+        for (operator, limit, paired) in ARRAY_LIMITS {
+            let operands = match operations.get(operator) {
+                Some(operands) => operands,
+                _ => continue,
+            };
+            if operands.len() > limit {
+                self.push(
+                    Severity::Error,
+                    location,
+                    format!(
+                        "found too many operands for operator {operator:?} ({})",
+                        operands.len(),
+                    ),
+                );
+            }
+            if paired && operands.len() % 2 == 1 {
+                self.push(
+                    Severity::Error,
+                    location,
+                    format!(
+                        "found an odd number of operands for operator {operator:?} ({})",
+                        operands.len(),
+                    ),
+                );
+            }
+            if operands
+                .iter()
+                .skip(1)
+                .any(|&operand| f32::from(operand) < 0.0)
+            {
+                self.push(
+                    Severity::Warning,
+                    location,
+                    format!("found unsorted operands for operator {operator:?}"),
+                );
+            }
+        }
+    }
+
+    fn check_glyph(
+        &mut self,
+        code: &[u8],
+        global: &[Vec<u8>],
+        local: &[Vec<u8>],
+        location: Location,
+    ) {
+        use crate::type2::Operator::*;
+
+        let mut program =
+            Program::with_profile(code, global, local, Profile::Compatible).with_masks();
+        let mut stems = 0;
+        loop {
+            let (operator, operands) = match program.next() {
+                Ok(Some(operation)) => operation,
+                Ok(None) => break,
+                Err(error) => {
+                    self.push(Severity::Error, location, error.to_string());
+                    break;
+                }
+            };
+            match operator {
+                HStem | VStem | HStemHM | VStemHM => stems += operands.len() >> 1,
+                HintMask | CntrMask if stems == 0 => self.push(
+                    Severity::Error,
+                    location,
+                    format!("found a mask without stems ({operator:?})"),
+                ),
+                HintMask | CntrMask => {
+                    let padding = 8 * operands.len() - stems;
+                    let last = operands[operands.len() - 1] as u8;
+                    if last & ((1u16 << padding) - 1) as u8 != 0 {
+                        self.push(
+                            Severity::Error,
+                            location,
+                            format!("found a mask inconsistent with the stems ({operator:?})"),
+                        );
+                    }
+                }
+                _ => {}
+            }
+        }
+        if stems > STEM_LIMIT {
+            self.push(
+                Severity::Warning,
+                location,
+                format!("found too many stems ({stems})"),
+            );
+        }
+        for diagnostic in program.diagnostics() {
+            self.push(
+                Severity::Warning,
+                location,
+                format!("found a deviation from the specification ({diagnostic:?})"),
+            );
+        }
+    }
+}
+
+fn arity(operator: Operator) -> Option<usize> {
+    use crate::compact1::Operator::*;
+
+    match operator {
+        BlueValues | OtherBlues | FamilyBlues | FamilyOtherBlues | StemSnapH | StemSnapV | XUID
+        | BaseFontBlend => None,
+        FontBBox => Some(4),
+        FontMatrix => Some(6),
+        Private => Some(2),
+        ROS => Some(3),
+        _ => Some(1),
+    }
+}
@@ -1,6 +1,6 @@
 //! Parser of PostScript fonts.
 
-#[macro_use(dereference, jump_take, jump_take_given, raise, table)]
+#[macro_use(dereference, jump_take, raise, table)]
 extern crate typeface;
 
 pub mod compact1;
@@ -0,0 +1,498 @@
+use std::fmt;
+use std::io::Cursor;
+
+use crate::tape::{Read, Write};
+use crate::type2::{bias, number, Operand, Operator};
+use crate::Result;
+
+const NESTING_LIMIT: usize = 10;
+
+/// A token of a charstring.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Token {
+    /// An operand.
+    Operand(Operand),
+    /// An operator.
+    Operator(Operator),
+    /// The bytes of a hint or counter mask following the corresponding operator.
+    Mask(Vec<u8>),
+}
+
+/// A subroutine call found in a charstring.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Call {
+    /// The operator, which is either `CallSubr` or `CallGSubr`.
+    pub operator: Operator,
+    /// The unbiased index of the subroutine.
+    pub index: usize,
+    /// The number of stem hints declared before the call.
+    pub stems: usize,
+    /// The number of operands on the stack before the call, excluding the index.
+    pub depth: usize,
+}
+
+struct Scanner<'l> {
+    global: &'l [Vec<u8>],
+    local: &'l [Vec<u8>],
+    depth: usize,
+    stems: usize,
+    calls: Vec<Call>,
+}
+
+/// Split a charstring into tokens without executing it.
+///
+/// Subroutines are consulted only in order to keep track of the stem hints,
+/// which determine the sizes of masks. Bytes following `endchar` are kept.
+pub fn tokenize(code: &[u8], global: &[Vec<u8>], local: &[Vec<u8>]) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    Scanner::new(global, local).scan(code, Some(&mut tokens), 0)?;
+    Ok(tokens)
+}
+
+/// Split a subroutine into tokens without executing it given a call reaching
+/// it, which determines the sizes of masks.
+pub fn tokenize_subroutine(
+    code: &[u8],
+    global: &[Vec<u8>],
+    local: &[Vec<u8>],
+    call: &Call,
+) -> Result<Vec<Token>> {
+    let mut tokens = vec![];
+    let mut scanner = Scanner::new(global, local);
+    scanner.stems = call.stems;
+    scanner.depth = call.depth;
+    scanner.scan(code, Some(&mut tokens), 0)?;
+    Ok(tokens)
+}
+
+/// Inline all subroutine calls of a charstring.
+///
+/// Subroutines are inlined without their trailing `return` operators, and the
+/// charstring is cut short if a subroutine ends it with `endchar`. Masks are
+/// sized according to the stem hints declared before them, including those
+/// declared across subroutine boundaries.
+pub fn desubroutinize(code: &[u8], global: &[Vec<u8>], local: &[Vec<u8>]) -> Result<Vec<u8>> {
+    let mut output = Vec::with_capacity(code.len());
+    Scanner::new(global, local).inline(code, &mut output, 0)?;
+    Ok(output)
+}
+
+/// Split a charstring without subroutine calls into operations given as
+/// chunks of bytes, each consisting of operands followed by an operator.
+pub(crate) fn segment(code: &[u8]) -> Result<Vec<(Option<Operator>, &[u8])>> {
+    use crate::type2::Operator::*;
+
+    let mut scanner = Scanner::new(&[], &[]);
+    let mut chunks = vec![];
+    let size = code.len() as u64;
+    let mut tape = Cursor::new(code);
+    let mut start = 0;
+    while tape.position() < size {
+        let operator = match tape.peek::<u8>()? {
+            0x1c | 0x20..=0xff => {
+                number::read(&mut tape)?;
+                scanner.depth += 1;
+                continue;
+            }
+            0x0c => Operator::from(tape.take::<u16>()?)?,
+            _ => Operator::from(tape.take::<u8>()? as u16)?,
+        };
+        scanner.update(operator);
+        match operator {
+            HintMask | CntrMask => {
+                tape.take_given::<Vec<u8>>((scanner.stems + 7) >> 3)?;
+            }
+            CallSubr | CallGSubr => raise!("found a subroutine call ({operator:?})"),
+            _ => {}
+        }
+        let end = tape.position() as usize;
+        chunks.push((Some(operator), &code[start..end]));
+        start = end;
+    }
+    if start < code.len() {
+        chunks.push((None, &code[start..]));
+    }
+    Ok(chunks)
+}
+
+/// Find the subroutine calls made by a charstring, including nested ones,
+/// without executing it.
+///
+/// Only calls whose operand is a literal referring to an existing subroutine
+/// are reported.
+pub fn trace(code: &[u8], global: &[Vec<u8>], local: &[Vec<u8>]) -> Result<Vec<Call>> {
+    let mut scanner = Scanner::new(global, local);
+    scanner.scan(code, None, 0)?;
+    Ok(scanner.calls)
+}
+
+/// Convert tokens back into a charstring replacing the operands of subroutine
+/// calls.
+///
+/// The replacement is given the operator and the operand of each call, which
+/// is required to be a literal.
+pub(crate) fn renumber<F>(tokens: &[Token], mut replace: F) -> Result<Vec<u8>>
+where
+    F: FnMut(Operator, Operand) -> Result<Operand>,
+{
+    use crate::type2::Operator::*;
+
+    let mut tape = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Operand(_)
+                if matches!(
+                    tokens.get(i + 1),
+                    Some(Token::Operator(CallSubr | CallGSubr))
+                ) => {}
+            Token::Operand(value) => number::write(&mut tape, *value)?,
+            Token::Operator(operator @ (CallSubr | CallGSubr)) => {
+                match i.checked_sub(1).map(|i| &tokens[i]) {
+                    Some(Token::Operand(value)) => {
+                        number::write(&mut tape, replace(*operator, *value)?)?
+                    }
+                    _ => raise!("found a call to a computed subroutine ({operator:?})"),
+                }
+                tape.give(operator)?;
+            }
+            Token::Operator(operator) => tape.give(operator)?,
+            Token::Mask(mask) => tape.give_bytes(mask)?,
+        }
+    }
+    Ok(tape)
+}
+
+/// Convert a charstring into text.
+///
+/// Each operation is given on a separate line, with its operands followed by
+/// the name of its operator. Subroutine calls whose operand is an integer
+/// referring to an existing subroutine are written as `callsubr#index` and
+/// `callgsubr#index` with the unbiased index, and the other ones with their
+/// operands followed by bare `callsubr` and `callgsubr`. Masks are written as
+/// sequences of bits following the corresponding operators.
+pub fn disassemble(code: &[u8], global: &[Vec<u8>], local: &[Vec<u8>]) -> Result<String> {
+    use crate::type2::Operator::*;
+
+    let tokens = tokenize(code, global, local)?;
+    let scanner = Scanner::new(global, local);
+    let locate = |i: usize| match (i.checked_sub(1).map(|i| &tokens[i]), &tokens[i]) {
+        (Some(Token::Operand(value)), Token::Operator(operator @ (CallSubr | CallGSubr))) => {
+            scanner.locate(*operator, *value)
+        }
+        _ => None,
+    };
+    let mut lines = vec![];
+    let mut line = vec![];
+    for (i, token) in tokens.iter().enumerate() {
+        match token {
+            Token::Operand(_) if i + 1 < tokens.len() && locate(i + 1).is_some() => {}
+            Token::Operand(_) => line.push(token.to_string()),
+            Token::Operator(operator @ (CallSubr | CallGSubr)) => {
+                match locate(i) {
+                    Some(index) => line.push(format!("{}#{index}", name(*operator))),
+                    _ => line.push(name(*operator)),
+                }
+                lines.push(line.join(" "));
+                line.clear();
+            }
+            Token::Operator(_) => {
+                line.push(token.to_string());
+                if !matches!(tokens.get(i + 1), Some(Token::Mask(_))) {
+                    lines.push(line.join(" "));
+                    line.clear();
+                }
+            }
+            Token::Mask(mask) => {
+                if !mask.is_empty() {
+                    line.push(token.to_string());
+                }
+                lines.push(line.join(" "));
+                line.clear();
+            }
+        }
+    }
+    if !line.is_empty() {
+        lines.push(line.join(" "));
+    }
+    Ok(lines.join("\n"))
+}
+
+/// Convert text into a charstring.
+///
+/// The text is expected to be in the format produced by `disassemble`, with
+/// tokens separated by any whitespace. Masks are allowed to be omitted if no
+/// stem hints are declared before them.
+pub fn assemble(text: &str, global: &[Vec<u8>], local: &[Vec<u8>]) -> Result<Vec<u8>> {
+    use crate::type2::Operator::*;
+
+    let operators = (0x00..0x20)
+        .chain(0x0c00..0x0c40)
+        .filter_map(|code| Operator::from(code).ok())
+        .map(|operator| (name(operator), operator))
+        .collect::<Vec<_>>();
+    let mut scanner = Scanner::new(global, local);
+    let mut tape = vec![];
+    let mut tokens = text.split_whitespace().peekable();
+    let mut last = None;
+    while let Some(token) = tokens.next() {
+        if let Ok(value) = token.parse::<Operand>() {
+            number::write(&mut tape, value)?;
+            scanner.depth += 1;
+            last = Some(value);
+            continue;
+        }
+        let (token_name, index) = match token.split_once('#') {
+            Some((token_name, index)) => (token_name, Some(index)),
+            _ => (token, None),
+        };
+        let operator = match operators.iter().find(|(name, _)| name == token_name) {
+            Some(&(_, operator)) => operator,
+            _ => raise!("found an unknown token ({token})"),
+        };
+        match (operator, index) {
+            (CallSubr | CallGSubr, Some(index)) => {
+                let count = scanner.subroutines(operator).len();
+                match index.parse::<i32>() {
+                    Ok(index) if index >= 0 && (index as usize) < count => {
+                        let value = (index - bias(count)) as Operand;
+                        number::write(&mut tape, value)?;
+                        last = Some(value);
+                    }
+                    _ => raise!("found no subroutine ({token})"),
+                }
+            }
+            (CallSubr | CallGSubr, _) => scanner.depth = scanner.depth.saturating_sub(1),
+            (_, Some(_)) => raise!("found an unknown token ({token})"),
+            _ => {}
+        }
+        tape.give(&operator)?;
+        match operator {
+            HintMask | CntrMask => {
+                scanner.update(operator);
+                match tokens.peek().and_then(|token| parse_mask(token)) {
+                    Some(mask) => {
+                        tokens.next();
+                        tape.give_bytes(&mask)?;
+                    }
+                    // A mask is empty and hence absent if there are no stems.
+                    _ if scanner.stems == 0 => {}
+                    _ => raise!("found a malformed mask ({operator:?})"),
+                }
+            }
+            CallSubr | CallGSubr => {
+                if let Some(i) = last.and_then(|value| scanner.locate(operator, value)) {
+                    let code = scanner.subroutines(operator)[i].as_slice();
+                    scanner.scan(code, None, 1)?;
+                }
+            }
+            _ => scanner.update(operator),
+        }
+        last = None;
+    }
+    Ok(tape)
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Token::Operand(value) => write!(formatter, "{value}"),
+            Token::Operator(operator) => write!(formatter, "{}", name(*operator)),
+            Token::Mask(mask) => mask
+                .iter()
+                .try_for_each(|byte| write!(formatter, "{byte:08b}")),
+        }
+    }
+}
+
+impl<'l> Scanner<'l> {
+    fn new(global: &'l [Vec<u8>], local: &'l [Vec<u8>]) -> Self {
+        Self {
+            global,
+            local,
+            depth: 0,
+            stems: 0,
+            calls: vec![],
+        }
+    }
+
+    fn scan(
+        &mut self,
+        code: &[u8],
+        mut tokens: Option<&mut Vec<Token>>,
+        nesting: usize,
+    ) -> Result<()> {
+        use crate::type2::Operator::*;
+
+        if nesting > NESTING_LIMIT {
+            raise!("found too deeply nested subroutines");
+        }
+        macro_rules! push(($token:expr) => ({
+            if let Some(tokens) = tokens.as_deref_mut() {
+                tokens.push($token);
+            }
+        }));
+        let size = code.len() as u64;
+        let mut tape = Cursor::new(code);
+        let mut last = None;
+        while tape.position() < size {
+            let operator = match tape.peek::<u8>()? {
+                0x1c | 0x20..=0xff => {
+                    let value = number::read(&mut tape)?;
+                    self.depth += 1;
+                    last = Some(value);
+                    push!(Token::Operand(value));
+                    continue;
+                }
+                0x0c => Operator::from(tape.take::<u16>()?)?,
+                _ => Operator::from(tape.take::<u8>()? as u16)?,
+            };
+            push!(Token::Operator(operator));
+            match operator {
+                HintMask | CntrMask => {
+                    self.update(operator);
+                    let mask = tape.take_given((self.stems + 7) >> 3)?;
+                    push!(Token::Mask(mask));
+                }
+                CallSubr | CallGSubr => {
+                    self.depth = self.depth.saturating_sub(1);
+                    if let Some(i) = last.and_then(|value| self.locate(operator, value)) {
+                        self.calls.push(Call {
+                            operator,
+                            index: i,
+                            stems: self.stems,
+                            depth: self.depth,
+                        });
+                        let code = self.subroutines(operator)[i].as_slice();
+                        self.scan(code, None, nesting + 1)?;
+                    }
+                }
+                Return | EndChar if tokens.is_none() => return Ok(()),
+                _ => self.update(operator),
+            }
+            last = None;
+        }
+        Ok(())
+    }
+
+    fn inline(&mut self, code: &[u8], output: &mut Vec<u8>, nesting: usize) -> Result<bool> {
+        use crate::type2::Operator::*;
+
+        if nesting > NESTING_LIMIT {
+            raise!("found too deeply nested subroutines");
+        }
+        let size = code.len() as u64;
+        let mut tape = Cursor::new(code);
+        let mut last = None;
+        while tape.position() < size {
+            let start = tape.position() as usize;
+            let operator = match tape.peek::<u8>()? {
+                0x1c | 0x20..=0xff => {
+                    let value = number::read(&mut tape)?;
+                    self.depth += 1;
+                    last = Some((value, output.len()));
+                    output.extend_from_slice(&code[start..tape.position() as usize]);
+                    continue;
+                }
+                0x0c => Operator::from(tape.take::<u16>()?)?,
+                _ => Operator::from(tape.take::<u8>()? as u16)?,
+            };
+            match operator {
+                HintMask | CntrMask => {
+                    self.update(operator);
+                    tape.take_given::<Vec<u8>>((self.stems + 7) >> 3)?;
+                }
+                CallSubr | CallGSubr => {
+                    self.depth = self.depth.saturating_sub(1);
+                    let (i, position) = match last {
+                        Some((value, position)) => match self.locate(operator, value) {
+                            Some(i) => (i, position),
+                            _ => raise!("found a call to a nonexistent subroutine ({operator:?})"),
+                        },
+                        _ => raise!("found a call to a computed subroutine ({operator:?})"),
+                    };
+                    output.truncate(position);
+                    let code = self.subroutines(operator)[i].as_slice();
+                    if self.inline(code, output, nesting + 1)? {
+                        return Ok(true);
+                    }
+                    last = None;
+                    continue;
+                }
+                Return => return Ok(false),
+                EndChar => {
+                    output.extend_from_slice(&code[start..tape.position() as usize]);
+                    return Ok(true);
+                }
+                _ => self.update(operator),
+            }
+            output.extend_from_slice(&code[start..tape.position() as usize]);
+            last = None;
+        }
+        Ok(false)
+    }
+
+    fn update(&mut self, operator: Operator) {
+        use crate::type2::Operator::*;
+
+        let delta: isize = match operator {
+            HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask => {
+                self.stems += self.depth >> 1;
+                self.depth = 0;
+                0
+            }
+            Abs | Neg | Not | Sqrt | Exch | Index | Get => 0,
+            And | Or | Eq | Add | Sub | Div | Mul | Drop => -1,
+            Put | Roll => -2,
+            IfElse => -3,
+            Random | Dup => 1,
+            _ => {
+                self.depth = 0;
+                0
+            }
+        };
+        self.depth = self.depth.saturating_add_signed(delta);
+    }
+
+    fn locate(&self, operator: Operator, value: Operand) -> Option<usize> {
+        if value.fract() != 0.0 {
+            return None;
+        }
+        let subroutines = self.subroutines(operator);
+        let i = value as i32 + bias(subroutines.len());
+        if i >= 0 && (i as usize) < subroutines.len() {
+            Some(i as usize)
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn subroutines(&self, operator: Operator) -> &'l [Vec<u8>] {
+        if operator == Operator::CallSubr {
+            self.local
+        } else {
+            self.global
+        }
+    }
+}
+
+#[inline]
+fn name(operator: Operator) -> String {
+    format!("{operator:?}").to_lowercase()
+}
+
+fn parse_mask(token: &str) -> Option<Vec<u8>> {
+    if token.is_empty() || !token.len().is_multiple_of(8) {
+        return None;
+    }
+    if !token.bytes().all(|bit| bit == b'0' || bit == b'1') {
+        return None;
+    }
+    Some(
+        token
+            .as_bytes()
+            .chunks(8)
+            .map(|bits| bits.iter().fold(0, |byte, bit| (byte << 1) | (bit - b'0')))
+            .collect(),
+    )
+}
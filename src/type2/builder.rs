@@ -0,0 +1,148 @@
+use crate::type2::{Operand, Operations, Operator, STACK_LIMIT};
+use crate::Result;
+
+/// A builder of charstrings.
+#[derive(Clone, Debug, Default)]
+pub struct Builder {
+    operations: Operations,
+    horizontal_stems: Vec<(Operand, Operand)>,
+    vertical_stems: Vec<(Operand, Operand)>,
+    width: Option<Operand>,
+    position: (Operand, Operand),
+    start: Option<(Operand, Operand)>,
+}
+
+impl Builder {
+    /// Create a builder.
+    #[inline]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the width difference with respect to the nominal width.
+    #[inline]
+    pub fn set_width(&mut self, width: Operand) {
+        self.width = Some(width);
+    }
+
+    /// Add a horizontal stem given its bottom edge and height.
+    #[inline]
+    pub fn add_horizontal_stem(&mut self, y: Operand, height: Operand) {
+        self.horizontal_stems.push((y, height));
+    }
+
+    /// Add a vertical stem given its left edge and width.
+    #[inline]
+    pub fn add_vertical_stem(&mut self, x: Operand, width: Operand) {
+        self.vertical_stems.push((x, width));
+    }
+
+    /// Start a new contour at a point.
+    pub fn move_to(&mut self, x: Operand, y: Operand) {
+        let (dx, dy) = self.advance(x, y);
+        self.operations.push((Operator::RMoveTo, vec![dx, dy]));
+        self.start = Some((x, y));
+    }
+
+    /// Draw a line to a point.
+    pub fn line_to(&mut self, x: Operand, y: Operand) {
+        self.open();
+        let (dx, dy) = self.advance(x, y);
+        self.operations.push((Operator::RLineTo, vec![dx, dy]));
+    }
+
+    /// Draw a cubic Bézier curve to a point given two control points.
+    #[allow(clippy::too_many_arguments)]
+    pub fn curve_to(
+        &mut self,
+        x1: Operand,
+        y1: Operand,
+        x2: Operand,
+        y2: Operand,
+        x: Operand,
+        y: Operand,
+    ) {
+        self.open();
+        let (dxa, dya) = self.advance(x1, y1);
+        let (dxb, dyb) = self.advance(x2, y2);
+        let (dxc, dyc) = self.advance(x, y);
+        self.operations
+            .push((Operator::RRCurveTo, vec![dxa, dya, dxb, dyb, dxc, dyc]));
+    }
+
+    /// Close the current contour.
+    ///
+    /// A final line returning to the start of the contour is dropped, as
+    /// contours are closed implicitly.
+    pub fn close(&mut self) {
+        if let Some(start) = self.start.take() {
+            if self.position == start {
+                if let Some((Operator::RLineTo, operands)) = self.operations.last() {
+                    self.position.0 -= operands[0];
+                    self.position.1 -= operands[1];
+                    self.operations.pop();
+                }
+            }
+        }
+    }
+
+    /// Return the width difference with respect to the nominal width.
+    #[inline]
+    pub fn width(&self) -> Option<Operand> {
+        self.width
+    }
+
+    /// Return the operations.
+    ///
+    /// Stems are split across several operators only when they exceed the
+    /// capacity of the stack, with the positions of each operator starting
+    /// from zero.
+    pub fn operations(&self) -> Result<Operations> {
+        let mut operations = vec![];
+        // The width is placed before the operands of the first operator.
+        let mut capacity = STACK_LIMIT / 2 - self.width.is_some() as usize;
+        for (operator, stems) in [
+            (Operator::HStem, &self.horizontal_stems),
+            (Operator::VStem, &self.vertical_stems),
+        ] {
+            let mut stems = stems.clone();
+            stems.sort_by(|one, other| one.0.total_cmp(&other.0).then(one.1.total_cmp(&other.1)));
+            let mut rest = &stems[..];
+            while !rest.is_empty() {
+                let (chunk, other) = rest.split_at(capacity.min(rest.len()));
+                rest = other;
+                capacity = STACK_LIMIT / 2;
+                let mut last = 0.0;
+                let mut operands = Vec::with_capacity(2 * chunk.len());
+                for &(position, size) in chunk {
+                    operands.push(position - last);
+                    operands.push(size);
+                    last = position + size;
+                }
+                operations.push((operator, operands));
+            }
+        }
+        operations.extend(crate::type2::specialize(&self.operations)?);
+        operations.push((Operator::EndChar, vec![]));
+        Ok(operations)
+    }
+
+    /// Encode the operations into a charstring.
+    #[inline]
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        crate::type2::encode(&self.operations()?, self.width)
+    }
+
+    fn advance(&mut self, x: Operand, y: Operand) -> (Operand, Operand) {
+        let delta = (x - self.position.0, y - self.position.1);
+        self.position = (x, y);
+        delta
+    }
+
+    fn open(&mut self) {
+        if self.start.is_none() {
+            let (x, y) = self.position;
+            self.move_to(x, y);
+        }
+    }
+}
@@ -0,0 +1,86 @@
+use crate::tape::Write;
+use crate::type2::{number, Operand, Operation, Operator, STACK_LIMIT};
+use crate::Result;
+
+/// Encode operations into a charstring.
+///
+/// The width, if given, is placed before the operands of the first
+/// stack-clearing operator. Vertical stems directly followed by a hint-mask or
+/// counter-mask operation are encoded implicitly, and the operands of the
+/// latter are written as the bytes of the corresponding mask, matching the
+/// output of programs with exposed masks.
+pub fn encode(operations: &[Operation], width: Option<Operand>) -> Result<Vec<u8>> {
+    use crate::type2::Operator::*;
+
+    let mut tape = vec![];
+    let mut width = width;
+    let mut stems = 0;
+    for (i, (operator, operands)) in operations.iter().enumerate() {
+        let operator = *operator;
+        let implicit = match operator {
+            HStem | VStem | HStemHM | VStemHM => {
+                if operands.is_empty() || operands.len() % 2 != 0 {
+                    raise!("found malformed operands ({operator:?})");
+                }
+                stems += operands.len() >> 1;
+                operator == VStemHM
+                    && matches!(operations.get(i + 1), Some((HintMask | CntrMask, _)))
+            }
+            _ => false,
+        };
+        let width = if clears(operator) { width.take() } else { None };
+        if operands.len() + width.is_some() as usize > STACK_LIMIT {
+            raise!("found too many operands ({operator:?})");
+        }
+        if let Some(width) = width {
+            number::write(&mut tape, width)?;
+        }
+        match operator {
+            HintMask | CntrMask => {
+                if operands.len() != (stems + 7) >> 3 {
+                    raise!("found a malformed mask ({operator:?})");
+                }
+                tape.give(&operator)?;
+                for &operand in operands {
+                    match operand {
+                        operand if operand.fract() == 0.0 && (0.0..256.0).contains(&operand) => {
+                            tape.give(&(operand as u8))?
+                        }
+                        _ => raise!("found a malformed mask ({operator:?})"),
+                    }
+                }
+            }
+            _ => {
+                for &operand in operands {
+                    number::write(&mut tape, operand)?;
+                }
+                if !implicit {
+                    tape.give(&operator)?;
+                }
+            }
+        }
+    }
+    if width.is_some() {
+        raise!("found no stack-clearing operator for the width");
+    }
+    Ok(tape)
+}
+
+#[inline]
+fn clears(operator: Operator) -> bool {
+    use crate::type2::Operator::*;
+
+    matches!(
+        operator,
+        HStem
+            | VStem
+            | HStemHM
+            | VStemHM
+            | HintMask
+            | CntrMask
+            | RMoveTo
+            | HMoveTo
+            | VMoveTo
+            | EndChar
+    )
+}
@@ -0,0 +1,68 @@
+//! The errors.
+
+use crate::Error;
+
+/// An error located in a charstring or a subroutine.
+#[derive(Debug)]
+pub struct ErrorWithLocation {
+    /// The location.
+    pub location: Location,
+    /// The position in bytes within the charstring or subroutine at which the
+    /// failure occurred.
+    pub offset: u64,
+    /// The error.
+    pub source: Error,
+}
+
+/// A location in a charstring.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub enum Location {
+    /// The charstring itself.
+    CharacterString,
+    /// A global subroutine given by its index.
+    GlobalSubroutine(usize),
+    /// A local subroutine given by its index.
+    LocalSubroutine(usize),
+}
+
+impl ErrorWithLocation {
+    /// Find the location of an error, looking through the errors it is caused
+    /// by.
+    pub fn find(error: &Error) -> Option<&Self> {
+        let mut error = error;
+        loop {
+            let inner = error.get_ref()?;
+            if let Some(value) = inner.downcast_ref::<Self>() {
+                return Some(value);
+            }
+            error = &inner.downcast_ref::<typeface::ErrorWithSource>()?.source;
+        }
+    }
+}
+
+impl std::fmt::Display for ErrorWithLocation {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            formatter,
+            "{} in {} at offset {}",
+            self.source, self.location, self.offset,
+        )
+    }
+}
+
+impl std::error::Error for ErrorWithLocation {
+    #[inline]
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+impl std::fmt::Display for Location {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Location::CharacterString => write!(formatter, "the charstring"),
+            Location::GlobalSubroutine(i) => write!(formatter, "global subroutine {i}"),
+            Location::LocalSubroutine(i) => write!(formatter, "local subroutine {i}"),
+        }
+    }
+}
@@ -2,9 +2,40 @@
 //!
 //! [1]: https://adobe-type-tools.github.io/font-tech-notes/pdfs/5177.Type2.pdf
 
+mod assembly;
+mod builder;
+mod encoder;
+mod error;
 mod number;
 mod operation;
 mod program;
+mod rewriter;
+mod subroutinizer;
+mod transform;
 
+pub use assembly::{
+    assemble, desubroutinize, disassemble, tokenize, tokenize_subroutine, trace, Call, Token,
+};
+pub use builder::Builder;
+pub use encoder::encode;
+pub use error::{ErrorWithLocation, Location};
 pub use operation::{Operand, Operation, Operations, Operator};
-pub use program::Program;
+pub use program::{Diagnostic, Profile, Program};
+pub use transform::{embolden, generalize, slant, specialize};
+
+pub(crate) use assembly::renumber;
+pub(crate) use rewriter::{rewrite, Edit, Rewritten};
+pub(crate) use subroutinizer::{subroutinize, Subroutinized};
+
+const STACK_LIMIT: usize = 48;
+
+#[inline]
+pub(crate) fn bias(count: usize) -> i32 {
+    if count < 1240 {
+        107
+    } else if count < 33900 {
+        1131
+    } else {
+        32768
+    }
+}
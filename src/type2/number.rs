@@ -14,6 +14,32 @@ pub fn read<T: crate::tape::Read>(tape: &mut T) -> Result<f32> {
     })
 }
 
+pub fn write<T: crate::tape::Write>(tape: &mut T, value: f32) -> Result<()> {
+    if value.fract() == 0.0 && (-32768.0..32768.0).contains(&value) {
+        let value = value as i32;
+        match value {
+            -107..=107 => tape.give(&((value + 139) as u8)),
+            108..=1131 => {
+                let value = value - 108;
+                tape.give_bytes(&[(value >> 8) as u8 + 247, value as u8])
+            }
+            -1131..=-108 => {
+                let value = -value - 108;
+                tape.give_bytes(&[(value >> 8) as u8 + 251, value as u8])
+            }
+            _ => {
+                tape.give(&0x1cu8)?;
+                tape.give(&(value as i16))
+            }
+        }
+    } else if (-32768.0..32768.0).contains(&value) {
+        tape.give(&0xffu8)?;
+        tape.give(&q32((value * 65536.0).round() as i32))
+    } else {
+        raise!("found a number out of range ({value})")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
@@ -23,4 +49,26 @@ mod tests {
         let mut tape = Cursor::new(vec![0xff, 0x00, 0x01, 0x04, 0x5a]);
         assert!(format!("{:.3}", super::read(&mut tape).unwrap()) == "1.017");
     }
+
+    #[test]
+    fn write() {
+        let cases: Vec<(f32, Vec<u8>)> = vec![
+            (0.0, vec![0x8b]),
+            (100.0, vec![0xef]),
+            (-100.0, vec![0x27]),
+            (1000.0, vec![0xfa, 0x7c]),
+            (-1000.0, vec![0xfe, 0x7c]),
+            (10000.0, vec![0x1c, 0x27, 0x10]),
+            (-10000.0, vec![0x1c, 0xd8, 0xf0]),
+            (-2.25, vec![0xff, 0xff, 0xfd, 0xc0, 0x00]),
+        ];
+        for (value, expected) in cases {
+            let mut tape = vec![];
+            super::write(&mut tape, value).unwrap();
+            assert_eq!(tape, expected);
+            let mut tape = Cursor::new(tape);
+            assert_eq!(super::read(&mut tape).unwrap(), value);
+        }
+        assert!(super::write(&mut vec![], 100000.0).is_err());
+    }
 }
@@ -12,16 +12,16 @@ pub type Operation = (Operator, Vec<Operand>);
 pub type Operations = Vec<Operation>;
 
 macro_rules! operator {
-    (pub $name:ident { $($code:pat => $variant:ident,)+ }) => (
+    (pub $name:ident { $($code:literal => $variant:ident,)+ }) => (
         operator! { @define pub $name { $($variant,)+ } }
         operator! { @implement pub $name { $($code => $variant,)+ } }
     );
     (@define pub $name:ident { $($variant:ident,)* }) => (
         /// An operator.
-        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
         pub enum $name { $($variant,)* }
     );
-    (@implement pub $name:ident { $($code:pat => $variant:ident,)* }) => (impl $name {
+    (@implement pub $name:ident { $($code:literal => $variant:ident,)* }) => (impl $name {
         #[doc(hidden)]
         pub fn from(code: u16) -> Result<Self> {
             use self::$name::*;
@@ -30,9 +30,26 @@ macro_rules! operator {
                 code => raise!("found an unknown operator ({code})"),
             })
         }
+
+        /// Return the code.
+        pub fn code(&self) -> u16 {
+            use self::$name::*;
+            match *self {
+                $($variant => $code,)+
+            }
+        }
     });
 }
 
+impl crate::value::Write for Operator {
+    fn write<T: crate::tape::Write>(&self, tape: &mut T) -> Result<()> {
+        match self.code() {
+            code if code > 0xff => tape.give(&code),
+            code => tape.give(&(code as u8)),
+        }
+    }
+}
+
 operator! {
     pub Operator {
         // 0x00 => Reserved,
@@ -70,7 +87,7 @@ operator! {
         // 0x20..=0xf6 => <numbers>,
         // 0xf7..=0xfe => <numbers>,
         // 0xff => <number>,
-        // 0x0c00 => Reserved,
+        0x0c00 => DotSection,
         // 0x0c01 => Reserved,
         // 0x0c02 => Reserved,
         0x0c03 => And,
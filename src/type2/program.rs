@@ -1,8 +1,11 @@
 use std::io::Cursor;
 
 use crate::tape::Read;
-use crate::type2::{number, Operand, Operation, Operator};
-use crate::Result;
+use crate::type2::{bias, number, ErrorWithLocation, Location, Operand, Operation, Operator};
+use crate::{Error, Result};
+
+/// The maximum number of nested subroutine calls.
+const NESTING_LIMIT: usize = 10;
 
 /// A program.
 pub struct Program<'l> {
@@ -12,34 +15,120 @@ pub struct Program<'l> {
     stack: Vec<Operand>,
     stems: usize,
     width: Option<Operand>,
+    pending: Option<Operation>,
+    cleared: bool,
+    masks: bool,
+    profile: Profile,
+    diagnostics: Vec<Diagnostic>,
+}
+
+/// A profile of interpretation.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Profile {
+    /// Follow the specification and reject any deviation.
+    Strict,
+    /// Tolerate the deviations that FreeType tolerates.
+    #[default]
+    Compatible,
+}
+
+/// A deviation from the specification encountered during interpretation.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Diagnostic {
+    /// A width given to an operator other than the first stack-clearing one.
+    MisplacedWidth(Operator),
+    /// Operands in excess of what an operator accepts, which were discarded.
+    ExcessOperands(Operator, usize),
+    /// A deprecated operator, which was ignored.
+    DeprecatedOperator(Operator),
+    /// A division by zero, whose result was replaced with zero.
+    DivisionByZero,
+    /// A result that is not a finite number, which was replaced with zero.
+    NonFiniteResult(Operator),
 }
 
 struct Routine<'l> {
     tape: Cursor<&'l [u8]>,
     size: usize,
+    location: Location,
+    depth: usize,
     caller: Option<Box<Routine<'l>>>,
 }
 
 impl<'l> Program<'l> {
-    /// Create a program.
+    /// Create a program with the compatible profile of interpretation.
     #[inline]
     pub fn new(code: &'l [u8], global: &'l [Vec<u8>], local: &'l [Vec<u8>]) -> Self {
+        Self::with_profile(code, global, local, Profile::default())
+    }
+
+    /// Create a program with a specific profile of interpretation.
+    pub fn with_profile(
+        code: &'l [u8],
+        global: &'l [Vec<u8>],
+        local: &'l [Vec<u8>],
+        profile: Profile,
+    ) -> Self {
         Program {
-            routine: Routine::new(code),
+            routine: Routine::new(code, Location::CharacterString),
             global,
             local,
             stack: vec![],
             stems: 0,
             width: None,
+            pending: None,
+            cleared: false,
+            masks: false,
+            profile,
+            diagnostics: vec![],
         }
     }
 
+    /// Expose the masks of hint-mask and counter-mask operations.
+    ///
+    /// The operands of such operations become the bytes of the corresponding
+    /// masks, and implicit vertical stems preceding them are returned as
+    /// separate operations.
+    #[inline]
+    pub fn with_masks(mut self) -> Self {
+        self.masks = true;
+        self
+    }
+
     /// Return the next operation.
+    ///
+    /// Errors are wrapped in `ErrorWithLocation`, indicating the charstring or
+    /// subroutine where the failure occurred.
     #[allow(clippy::should_implement_trait)]
-    #[allow(unused_comparisons, unused_must_use)]
     pub fn next(&mut self) -> Result<Option<Operation>> {
+        self.step().map_err(|error| {
+            Error::other(ErrorWithLocation {
+                location: self.routine.location,
+                offset: self.routine.tape.position(),
+                source: error,
+            })
+        })
+    }
+
+    /// Return the width difference with respect to the nominal width.
+    #[inline]
+    pub fn width(&self) -> Option<Operand> {
+        self.width
+    }
+
+    /// Return the diagnostics reported so far.
+    #[inline]
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    #[allow(unused_comparisons, unused_must_use)]
+    fn step(&mut self) -> Result<Option<Operation>> {
         use crate::type2::Operator::*;
 
+        if let Some(operation) = self.pending.take() {
+            return Ok(Some(operation));
+        }
         if self.routine.done()? {
             return Ok(None);
         }
@@ -120,7 +209,7 @@ impl<'l> Program<'l> {
                     clear!(@reduce [$min, left] [$($tail)*]);
                 }
             });
-            ($([$($predicate:ident($count:expr)),*]),+) => ({
+            (@operands $([$($predicate:ident($count:expr)),*]),+) => ({
                 let length = self.stack.len();
                 let mut min = !0;
                 $(clear!(@reduce [min, length] [$($predicate($count),)*]);)+
@@ -128,13 +217,24 @@ impl<'l> Program<'l> {
                     raise!("found malformed operands");
                 }
                 let mut stack = std::mem::take(&mut self.stack);
-                let operands = stack.drain(min..).collect();
-                if min > 0 && self.width.is_none() {
-                    self.width = Some(stack[min - 1]);
-                }
+                let operands: Vec<_> = stack.drain(min..).collect();
+                self.settle(operator, &stack)?;
+                operands
+            });
+            ($([$($predicate:ident($count:expr)),*]),+) => ({
+                let operands = clear!(@operands $([$($predicate($count)),*]),+);
                 return Ok(Some((operator, operands)));
             });
         );
+        macro_rules! finite(($value:expr) => ({
+            let value = $value;
+            if value.is_finite() {
+                value
+            } else {
+                self.report(Diagnostic::NonFiniteResult(operator))?;
+                0.0
+            }
+        }));
 
         match operator {
             // Path-construction operators
@@ -157,16 +257,13 @@ impl<'l> Program<'l> {
 
             // Terminal operator
             EndChar => {
+                let _ = clear!(@operands [maybe_equal(4)]);
                 while let Some(caller) = self.routine.caller.take() {
                     if !self.routine.done()? {
                         raise!("found trailing data after the end operator");
                     }
                     std::mem::replace(&mut self.routine, *caller);
                 }
-                let length = self.stack.len();
-                if length > 0 && self.width.is_none() {
-                    self.width = Some(self.stack[length - 1]);
-                }
                 return Ok(None);
             }
 
@@ -176,26 +273,44 @@ impl<'l> Program<'l> {
                 clear!([equal(2), maybe_modulo(2)]);
             }
             HintMask | CntrMask => {
-                self.stems += self.stack.len() >> 1;
-                let _ = self.routine.take_given::<Vec<u8>>((self.stems + 7) >> 3)?;
-                clear!([equal(0)]);
+                let operands = clear!(@operands [maybe_modulo(2)]);
+                self.stems += operands.len() >> 1;
+                let mask = self.routine.take_given::<Vec<u8>>((self.stems + 7) >> 3)?;
+                if !self.masks {
+                    return Ok(Some((operator, vec![])));
+                }
+                let operation = (operator, mask.into_iter().map(Operand::from).collect());
+                if operands.is_empty() {
+                    return Ok(Some(operation));
+                }
+                self.pending = Some(operation);
+                return Ok(Some((VStemHM, operands)));
+            }
+            DotSection => {
+                self.report(Diagnostic::DeprecatedOperator(operator))?;
+                self.stack.clear();
             }
 
             // Arithmetic operators
             Abs => push!(pop!().abs()),
-            Add => push!(pop!() + pop!()),
+            Add => push!(finite!(pop!() + pop!())),
             Sub => {
                 let (right, left) = (pop!(), pop!());
-                push!(left - right);
+                push!(finite!(left - right));
             }
             Div => {
                 let (right, left) = (pop!(), pop!());
-                push!(left / right);
+                if right == 0.0 {
+                    self.report(Diagnostic::DivisionByZero)?;
+                    push!(0.0);
+                } else {
+                    push!(finite!(left / right));
+                }
             }
             Neg => push!(-pop!()),
             // Random =>
-            Mul => push!(pop!() * pop!()),
-            Sqrt => push!(pop!().sqrt()),
+            Mul => push!(finite!(pop!() * pop!())),
+            Sqrt => push!(finite!(pop!().sqrt())),
             #[allow(dropping_copy_types)]
             Drop => std::mem::drop(pop!()),
             Exch => {
@@ -260,6 +375,9 @@ impl<'l> Program<'l> {
             // Subroutine operators
             CallSubr | CallGSubr => {
                 let address = pop!(i32);
+                if self.routine.depth >= NESTING_LIMIT {
+                    raise!("found too many nested subroutine calls");
+                }
                 let mut routine = {
                     let subroutines = if operator == CallSubr {
                         &self.local
@@ -271,8 +389,14 @@ impl<'l> Program<'l> {
                     if i < 0 || i as usize >= count {
                         raise!("found no subroutine");
                     }
-                    Routine::new(&subroutines[i as usize])
+                    let location = if operator == CallSubr {
+                        Location::LocalSubroutine(i as usize)
+                    } else {
+                        Location::GlobalSubroutine(i as usize)
+                    };
+                    Routine::new(&subroutines[i as usize], location)
                 };
+                routine.depth = self.routine.depth + 1;
                 std::mem::swap(&mut self.routine, &mut routine);
                 self.routine.caller = Some(Box::new(routine));
             }
@@ -286,22 +410,62 @@ impl<'l> Program<'l> {
 
             operator => raise!("found an unknown operator ({operator:?})"),
         };
-        self.next()
+        self.step()
     }
 
-    /// Return the width difference with respect to the nominal width.
-    #[inline]
-    pub fn width(&self) -> Option<Operand> {
-        self.width
+    fn settle(&mut self, operator: Operator, leftovers: &[Operand]) -> Result<()> {
+        let first = !std::mem::replace(&mut self.cleared, true);
+        let excess = match leftovers.split_first() {
+            Some((&width, rest)) if first => {
+                self.width = Some(width);
+                rest.len()
+            }
+            _ => leftovers.len(),
+        };
+        match excess {
+            0 => Ok(()),
+            1 if !first => self.report(Diagnostic::MisplacedWidth(operator)),
+            _ => self.report(Diagnostic::ExcessOperands(operator, excess)),
+        }
+    }
+
+    fn report(&mut self, diagnostic: Diagnostic) -> Result<()> {
+        if self.profile == Profile::Strict {
+            raise!("{diagnostic}");
+        }
+        self.diagnostics.push(diagnostic);
+        Ok(())
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, formatter: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Diagnostic::MisplacedWidth(operator) => {
+                write!(formatter, "found a misplaced width ({operator:?})")
+            }
+            Diagnostic::ExcessOperands(operator, count) => {
+                write!(formatter, "found excess operands ({operator:?}, {count})")
+            }
+            Diagnostic::DeprecatedOperator(operator) => {
+                write!(formatter, "found a deprecated operator ({operator:?})")
+            }
+            Diagnostic::DivisionByZero => write!(formatter, "found a division by zero"),
+            Diagnostic::NonFiniteResult(operator) => {
+                write!(formatter, "found a non-finite result ({operator:?})")
+            }
+        }
     }
 }
 
 impl<'l> Routine<'l> {
     #[inline]
-    fn new(code: &'l [u8]) -> Routine<'l> {
+    fn new(code: &'l [u8], location: Location) -> Routine<'l> {
         Routine {
             tape: Cursor::new(code),
             size: code.len(),
+            location,
+            depth: 0,
             caller: None,
         }
     }
@@ -318,14 +482,3 @@ impl<'l> Routine<'l> {
 }
 
 dereference! { Routine<'l>::tape => Cursor<&'l [u8]> }
-
-#[inline]
-fn bias(count: usize) -> i32 {
-    if count < 1240 {
-        107
-    } else if count < 33900 {
-        1131
-    } else {
-        32768
-    }
-}
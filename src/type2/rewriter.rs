@@ -0,0 +1,284 @@
+use std::collections::{BTreeMap, HashMap};
+use std::io::Cursor;
+
+use crate::tape::Read;
+use crate::type2::{bias, number, Operand, Operator};
+use crate::Result;
+
+const NESTING_LIMIT: usize = 10;
+
+/// Rewritten charstrings and subroutines.
+pub(crate) struct Rewritten {
+    /// The charstrings.
+    pub character_strings: Vec<Vec<u8>>,
+    /// The global subroutines.
+    pub global: Vec<Vec<u8>>,
+    /// The local subroutines of each group.
+    pub locals: Vec<Vec<Vec<u8>>>,
+}
+
+/// An edit of an operand or an operator.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub(crate) enum Edit {
+    /// Keep as is.
+    Keep,
+    /// Delete.
+    Delete,
+    /// Replace an operand with a value.
+    Replace(Operand),
+    /// Insert a value before.
+    Insert(Operand),
+}
+
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+enum Routine {
+    Glyph(usize),
+    Global(usize),
+    Local(usize, usize),
+}
+
+struct Item {
+    value: Operand,
+    source: Option<(Routine, usize, usize)>,
+}
+
+struct State {
+    group: usize,
+    stack: Vec<Item>,
+    stems: usize,
+    cleared: bool,
+}
+
+struct Rewriter<'l, F> {
+    global: &'l [Vec<u8>],
+    locals: &'l [&'l [Vec<u8>]],
+    edit: F,
+    edits: HashMap<Routine, BTreeMap<usize, (usize, Edit)>>,
+}
+
+/// Edit the operands and operators of charstrings and the subroutines they
+/// call.
+///
+/// Each charstring is given along with the index of its group of local
+/// subroutines. The charstrings are executed without evaluating arithmetic,
+/// and each stack-clearing operator is given to the editor along with the
+/// operands it consumes and whether the stack has been cleared before, which
+/// indicates that the operands do not include the width. The editor returns
+/// the edits of the operands, wherever they are given, and of the operator.
+/// Operands of subroutines edited differently in different contexts and
+/// computed operands edited in any way are not supported. Subroutines that
+/// are not called are left intact.
+pub(crate) fn rewrite<F>(
+    character_strings: &[(&[u8], usize)],
+    global: &[Vec<u8>],
+    locals: &[&[Vec<u8>]],
+    edit: F,
+) -> Result<Rewritten>
+where
+    F: FnMut(Operator, &[Operand], bool) -> Result<(Vec<Edit>, Edit)>,
+{
+    let mut rewriter = Rewriter {
+        global,
+        locals,
+        edit,
+        edits: HashMap::new(),
+    };
+    for (glyph_id, &(code, group)) in character_strings.iter().enumerate() {
+        if group >= locals.len() {
+            raise!("found no local subroutines for glyph {glyph_id}");
+        }
+        let mut state = State {
+            group,
+            stack: vec![],
+            stems: 0,
+            cleared: false,
+        };
+        rewriter.run(Routine::Glyph(glyph_id), code, &mut state, 0)?;
+    }
+    Ok(Rewritten {
+        character_strings: character_strings
+            .iter()
+            .enumerate()
+            .map(|(glyph_id, &(code, _))| rewriter.apply(Routine::Glyph(glyph_id), code))
+            .collect::<Result<_>>()?,
+        global: global
+            .iter()
+            .enumerate()
+            .map(|(i, code)| rewriter.apply(Routine::Global(i), code))
+            .collect::<Result<_>>()?,
+        locals: locals
+            .iter()
+            .enumerate()
+            .map(|(group, local)| {
+                local
+                    .iter()
+                    .enumerate()
+                    .map(|(i, code)| rewriter.apply(Routine::Local(group, i), code))
+                    .collect::<Result<_>>()
+            })
+            .collect::<Result<_>>()?,
+    })
+}
+
+impl<F> Rewriter<'_, F>
+where
+    F: FnMut(Operator, &[Operand], bool) -> Result<(Vec<Edit>, Edit)>,
+{
+    fn run(
+        &mut self,
+        routine: Routine,
+        code: &[u8],
+        state: &mut State,
+        nesting: usize,
+    ) -> Result<bool> {
+        use crate::type2::Operator::*;
+
+        if nesting > NESTING_LIMIT {
+            raise!("found too deeply nested subroutines");
+        }
+        macro_rules! pop(() => ({
+            match state.stack.pop() {
+                Some(item) => {
+                    if let Some((routine, start, end)) = item.source {
+                        self.insert(routine, start, end, Edit::Keep)?;
+                    }
+                    item
+                }
+                _ => raise!("expected an operand"),
+            }
+        }));
+        let size = code.len() as u64;
+        let mut tape = Cursor::new(code);
+        while tape.position() < size {
+            let start = tape.position() as usize;
+            let operator = match tape.peek::<u8>()? {
+                0x1c | 0x20..=0xff => {
+                    let value = number::read(&mut tape)?;
+                    let source = Some((routine, start, tape.position() as usize));
+                    state.stack.push(Item { value, source });
+                    continue;
+                }
+                0x0c => Operator::from(tape.take::<u16>()?)?,
+                _ => Operator::from(tape.take::<u8>()? as u16)?,
+            };
+            match operator {
+                CallSubr | CallGSubr => {
+                    let item = pop!();
+                    if item.source.is_none() {
+                        raise!("found a call to a computed subroutine ({operator:?})");
+                    }
+                    let subroutines = if operator == CallSubr {
+                        self.locals[state.group]
+                    } else {
+                        self.global
+                    };
+                    let i = item.value as i32 + bias(subroutines.len());
+                    if i < 0 || i as usize >= subroutines.len() {
+                        raise!("found a call to a nonexistent subroutine ({operator:?})");
+                    }
+                    let routine = if operator == CallSubr {
+                        Routine::Local(state.group, i as usize)
+                    } else {
+                        Routine::Global(i as usize)
+                    };
+                    if self.run(routine, &subroutines[i as usize], state, nesting + 1)? {
+                        return Ok(true);
+                    }
+                }
+                Return => return Ok(false),
+                Abs | Neg | Not | Sqrt | Index | Get | And | Or | Eq | Add | Sub | Div | Mul
+                | Drop | Put | Roll | IfElse | Random | Dup | Exch => {
+                    let (pops, pushes) = match operator {
+                        And | Or | Eq | Add | Sub | Div | Mul => (2, 1),
+                        Drop => (1, 0),
+                        Put | Roll => (2, 0),
+                        IfElse => (4, 1),
+                        Random => (0, 1),
+                        Dup => (1, 2),
+                        Exch => (2, 2),
+                        _ => (1, 1),
+                    };
+                    for _ in 0..pops {
+                        pop!();
+                    }
+                    for _ in 0..pushes {
+                        state.stack.push(Item {
+                            value: 0.0,
+                            source: None,
+                        });
+                    }
+                }
+                _ => {
+                    let stack = std::mem::take(&mut state.stack);
+                    let values = stack.iter().map(|item| item.value).collect::<Vec<_>>();
+                    let (edits, edit) = (self.edit)(operator, &values, state.cleared)?;
+                    if edits.len() != stack.len() {
+                        raise!("found a malformed edit ({operator:?})");
+                    }
+                    for (item, edit) in stack.iter().zip(edits) {
+                        match item.source {
+                            Some((routine, start, end)) => {
+                                self.insert(routine, start, end, edit)?
+                            }
+                            _ if edit != Edit::Keep => {
+                                raise!("found computed operands ({operator:?})")
+                            }
+                            _ => {}
+                        }
+                    }
+                    if let HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask = operator {
+                        let width = !state.cleared && stack.len() % 2 == 1;
+                        state.stems += (stack.len() - width as usize) >> 1;
+                    }
+                    if let HintMask | CntrMask = operator {
+                        tape.take_given::<Vec<u8>>((state.stems + 7) >> 3)?;
+                    }
+                    self.insert(routine, start, tape.position() as usize, edit)?;
+                    if operator == EndChar {
+                        return Ok(true);
+                    }
+                    if operator != DotSection {
+                        state.cleared = true;
+                    }
+                }
+            }
+        }
+        Ok(false)
+    }
+
+    fn insert(&mut self, routine: Routine, start: usize, end: usize, edit: Edit) -> Result<()> {
+        let edits = self.edits.entry(routine).or_default();
+        match edits.insert(start, (end, edit)) {
+            Some((_, other)) if other != edit => {
+                raise!("found operands edited differently in different contexts")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    fn apply(&self, routine: Routine, code: &[u8]) -> Result<Vec<u8>> {
+        let edits = match self.edits.get(&routine) {
+            Some(edits) => edits,
+            _ => return Ok(code.to_vec()),
+        };
+        let mut output = Vec::with_capacity(code.len());
+        let mut position = 0;
+        for (&start, &(end, edit)) in edits.iter() {
+            if edit == Edit::Keep {
+                continue;
+            }
+            output.extend_from_slice(&code[position..start]);
+            position = end;
+            match edit {
+                Edit::Replace(value) => number::write(&mut output, value)?,
+                Edit::Insert(value) => {
+                    number::write(&mut output, value)?;
+                    position = start;
+                }
+                _ => {}
+            }
+        }
+        output.extend_from_slice(&code[position..]);
+        Ok(output)
+    }
+}
@@ -0,0 +1,225 @@
+use std::collections::HashMap;
+
+use crate::type2::{assembly, bias, number, Operator};
+use crate::Result;
+
+const SEQUENCE_LIMIT: usize = 16;
+const SUBROUTINE_LIMIT: usize = 65535;
+const CALL_COST: usize = 3;
+const SUBROUTINE_COST: usize = 3;
+
+/// Charstrings with repeated operations moved into subroutines.
+pub(crate) struct Subroutinized {
+    /// The charstrings of each group.
+    pub character_strings: Vec<Vec<Vec<u8>>>,
+    /// The global subroutines.
+    pub global: Vec<Vec<u8>>,
+    /// The local subroutines of each group.
+    pub locals: Vec<Vec<Vec<u8>>>,
+}
+
+enum Piece {
+    Unit(usize),
+    Call(usize),
+}
+
+/// Move repeated operations of charstrings without subroutine calls into
+/// subroutines.
+///
+/// The charstrings are given in groups sharing local subroutines. Sequences
+/// used by more than one group become global subroutines. Subroutines are not
+/// nested, and the more frequently a subroutine is called, the shorter the
+/// encoding of its index.
+pub(crate) fn subroutinize(groups: &[Vec<&[u8]>]) -> Result<Subroutinized> {
+    let mut units: Vec<&[u8]> = vec![];
+    let mut excluded = vec![];
+    let mut identifiers = HashMap::new();
+    let mut glyphs = vec![];
+    for (group, codes) in groups.iter().enumerate() {
+        for code in codes.iter() {
+            let mut sequence = vec![];
+            for (operator, chunk) in assembly::segment(code)? {
+                let identifier = *identifiers.entry(chunk).or_insert_with(|| {
+                    units.push(chunk);
+                    excluded.push(!matches!(
+                        operator,
+                        Some(operator) if operator != Operator::EndChar && operator != Operator::Return,
+                    ));
+                    units.len() - 1
+                });
+                sequence.push(identifier);
+            }
+            glyphs.push((group, sequence));
+        }
+    }
+    let size = |sequence: &[usize]| -> usize { sequence.iter().map(|&i| units[i].len()).sum() };
+
+    let mut counts = HashMap::<&[usize], usize>::new();
+    for (_, sequence) in glyphs.iter() {
+        for start in 0..sequence.len() {
+            for end in (start + 1)..=(start + SEQUENCE_LIMIT).min(sequence.len()) {
+                if excluded[sequence[end - 1]] {
+                    break;
+                }
+                *counts.entry(&sequence[start..end]).or_default() += 1;
+            }
+        }
+    }
+    let mut candidates = counts
+        .into_iter()
+        .filter_map(|(sequence, count)| {
+            let savings = saving(size(sequence), count);
+            if savings > 0 {
+                Some((sequence, savings))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    candidates.sort_by(|one, other| other.1.cmp(&one.1).then_with(|| one.0.cmp(other.0)));
+
+    let mut occurrences = HashMap::<&[usize], Vec<(usize, usize)>>::new();
+    for (sequence, _) in candidates.iter() {
+        occurrences.insert(sequence, vec![]);
+    }
+    for (glyph, (_, sequence)) in glyphs.iter().enumerate() {
+        for start in 0..sequence.len() {
+            for end in (start + 1)..=(start + SEQUENCE_LIMIT).min(sequence.len()) {
+                if let Some(positions) = occurrences.get_mut(&sequence[start..end]) {
+                    positions.push((glyph, start));
+                }
+            }
+        }
+    }
+    let mut consumed = glyphs
+        .iter()
+        .map(|(_, sequence)| vec![false; sequence.len()])
+        .collect::<Vec<_>>();
+    let mut placements = glyphs.iter().map(|_| HashMap::new()).collect::<Vec<_>>();
+    let mut subroutines = vec![];
+    for (sequence, _) in candidates {
+        if subroutines.len() == SUBROUTINE_LIMIT {
+            break;
+        }
+        let mut uses = vec![];
+        for &(glyph, start) in occurrences[sequence].iter() {
+            let range = start..(start + sequence.len());
+            if consumed[glyph][range.clone()]
+                .iter()
+                .all(|&consumed| !consumed)
+            {
+                consumed[glyph][range].fill(true);
+                uses.push((glyph, start));
+            }
+        }
+        if uses.len() > 1 && saving(size(sequence), uses.len()) > 0 {
+            for &(glyph, start) in uses.iter() {
+                placements[glyph].insert(start, subroutines.len());
+            }
+            subroutines.push((sequence, uses));
+        } else {
+            for (glyph, start) in uses {
+                consumed[glyph][start..(start + sequence.len())].fill(false);
+            }
+        }
+    }
+
+    let mut scopes = vec![None; subroutines.len()];
+    for (i, (_, uses)) in subroutines.iter().enumerate() {
+        let group = glyphs[uses[0].0].0;
+        scopes[i] = if uses.iter().all(|&(glyph, _)| glyphs[glyph].0 == group) {
+            Some(group)
+        } else {
+            None
+        };
+    }
+    let mut members = vec![vec![]; groups.len()];
+    let mut global_members = vec![];
+    for (i, scope) in scopes.iter().enumerate() {
+        match scope {
+            Some(group) => members[*group].push(i),
+            _ => global_members.push(i),
+        }
+    }
+    let mut indices = vec![0; subroutines.len()];
+    for members in members.iter_mut().chain(Some(&mut global_members)) {
+        members.sort_by(|&one, &other| subroutines[other].1.len().cmp(&subroutines[one].1.len()));
+        for (&i, index) in members.iter().zip(slots(members.len())) {
+            indices[i] = index;
+        }
+    }
+
+    let assemble = |pieces: &mut dyn Iterator<Item = Piece>| -> Result<Vec<u8>> {
+        let mut code = vec![];
+        for piece in pieces {
+            match piece {
+                Piece::Unit(i) => code.extend_from_slice(units[i]),
+                Piece::Call(i) => {
+                    let (count, operator) = match scopes[i] {
+                        Some(group) => (members[group].len(), Operator::CallSubr),
+                        _ => (global_members.len(), Operator::CallGSubr),
+                    };
+                    number::write(&mut code, (indices[i] as i32 - bias(count)) as f32)?;
+                    code.push(operator.code() as u8);
+                }
+            }
+        }
+        Ok(code)
+    };
+    let mut character_strings = groups
+        .iter()
+        .map(|codes| Vec::with_capacity(codes.len()))
+        .collect::<Vec<_>>();
+    for (glyph, (group, sequence)) in glyphs.iter().enumerate() {
+        let mut pieces = vec![];
+        let mut start = 0;
+        while start < sequence.len() {
+            match placements[glyph].get(&start) {
+                Some(&i) => {
+                    pieces.push(Piece::Call(i));
+                    start += subroutines[i].0.len();
+                }
+                _ => {
+                    pieces.push(Piece::Unit(sequence[start]));
+                    start += 1;
+                }
+            }
+        }
+        character_strings[*group].push(assemble(&mut pieces.into_iter())?);
+    }
+    let build = |members: &[usize]| -> Result<Vec<Vec<u8>>> {
+        let mut codes = vec![vec![]; members.len()];
+        for &i in members {
+            let mut code = assemble(&mut subroutines[i].0.iter().map(|&i| Piece::Unit(i)))?;
+            code.push(Operator::Return.code() as u8);
+            codes[indices[i]] = code;
+        }
+        Ok(codes)
+    };
+    let global = build(&global_members)?;
+    let locals = members
+        .iter()
+        .map(|members| build(members))
+        .collect::<Result<Vec<_>>>()?;
+    Ok(Subroutinized {
+        character_strings,
+        global,
+        locals,
+    })
+}
+
+#[inline]
+fn saving(size: usize, count: usize) -> isize {
+    (count * size) as isize - (count * CALL_COST + size + SUBROUTINE_COST) as isize
+}
+
+fn slots(count: usize) -> Vec<usize> {
+    let bias = bias(count);
+    let mut slots = (0..count).collect::<Vec<_>>();
+    slots.sort_by_key(|&i| {
+        let mut code = vec![];
+        let _ = number::write(&mut code, (i as i32 - bias) as f32);
+        code.len()
+    });
+    slots
+}
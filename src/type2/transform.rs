@@ -0,0 +1,378 @@
+use crate::type2::{Operand, Operation, Operations, Operator, STACK_LIMIT};
+use crate::Result;
+
+/// Generalize operations.
+///
+/// Each path-construction operation is replaced with one or more operations
+/// with operators `RMoveTo`, `RLineTo`, and `RRCurveTo`, one per segment. The
+/// other operations are left intact.
+pub fn generalize(operations: &[Operation]) -> Result<Operations> {
+    use crate::type2::Operator::*;
+
+    let mut result = vec![];
+    macro_rules! line(($dx:expr, $dy:expr) => (result.push((RLineTo, vec![$dx, $dy]))));
+    macro_rules! curve(
+        ($dxa:expr, $dya:expr, $dxb:expr, $dyb:expr, $dxc:expr, $dyc:expr) => (
+            result.push((RRCurveTo, vec![$dxa, $dya, $dxb, $dyb, $dxc, $dyc]))
+        );
+    );
+    for (operator, operands) in operations {
+        let operator = *operator;
+        if !valid(operator, operands.len()) {
+            raise!("found malformed operands ({operator:?})");
+        }
+        match operator {
+            RMoveTo => result.push((RMoveTo, operands.clone())),
+            HMoveTo => result.push((RMoveTo, vec![operands[0], 0.0])),
+            VMoveTo => result.push((RMoveTo, vec![0.0, operands[0]])),
+            RLineTo => {
+                for chunk in operands.chunks_exact(2) {
+                    line!(chunk[0], chunk[1]);
+                }
+            }
+            HLineTo | VLineTo => {
+                for (i, &operand) in operands.iter().enumerate() {
+                    if (operator == HLineTo) == i.is_multiple_of(2) {
+                        line!(operand, 0.0);
+                    } else {
+                        line!(0.0, operand);
+                    }
+                }
+            }
+            RRCurveTo => {
+                for chunk in operands.chunks_exact(6) {
+                    result.push((RRCurveTo, chunk.to_vec()));
+                }
+            }
+            HHCurveTo => {
+                let (mut dy, operands) = split(operands, 4);
+                for chunk in operands.chunks_exact(4) {
+                    curve!(chunk[0], dy, chunk[1], chunk[2], chunk[3], 0.0);
+                    dy = 0.0;
+                }
+            }
+            VVCurveTo => {
+                let (mut dx, operands) = split(operands, 4);
+                for chunk in operands.chunks_exact(4) {
+                    curve!(dx, chunk[0], chunk[1], chunk[2], 0.0, chunk[3]);
+                    dx = 0.0;
+                }
+            }
+            HVCurveTo | VHCurveTo => {
+                let count = operands.len() / 4;
+                for (i, chunk) in operands.chunks_exact(4).enumerate() {
+                    let last = if i + 1 == count && operands.len() % 4 == 1 {
+                        operands[operands.len() - 1]
+                    } else {
+                        0.0
+                    };
+                    if (operator == HVCurveTo) == i.is_multiple_of(2) {
+                        curve!(chunk[0], 0.0, chunk[1], chunk[2], last, chunk[3]);
+                    } else {
+                        curve!(0.0, chunk[0], chunk[1], chunk[2], chunk[3], last);
+                    }
+                }
+            }
+            RCurveLine => {
+                let (curves, line) = operands.split_at(operands.len() - 2);
+                for chunk in curves.chunks_exact(6) {
+                    result.push((RRCurveTo, chunk.to_vec()));
+                }
+                line!(line[0], line[1]);
+            }
+            RLineCurve => {
+                let (lines, curve) = operands.split_at(operands.len() - 6);
+                for chunk in lines.chunks_exact(2) {
+                    line!(chunk[0], chunk[1]);
+                }
+                result.push((RRCurveTo, curve.to_vec()));
+            }
+            Flex => {
+                result.push((RRCurveTo, operands[0..6].to_vec()));
+                result.push((RRCurveTo, operands[6..12].to_vec()));
+            }
+            HFlex => {
+                let d = operands;
+                curve!(d[0], 0.0, d[1], d[2], d[3], 0.0);
+                curve!(d[4], 0.0, d[5], -d[2], d[6], 0.0);
+            }
+            HFlex1 => {
+                let d = operands;
+                curve!(d[0], d[1], d[2], d[3], d[4], 0.0);
+                curve!(d[5], 0.0, d[6], d[7], d[8], -(d[1] + d[3] + d[7]));
+            }
+            Flex1 => {
+                let d = operands;
+                let dx: Operand = d[0..10].iter().step_by(2).sum();
+                let dy: Operand = d[1..10].iter().step_by(2).sum();
+                result.push((RRCurveTo, d[0..6].to_vec()));
+                if dx.abs() > dy.abs() {
+                    curve!(d[6], d[7], d[8], d[9], d[10], -dy);
+                } else {
+                    curve!(d[6], d[7], d[8], d[9], -dx, d[10]);
+                }
+            }
+            _ => result.push((operator, operands.clone())),
+        }
+    }
+    Ok(result)
+}
+
+/// Embolden operations.
+///
+/// The operations are generalized first, and the contours are offset outward
+/// by half the amount and then shifted up and to the right by the same, so
+/// that the glyph grows by the amount in both directions while its lower-left
+/// extremes stay in place. Hint operations are removed.
+pub fn embolden(operations: &[Operation], amount: Operand) -> Result<Operations> {
+    use crate::type2::Operator::*;
+
+    let operations = generalize(operations)?;
+    let mut contours: Vec<Vec<(Operand, Operand)>> = vec![];
+    let mut position = (0.0, 0.0);
+    for (operator, operands) in operations.iter() {
+        match operator {
+            RMoveTo => contours.push(vec![]),
+            RLineTo | RRCurveTo if !contours.is_empty() => {}
+            RLineTo | RRCurveTo => raise!("found a contour without a starting point"),
+            _ => continue,
+        }
+        let contour = contours.last_mut().unwrap();
+        for chunk in operands.chunks_exact(2) {
+            position = (position.0 + chunk[0], position.1 + chunk[1]);
+            contour.push(position);
+        }
+    }
+    let area: Operand = contours.iter().map(|contour| area(contour)).sum();
+    let distance = if area < 0.0 { -amount } else { amount } / 2.0;
+    let mut points = contours
+        .iter()
+        .flat_map(|contour| {
+            contour
+                .iter()
+                .zip(offset(contour, distance))
+                .map(|(point, (dx, dy))| (point.0 + dx + amount / 2.0, point.1 + dy + amount / 2.0))
+        })
+        .collect::<Vec<_>>()
+        .into_iter();
+    let mut result = vec![];
+    let mut position = (0.0, 0.0);
+    for (operator, mut operands) in operations {
+        match operator {
+            RMoveTo | RLineTo | RRCurveTo => {
+                for chunk in operands.chunks_exact_mut(2) {
+                    let point = points.next().unwrap();
+                    chunk[0] = point.0 - position.0;
+                    chunk[1] = point.1 - position.1;
+                    position = point;
+                }
+            }
+            HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask | DotSection => continue,
+            _ => {}
+        }
+        result.push((operator, operands));
+    }
+    Ok(result)
+}
+
+/// Slant operations.
+///
+/// The operations are generalized first, and each point is shifted
+/// horizontally by its vertical position times the tangent of the angle, which
+/// is given in degrees clockwise from the vertical. The horizontal offsets of
+/// accents composed by `endchar` are adjusted accordingly. Hint operations are
+/// removed.
+pub fn slant(operations: &[Operation], angle: Operand) -> Result<Operations> {
+    use crate::type2::Operator::*;
+
+    let tangent = angle.to_radians().tan();
+    let mut result = vec![];
+    for (operator, mut operands) in generalize(operations)? {
+        match operator {
+            RMoveTo | RLineTo | RRCurveTo => {
+                for chunk in operands.chunks_exact_mut(2) {
+                    chunk[0] += chunk[1] * tangent;
+                }
+            }
+            EndChar if operands.len() >= 4 => {
+                let count = operands.len();
+                operands[count - 4] += operands[count - 3] * tangent;
+            }
+            HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask | DotSection => continue,
+            _ => {}
+        }
+        result.push((operator, operands));
+    }
+    Ok(result)
+}
+
+/// Specialize operations.
+///
+/// The operations are generalized first, and then each segment is expressed
+/// with the most compact operator available, merging consecutive segments
+/// whenever the stack limit allows.
+pub fn specialize(operations: &[Operation]) -> Result<Operations> {
+    let mut result: Operations = vec![];
+    for (operator, operands) in generalize(operations)? {
+        let (operator, operands) = narrow(operator, &operands);
+        if let Some((last_operator, last_operands)) = result.last_mut() {
+            if last_operands.len() + operands.len() <= STACK_LIMIT {
+                if let Some(merged) = merge(*last_operator, last_operands, operator, &operands) {
+                    *last_operator = merged;
+                    last_operands.extend(operands);
+                    continue;
+                }
+            }
+        }
+        result.push((operator, operands));
+    }
+    Ok(result)
+}
+
+fn narrow(operator: Operator, operands: &[Operand]) -> Operation {
+    use crate::type2::Operator::*;
+
+    match (operator, operands) {
+        (RMoveTo, &[dx, dy]) => match (dy == 0.0, dx == 0.0) {
+            (true, _) => (HMoveTo, vec![dx]),
+            (_, true) => (VMoveTo, vec![dy]),
+            _ => (RMoveTo, vec![dx, dy]),
+        },
+        (RLineTo, &[dx, dy]) => match (dy == 0.0, dx == 0.0) {
+            (true, _) => (HLineTo, vec![dx]),
+            (_, true) => (VLineTo, vec![dy]),
+            _ => (RLineTo, vec![dx, dy]),
+        },
+        (RRCurveTo, &[dxa, dya, dxb, dyb, dxc, dyc]) => {
+            match (dya == 0.0, dxa == 0.0, dyc == 0.0, dxc == 0.0) {
+                (true, _, true, _) => (HHCurveTo, vec![dxa, dxb, dyb, dxc]),
+                (_, true, _, true) => (VVCurveTo, vec![dya, dxb, dyb, dyc]),
+                (true, _, _, true) => (HVCurveTo, vec![dxa, dxb, dyb, dyc]),
+                (_, true, true, _) => (VHCurveTo, vec![dya, dxb, dyb, dxc]),
+                (true, _, _, _) => (HVCurveTo, vec![dxa, dxb, dyb, dyc, dxc]),
+                (_, true, _, _) => (VHCurveTo, vec![dya, dxb, dyb, dxc, dyc]),
+                (_, _, true, _) => (HHCurveTo, vec![dya, dxa, dxb, dyb, dxc]),
+                (_, _, _, true) => (VVCurveTo, vec![dxa, dya, dxb, dyb, dyc]),
+                _ => (RRCurveTo, operands.to_vec()),
+            }
+        }
+        _ => (operator, operands.to_vec()),
+    }
+}
+
+fn merge(
+    last_operator: Operator,
+    last_operands: &[Operand],
+    operator: Operator,
+    operands: &[Operand],
+) -> Option<Operator> {
+    use crate::type2::Operator::*;
+
+    let count = last_operands.len();
+    let alternate = |operator: Operator, other: Operator, segments: usize| {
+        if segments.is_multiple_of(2) {
+            operator
+        } else {
+            other
+        }
+    };
+    match (last_operator, operator) {
+        (RLineTo, RLineTo) => Some(RLineTo),
+        (RLineTo, RRCurveTo) => Some(RLineCurve),
+        (RRCurveTo, RRCurveTo) => Some(RRCurveTo),
+        (RRCurveTo, RLineTo) if operands.len() == 2 => Some(RCurveLine),
+        (HLineTo, HLineTo | VLineTo) if operator == alternate(HLineTo, VLineTo, count) => {
+            Some(HLineTo)
+        }
+        (VLineTo, HLineTo | VLineTo) if operator == alternate(VLineTo, HLineTo, count) => {
+            Some(VLineTo)
+        }
+        (HHCurveTo, HHCurveTo) | (VVCurveTo, VVCurveTo) if operands.len() == 4 => {
+            Some(last_operator)
+        }
+        (HVCurveTo, HVCurveTo | VHCurveTo)
+            if count.is_multiple_of(4)
+                && operator == alternate(HVCurveTo, VHCurveTo, count / 4) =>
+        {
+            Some(HVCurveTo)
+        }
+        (VHCurveTo, HVCurveTo | VHCurveTo)
+            if count.is_multiple_of(4)
+                && operator == alternate(VHCurveTo, HVCurveTo, count / 4) =>
+        {
+            Some(VHCurveTo)
+        }
+        _ => None,
+    }
+}
+
+fn area(points: &[(Operand, Operand)]) -> Operand {
+    let count = points.len();
+    (0..count)
+        .map(|i| {
+            let (one, other) = (points[i], points[(i + 1) % count]);
+            one.0 * other.1 - other.0 * one.1
+        })
+        .sum::<Operand>()
+        / 2.0
+}
+
+fn offset(points: &[(Operand, Operand)], distance: Operand) -> Vec<(Operand, Operand)> {
+    let count = points.len();
+    let direction = |one: (Operand, Operand), other: (Operand, Operand)| {
+        let (dx, dy) = (other.0 - one.0, other.1 - one.1);
+        let length = dx.hypot(dy);
+        (dx / length, dy / length)
+    };
+    (0..count)
+        .map(|i| {
+            let point = points[i];
+            let previous = (1..count)
+                .map(|j| points[(i + count - j) % count])
+                .find(|&other| other != point);
+            let next = (1..count)
+                .map(|j| points[(i + j) % count])
+                .find(|&other| other != point);
+            match (previous, next) {
+                (Some(previous), Some(next)) => {
+                    let (ax, ay) = direction(previous, point);
+                    let (bx, by) = direction(point, next);
+                    // The miter is limited for sharp corners.
+                    let scale = distance / (1.0 + ax * bx + ay * by).max(0.25);
+                    ((ay + by) * scale, -(ax + bx) * scale)
+                }
+                _ => (0.0, 0.0),
+            }
+        })
+        .collect()
+}
+
+#[inline]
+fn split(operands: &[Operand], modulo: usize) -> (Operand, &[Operand]) {
+    if operands.len() % modulo == 1 {
+        (operands[0], &operands[1..])
+    } else {
+        (0.0, operands)
+    }
+}
+
+fn valid(operator: Operator, count: usize) -> bool {
+    use crate::type2::Operator::*;
+
+    match operator {
+        RMoveTo => count == 2,
+        HMoveTo | VMoveTo => count == 1,
+        RLineTo => count > 0 && count.is_multiple_of(2),
+        HLineTo | VLineTo => count > 0,
+        RRCurveTo => count > 0 && count.is_multiple_of(6),
+        HHCurveTo | VVCurveTo => count >= 4 && count % 4 < 2,
+        HVCurveTo | VHCurveTo => count >= 4 && count % 4 < 2,
+        RCurveLine => count >= 8 && (count - 2).is_multiple_of(6),
+        RLineCurve => count >= 8 && (count - 6).is_multiple_of(2),
+        Flex => count == 13,
+        HFlex => count == 7,
+        HFlex1 => count == 9,
+        Flex1 => count == 11,
+        _ => true,
+    }
+}
@@ -8,6 +8,913 @@ macro_rules! operations(
     });
 );
 
+mod analysis {
+    use postscript::compact1::analysis::{Analysis, Caller, Subroutine};
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::{CharacterStrings, Subroutines};
+
+    use crate::support::setup_character_id_keyed;
+
+    #[test]
+    fn character_id_keyed() {
+        let mut set = setup_character_id_keyed();
+        // -107 callgsubr endchar
+        set.character_strings[0] = CharacterStrings(vec![vec![0x20, 0x1d, 0x0e]; 2].into());
+        // -107 callsubr return
+        set.subroutines = Subroutines(vec![vec![0x20, 0x0a, 0x0b]].into());
+        if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+            for record in record.records.iter_mut() {
+                record.subroutines = Subroutines(vec![vec![0x0b]].into());
+            }
+        }
+        let analysis = ok!(Analysis::new(&set));
+        for key in [Subroutine::Local(0, 0, 0), Subroutine::Local(0, 1, 0)] {
+            let usage = &analysis.subroutines[&key];
+            assert_eq!(usage.callers, [Caller::Subroutine(Subroutine::Global(0))],);
+            assert_eq!(usage.depth, Some(2));
+        }
+        assert!(analysis.unused().is_empty());
+    }
+
+    #[test]
+    fn cycle() {
+        let mut set = setup_character_id_keyed();
+        // -107 callsubr endchar
+        set.character_strings[0] =
+            CharacterStrings(vec![vec![0x0e], vec![0x20, 0x0a, 0x0e]].into());
+        if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+            // -107 callsubr return
+            record.records[1].subroutines = Subroutines(vec![vec![0x20, 0x0a, 0x0b]].into());
+        }
+        assert!(Analysis::new(&set).is_err());
+    }
+}
+
+mod builder {
+    use postscript::compact1::font_set::{Builder, Record};
+    use postscript::compact1::{CharacterSet, Encoding, Number, Operator};
+
+    use crate::support::{setup_character_name_keyed, write};
+
+    #[test]
+    fn character_id_keyed() {
+        let mut builder = Builder::new_character_id_keyed("Test", "Adobe", "Identity", 0.into());
+        ok!(builder.set_string(Operator::FullName, "Test Regular"));
+        assert_eq!(ok!(builder.add_dictionary()), 1);
+        ok!(builder.set_dictionary_string(1, Operator::FontName, "Test-Two"));
+        ok!(builder.set_private_operation(1, Operator::StdVW, vec![80.into()]));
+        ok!(builder.add_glyph(".notdef", vec![0x0e]));
+        ok!(builder.add_outline("cid00001", &square(100)));
+        ok!(builder.add_outline("cid00005", &square(200)));
+        assert!(builder.add_glyph("cid00005", vec![0x0e]).is_err());
+        assert!(builder.add_glyph("five", vec![0x0e]).is_err());
+        assert!(builder.set_code(65, 1).is_err());
+        ok!(builder.set_dictionary(2, 1));
+        let set = write(&ok!(builder.build()));
+        assert_eq!(set.character_strings[0].len(), 3);
+        assert_eq!(set.character_sets[0].get_string_id(2), Some(5));
+        assert_eq!(
+            set.operations[0].get(Operator::CIDCount),
+            Some(&[Number::Integer(6)][..]),
+        );
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(ok!(set.strings.get(record.registry)), "Adobe");
+                assert_eq!(record.encoding.get(1), Some(0));
+                assert_eq!(record.encoding.get(2), Some(1));
+                let string_id = match record.operations[1].get_single(Operator::FontName) {
+                    Some(Number::Integer(value)) => value as u16,
+                    _ => unreachable!(),
+                };
+                assert_eq!(ok!(set.strings.get(string_id)), "Test-Two");
+                assert_eq!(
+                    record.records[1].operations.get(Operator::StdVW),
+                    Some(&[Number::Integer(80)][..]),
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn character_name_keyed() {
+        let mut builder = Builder::new("Test");
+        assert!(builder.add_glyph("A", vec![0x0e]).is_err());
+        ok!(builder.set_string(Operator::Notice, "Notice"));
+        assert!(builder.set_string(Operator::FontBBox, "").is_err());
+        ok!(builder.set_operation(
+            Operator::FontBBox,
+            vec![0.into(), 0.into(), 200.into(), 200.into()]
+        ));
+        assert!(builder
+            .set_operation(Operator::CharStrings, vec![])
+            .is_err());
+        ok!(builder.set_private_operation(0, Operator::BlueValues, vec![(-10).into(), 10.into()]));
+        assert!(builder
+            .set_private_operation(1, Operator::BlueValues, vec![])
+            .is_err());
+        assert!(builder.add_dictionary().is_err());
+        ok!(builder.add_glyph(".notdef", vec![0x0e]));
+        ok!(builder.add_outline("A", &square(100)));
+        ok!(builder.add_outline("B", &square(200)));
+        ok!(builder.add_outline("A.alt", &square(300)));
+        assert!(builder.add_glyph("bad", vec![0x0a, 0x0e]).is_err());
+        ok!(builder.set_code(65, 1));
+        ok!(builder.set_code(66, 2));
+        let set = write(&ok!(builder.build()));
+        assert_eq!(
+            ok!(set.strings.get(ok!(set.character_sets[0].get_string_id(3)))),
+            "A.alt"
+        );
+        assert_eq!(set.character_sets[0].get(1), None);
+        assert_eq!(set.character_sets[0].get_string_id(1), Some(34));
+        match &set.encodings[0] {
+            Encoding::Format0(ref encoding) => assert_eq!(encoding.codes, [65, 66]),
+            _ => unreachable!(),
+        }
+        match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert_eq!(
+                    record.operations.get(Operator::BlueValues),
+                    Some(&[Number::Integer(-10), Number::Integer(10)][..]),
+                );
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(set.character_strings[0][1], ok!(square(100).encode()));
+
+        let mut builder = Builder::new("Test");
+        ok!(builder.add_glyph(".notdef", vec![0x0e]));
+        ok!(builder.add_glyph("space", vec![0x0e]));
+        ok!(builder.add_glyph("exclam", vec![0x0e]));
+        ok!(builder.set_code(66, 1));
+        ok!(builder.set_code(65, 2));
+        let set = write(&ok!(builder.build()));
+        assert!(matches!(set.character_sets[0], CharacterSet::ISOAdobe));
+        assert!(matches!(set.encodings[0], Encoding::Format0(_)));
+
+        let mut builder = Builder::new("Test");
+        ok!(builder.add_glyph(".notdef", vec![0x0e]));
+        ok!(builder.add_glyph("space", vec![0x0e]));
+        ok!(builder.add_glyph("exclam", vec![0x0e]));
+        ok!(builder.set_code(66, 2));
+        let set = write(&ok!(builder.build()));
+        match &set.encodings[0] {
+            Encoding::Format0(ref encoding) => assert_eq!(encoding.codes, [0, 66]),
+            _ => unreachable!(),
+        }
+    }
+
+    fn square(size: i32) -> postscript::type2::Builder {
+        let size = size as f32;
+        let mut builder = postscript::type2::Builder::new();
+        builder.set_width(size);
+        builder.move_to(0.0, 0.0);
+        builder.line_to(size, 0.0);
+        builder.line_to(size, size);
+        builder.line_to(0.0, size);
+        builder.close();
+        builder
+    }
+
+    #[test]
+    fn encoding_supplemental() {
+        use postscript::compact1::encoding::{EncodingSupplemental, Range1, Supplement};
+
+        let mut set = setup_character_name_keyed();
+        let string_id = ok!(set.character_sets[0].get_string_id(1));
+        let encoding = EncodingSupplemental {
+            format: 0x81,
+            count: 1,
+            codes: vec![],
+            ranges: vec![Range1 {
+                first_code: 0x41,
+                left_count: 2,
+            }],
+            supplement_count: 1,
+            supplements: vec![Supplement {
+                code: 0x61,
+                glyph: string_id,
+            }],
+        };
+        set.encodings[0] = Encoding::FormatSupplemental(encoding.clone());
+        let other = write(&set);
+        match &other.encodings[0] {
+            Encoding::FormatSupplemental(ref other) => {
+                assert_eq!(other.format, 0x81);
+                assert_eq!(other.ranges.len(), 1);
+                assert_eq!(other.ranges[0].left_count, 2);
+                assert_eq!(other.supplements.len(), 1);
+                assert_eq!(other.supplements[0].glyph, string_id);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut encoding = encoding;
+        encoding.count = 2;
+        set.encodings[0] = Encoding::FormatSupplemental(encoding);
+        let mut tape = std::io::Cursor::new(vec![]);
+        assert!(postscript::tape::Write::give(&mut tape, &set).is_err());
+    }
+}
+
+mod convert {
+    use postscript::compact1::editor::Editor;
+    use postscript::compact1::font_set::{Builder, Record};
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{Number, Operator};
+
+    use crate::support::{
+        code, interpret, setup_character_id_keyed, setup_character_name_keyed, setup_font_set,
+        write, Fixture,
+    };
+
+    #[test]
+    fn accents() {
+        let mut set = setup_character_name_keyed();
+        ok!(set.convert_to_character_id_keyed(0));
+        let (operations, width) = interpret(&write(&set), 3);
+        assert!(!operations.is_empty());
+        assert_eq!(width, Some(500.0));
+    }
+
+    #[test]
+    fn character_id_keyed() {
+        let matrix = |value: f32| {
+            let mut operands = vec![Number::Integer(0); 6];
+            operands[0] = Number::Real(value);
+            operands[3] = Number::Real(value);
+            operands
+        };
+        let mut builder =
+            Builder::new_character_id_keyed("Test", "Adobe", "Identity", Number::Integer(0));
+        ok!(builder.add_dictionary());
+        for (dictionary_id, name) in [(0, "Test-Zero"), (1, "Test-One")] {
+            ok!(builder.set_dictionary_string(dictionary_id, Operator::FontName, name));
+            let operands = vec![Number::Integer(50)];
+            ok!(builder.set_private_operation(dictionary_id, Operator::StdVW, operands));
+        }
+        for (name, dictionary_id) in [(".notdef", 0), ("cid00007", 0), ("cid00009", 1)] {
+            let glyph_id = ok!(builder.add_glyph(name, vec![0x0e]));
+            ok!(builder.set_dictionary(glyph_id, dictionary_id));
+        }
+        let mut set = ok!(builder.build());
+        set.operations[0].insert(Operator::FontMatrix, matrix(1.0));
+        set.character_strings[0].replace(1, code("0 0 rmoveto -107 callsubr endchar"));
+        set.character_strings[0]
+            .replace(2, code("0 0 rmoveto -107 callsubr -106 callsubr endchar"));
+        set.subroutines = Subroutines(vec![code("40 40 rlineto return")].into());
+        if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+            for operations in record.operations.iter_mut() {
+                operations.insert(Operator::FontMatrix, matrix(0.0005));
+            }
+            record.records[0].subroutines = Subroutines(vec![code("10 10 rlineto return")].into());
+            record.records[1].subroutines = Subroutines(
+                vec![
+                    code("20 20 rlineto return"),
+                    code("30 30 rlineto -107 callgsubr return"),
+                ]
+                .into(),
+            );
+        }
+        let mut other = set.clone();
+        ok!(other.convert_to_character_name_keyed(0));
+        let mut other = write(&other);
+        assert_eq!(
+            other.operations[0].get(Operator::FontMatrix),
+            Some(
+                &[
+                    0.0005.into(),
+                    0.into(),
+                    0.into(),
+                    0.0005.into(),
+                    0.into(),
+                    0.into()
+                ][..]
+            ),
+        );
+        assert!(!other.operations[0].contains_key(&Operator::ROS));
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert_eq!(record.subroutines.len(), 3);
+                assert_eq!(
+                    record.operations.get(Operator::StdVW),
+                    Some(&[Number::Integer(50)][..]),
+                );
+            }
+            _ => unreachable!(),
+        }
+        for glyph_id in 0..3 {
+            assert_eq!(interpret(&set, glyph_id), interpret(&other, glyph_id));
+        }
+        assert_eq!(other.strings.len(), 2);
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_name(1).as_deref(), Some("cid00007"));
+        assert_eq!(editor.glyph_name(2).as_deref(), Some("cid00009"));
+
+        let mut other = set.clone();
+        if let Record::CharacterIDKeyed(ref mut record) = other.records[0] {
+            let operands = vec![Number::Integer(60)];
+            record.records[0]
+                .operations
+                .insert(Operator::StdVW, operands);
+        }
+        assert!(other.convert_to_character_name_keyed(0).is_err());
+
+        let mut other = set.clone();
+        if let Record::CharacterIDKeyed(ref mut record) = other.records[0] {
+            let operations = &mut record.records[1].operations;
+            operations.insert(Operator::DefaultWidthX, vec![Number::Integer(500)]);
+            operations.insert(Operator::NominalWidthX, vec![Number::Integer(100)]);
+        }
+        ok!(other.convert_to_character_name_keyed(0));
+        let other = write(&other);
+        assert_eq!(interpret(&other, 1).1, None);
+        assert_eq!(interpret(&other, 2).1, Some(500.0));
+
+        let mut other = set.clone();
+        other.operations[0].insert(Operator::FontMatrix, matrix(2.0));
+        ok!(other.convert_to_character_name_keyed(0));
+        assert!(!other.operations[0].contains_key(&Operator::FontMatrix));
+    }
+
+    #[test]
+    fn subroutine_limit() {
+        let mut set = setup_character_id_keyed();
+        match set.records[0] {
+            Record::CharacterIDKeyed(ref mut record) => {
+                for record in record.records.iter_mut() {
+                    record.subroutines = Subroutines(vec![vec![0x0b]; 40000].into());
+                }
+            }
+            _ => unreachable!(),
+        }
+        assert!(set.convert_to_character_name_keyed(0).is_err());
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.convert_to_character_id_keyed(0));
+        let mut other = write(&other);
+        assert_eq!(other.strings.len(), 7);
+        assert_eq!(
+            other.operations[0].get(Operator::CIDCount),
+            Some(&[Number::Integer(547)][..]),
+        );
+        assert_eq!(other.character_sets[0].get_string_id(42), Some(42));
+        match &other.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(ok!(other.strings.get(record.registry)), "Adobe");
+                assert_eq!(ok!(other.strings.get(record.ordering)), "Identity");
+                assert_eq!(record.records.len(), 1);
+                assert_eq!(record.records[0].subroutines.len(), 180);
+                assert_eq!(record.encoding.get(546), Some(0));
+            }
+            _ => unreachable!(),
+        }
+        for glyph_id in 0..547 {
+            assert_eq!(interpret(&set, glyph_id), interpret(&other, glyph_id));
+        }
+
+        ok!(other.convert_to_character_name_keyed(0));
+        let mut other = write(&other);
+        for glyph_id in 0..547 {
+            assert_eq!(interpret(&set, glyph_id), interpret(&other, glyph_id));
+        }
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_name(42).as_deref(), Some("cid00042"));
+    }
+}
+
+mod dehint {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{FontSet, GlyphID, Number, Operator};
+    use postscript::type2::{assemble, Operation};
+
+    use crate::support::{code, interpret, setup_builder, setup_font_set, write, Fixture};
+
+    #[test]
+    fn character_name_keyed() {
+        let local = vec![code("hstemhm 30 40 return"), code("10 20 return")];
+        let mut builder = setup_builder(&[(".notdef", "endchar"), ("A", "endchar")]);
+        let operands = [-10, 10, 500, 10].map(Number::Integer).to_vec();
+        ok!(builder.set_private_operation(0, Operator::BlueValues, operands));
+        let mut set = ok!(builder.build());
+        let text = "100 10 20 -107 callsubr hintmask 11000000 0 0 rmoveto \
+                    hintmask 10000000 -106 callsubr rlineto endchar";
+        set.character_strings[0].replace(1, ok!(assemble(text, &[], &local)));
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            record.subroutines = Subroutines(local.clone().into());
+        }
+        let mut other = set.clone();
+        ok!(other.dehint(true));
+        let other = write(&other);
+        let code = ok!(assemble(
+            "100 -107 callsubr 0 0 rmoveto -106 callsubr rlineto endchar",
+            &[],
+            &vec![vec![]; 2]
+        ));
+        assert_eq!(other.character_strings[0][1], code);
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert!(record.operations.get(Operator::BlueValues).is_none());
+                assert_eq!(record.subroutines[0], [0x0b]);
+                assert_eq!(record.subroutines[1], [0x95, 0x9f, 0x0b]);
+            }
+            _ => unreachable!(),
+        }
+        compare(&set, &other, 1);
+
+        let text = text.replace("0 0 rmoveto", "-106 callsubr hstem 0 0 rmoveto");
+        set.character_strings[0].replace(1, ok!(assemble(&text, &[], &local)));
+        assert!(set.dehint(false).is_err());
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.dehint(true));
+        let other = write(&other);
+        let size = |set: &FontSet| -> usize {
+            set.character_strings[0]
+                .iter()
+                .chain(set.subroutines.iter())
+                .map(Vec::len)
+                .sum()
+        };
+        assert!(size(&other) < size(&set));
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert!(record.operations.get(Operator::BlueValues).is_none());
+                assert!(record.operations.get(Operator::StdHW).is_none());
+                assert!(record.operations.get(Operator::Subrs).is_some());
+            }
+            _ => unreachable!(),
+        }
+        for glyph_id in 0..547 {
+            compare(&set, &other, glyph_id);
+        }
+    }
+
+    fn compare(one: &FontSet, other: &FontSet, glyph_id: GlyphID) {
+        use postscript::type2::Operator::*;
+
+        let hint = |(operator, _): &Operation| {
+            matches!(
+                operator,
+                HStem | VStem | HStemHM | VStemHM | HintMask | CntrMask
+            )
+        };
+        let (mut operations, width) = interpret(one, glyph_id);
+        operations.retain(|operation| !hint(operation));
+        let other = interpret(other, glyph_id);
+        assert!(!other.0.iter().any(hint));
+        assert_eq!((operations, width), other);
+    }
+}
+
+mod editor {
+    use postscript::compact1::editor::Editor;
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::{Encoding, Number, Operator};
+
+    use crate::support::{setup_builder, setup_character_id_keyed, setup_font_set, write, Fixture};
+
+    #[test]
+    fn character_id_keyed() {
+        let mut set = setup_character_id_keyed();
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        assert!(editor.add_glyph("cid00007", vec![0x0e]).is_err());
+        ok!(editor.set_operation(Operator::CIDCount, vec![Number::Integer(8)]));
+        assert!(editor
+            .add_glyph("cid00007", vec![0x8b, 0x0a, 0x0e])
+            .is_err());
+        assert_eq!(ok!(editor.add_glyph("cid00007", vec![0x0e])), 2);
+        assert!(editor.add_glyph("cid00007", vec![0x0e]).is_err());
+        assert!(editor.add_glyph("seven", vec![0x0e]).is_err());
+        assert!(editor.set_dictionary(1, 0).is_err());
+        ok!(editor.set_dictionary(2, 1));
+        assert_eq!(editor.glyph_name(2).as_deref(), Some("cid00007"));
+        let set = write(&set);
+        assert_eq!(set.character_sets[0].get_string_id(2), Some(7));
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.encoding.get(1), Some(1));
+                assert_eq!(record.encoding.get(2), Some(1));
+                assert_eq!(record.encoding.get(3), None);
+            }
+            _ => unreachable!(),
+        }
+
+        let mut set = set;
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        assert!(editor.reorder_glyphs(&[1, 0, 2]).is_err());
+        assert!(editor.reorder_glyphs(&[0, 2, 2]).is_err());
+        ok!(editor.reorder_glyphs(&[0, 2, 1]));
+        assert!(editor.rename_glyph(1, "cid00001").is_err());
+        ok!(editor.rename_glyph(1, "cid00003"));
+        let set = write(&set);
+        assert_eq!(set.character_sets[0].get_string_id(1), Some(3));
+        assert_eq!(set.character_sets[0].get_string_id(2), Some(1));
+        assert_eq!(set.character_strings[0][1], [0x0e]);
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.encoding.get(1), Some(1));
+                assert_eq!(record.encoding.get(2), Some(1));
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn character_name_keyed() {
+        let mut builder = setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "10 endchar"),
+            ("B", "20 endchar"),
+            ("C.alt", "30 endchar"),
+        ]);
+        ok!(builder.set_code(0x41, 1));
+        ok!(builder.set_code(0x42, 2));
+        let mut set = ok!(builder.build());
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        assert!(editor.reorder_glyphs(&[0, 1, 1, 2]).is_err());
+        ok!(editor.reorder_glyphs(&[0, 2, 1, 3]));
+        assert!(editor.rename_glyph(0, "null").is_err());
+        assert!(editor.rename_glyph(1, "A").is_err());
+        ok!(editor.rename_glyph(1, "uni0042"));
+        ok!(editor.rename_glyph(3, "uni0043"));
+        ok!(editor.rename_glyph(2, "A"));
+
+        let mut other = write(&set);
+        assert_eq!(other.strings.len(), 2);
+        match &other.encodings[0] {
+            Encoding::Format0(ref encoding) => assert_eq!(encoding.codes, [0x42, 0x41]),
+            _ => unreachable!(),
+        }
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_name(1).as_deref(), Some("uni0042"));
+        assert_eq!(editor.glyph_name(2).as_deref(), Some("A"));
+        assert_eq!(editor.glyph_name(3).as_deref(), Some("uni0043"));
+        assert_eq!(ok!(editor.character_string(1)), &[0x9f, 0x0e]);
+    }
+
+    #[test]
+    fn encoding_supplemental() {
+        use postscript::compact1::encoding::{EncodingSupplemental, Supplement};
+
+        let mut set = setup_font_set(Fixture::SourceSerifPro);
+        let string_id = match set.operations[0].get_single(Operator::Notice) {
+            Some(Number::Integer(value)) => value as u16,
+            _ => unreachable!(),
+        };
+        let notice = ok!(set.strings.get(string_id));
+        set.encodings[0] = Encoding::FormatSupplemental(EncodingSupplemental {
+            format: 0x80,
+            count: 0,
+            codes: vec![],
+            ranges: vec![],
+            supplement_count: 1,
+            supplements: vec![Supplement {
+                code: 0x41,
+                glyph: string_id,
+            }],
+        });
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        ok!(editor.set_string(Operator::Notice, "Notice"));
+        assert_eq!(set.strings.get(string_id), Some(notice));
+    }
+
+    #[test]
+    fn encoding_supplemental_order() {
+        use postscript::compact1::encoding::{EncodingSupplemental, Supplement};
+
+        let mut set = ok!(setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "10 endchar"),
+            ("B", "20 endchar"),
+        ])
+        .build());
+        let string_id = ok!(set.character_sets[0].get_string_id(1));
+        set.encodings[0] = Encoding::FormatSupplemental(EncodingSupplemental {
+            format: 0x80,
+            count: 2,
+            codes: vec![0x41, 0x42],
+            ranges: vec![],
+            supplement_count: 1,
+            supplements: vec![Supplement {
+                code: 0x61,
+                glyph: string_id,
+            }],
+        });
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        ok!(editor.reorder_glyphs(&[0, 2, 1]));
+        let other = write(&set);
+        match &other.encodings[0] {
+            Encoding::FormatSupplemental(ref encoding) => {
+                assert_eq!(encoding.codes, [0x42, 0x41]);
+                assert_eq!(encoding.supplements.len(), 1);
+                assert_eq!(encoding.supplements[0].glyph, string_id);
+            }
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn source_serif() {
+        let mut set = setup_font_set(Fixture::SourceSerifPro);
+        let mut editor = ok!(Editor::new(&mut set, 0));
+        assert_eq!(editor.glyph_count(), 547);
+        assert_eq!(
+            editor.get_string(Operator::FullName).as_deref(),
+            Some("Source Serif Pro")
+        );
+        ok!(editor.set_string(Operator::FullName, "Source Serif Pro Test"));
+        ok!(editor.set_string(Operator::Notice, "Notice"));
+        assert!(editor.set_operation(Operator::CharStrings, vec![]).is_err());
+        assert!(editor.set_string(Operator::FontBBox, "").is_err());
+
+        let glyph_id = ok!(editor.glyph_id("Aring"));
+        let code = ok!(editor.character_string(glyph_id)).to_vec();
+        assert_eq!(ok!(editor.add_glyph("Aring.alt", code.clone())), 547);
+        assert!(editor.add_glyph("Aring", code.clone()).is_err());
+        assert!(editor
+            .set_character_string(glyph_id, vec![0xfa, 0x7c, 0x0a, 0x0e])
+            .is_err());
+        ok!(editor.set_character_string(0, vec![0x0e]));
+        let operands = vec![Number::Integer(-20), Number::Integer(20)];
+        ok!(editor.set_private_operation(0, Operator::BlueValues, operands.clone()));
+        assert!(editor
+            .set_private_operation(1, Operator::BlueValues, operands.clone())
+            .is_err());
+
+        let mut other = write(&set);
+        assert_eq!(other.strings.len(), 324);
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_count(), 548);
+        assert_eq!(editor.glyph_id("Aring.alt"), Some(547));
+        assert_eq!(editor.glyph_name(547).as_deref(), Some("Aring.alt"));
+        assert_eq!(ok!(editor.character_string(547)), &code[..]);
+        assert_eq!(ok!(editor.character_string(0)), &[0x0e]);
+        assert_eq!(
+            editor.get_string(Operator::FullName).as_deref(),
+            Some("Source Serif Pro Test"),
+        );
+        assert_eq!(
+            editor.get_string(Operator::FamilyName).as_deref(),
+            Some("Source Serif Pro")
+        );
+        assert_eq!(
+            editor.get_string(Operator::Notice).as_deref(),
+            Some("Notice")
+        );
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert_eq!(
+                    record.operations.get(Operator::BlueValues),
+                    Some(&operands[..])
+                );
+            }
+            _ => unreachable!(),
+        }
+    }
+}
+
+mod error {
+    use std::io::Cursor;
+
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::recovery::Recovered;
+    use postscript::compact1::{ErrorWithLocation, FontSet, Location, Number, Operator};
+    use postscript::tape::{Read, Write};
+
+    use crate::support::{setup_character_id_keyed, Fixture};
+
+    #[test]
+    fn character_id_keyed() {
+        let mut tape = Cursor::new(vec![]);
+        ok!(tape.give(&setup_character_id_keyed()));
+        let data = tape.into_inner();
+        let set: FontSet = ok!(Cursor::new(&data).take());
+        let (size, offset, subroutines) = match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                let operands = ok!(record.operations[1].get(Operator::Private));
+                let subroutines = ok!(record.records[1].operations.get_single(Operator::Subrs));
+                (operands[0], operands[1], subroutines)
+            }
+            _ => unreachable!(),
+        };
+        let (offset, subroutines) = match (size, offset, subroutines) {
+            (Number::Integer(_), Number::Integer(offset), Number::Integer(subroutines)) => {
+                (offset as usize, offset as usize + subroutines as usize)
+            }
+            _ => unreachable!(),
+        };
+        let glyphs = match ok!(set.operations[0].get_single(Operator::CharStrings)) {
+            Number::Integer(offset) => offset as usize,
+            _ => unreachable!(),
+        };
+
+        let mut other = data.clone();
+        other[offset] = 0xff;
+        let (location, position) = locate(&other, 0);
+        assert_eq!(location, Location::Private(0, 1));
+        assert_eq!(position, offset as u64);
+
+        let mut other = data.clone();
+        other[subroutines + 2] = 0;
+        let (location, position) = locate(&other, 0);
+        assert_eq!(location, Location::LocalSubroutines(0, 1));
+        assert_eq!(position, subroutines as u64);
+
+        let mut other = data.clone();
+        other[glyphs + 6 + 1 + 2] = 0;
+        assert!(Cursor::new(&other).take::<FontSet>().is_ok());
+        let omissions = ok!(Recovered::new(&other)).omissions;
+        assert_eq!(omissions.len(), 1);
+        assert_eq!(omissions[0].location, Location::Glyph(0, 1));
+        assert_eq!(omissions[0].offset, glyphs as u64 + 6 + 1 + 3);
+
+        let mut other = data.clone();
+        other[subroutines + 5] = 0;
+        let omissions = ok!(Recovered::new(&other)).omissions;
+        assert_eq!(omissions.len(), 1);
+        assert_eq!(omissions[0].location, Location::Glyph(0, 1));
+        assert_eq!(omissions[0].offset, subroutines as u64 + 5 + 1);
+    }
+
+    #[test]
+    fn source_serif() {
+        let mut data = ok!(std::fs::read(Fixture::SourceSerifPro.path()));
+        let start = Fixture::SourceSerifPro.offset() as usize;
+        let set: FontSet = ok!(Cursor::new(&data[start..]).take());
+        let offset = match ok!(set.operations[0].get_single(Operator::CharStrings)) {
+            Number::Integer(offset) => start + offset as usize,
+            _ => unreachable!(),
+        };
+        data[offset + 2] = 0;
+        let mut tape = Cursor::new(&data);
+        tape.set_position(start as u64);
+        let error = tape.take::<FontSet>().unwrap_err();
+        let located = ok!(ErrorWithLocation::find(&error));
+        assert_eq!(located.location, Location::CharacterStrings(0));
+        assert_eq!(located.offset, offset as u64);
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "found a malformed offset in the index of character strings of font 0 at offset {offset}",
+            ),
+        );
+    }
+
+    fn locate(data: &[u8], start: u64) -> (Location, u64) {
+        let mut tape = Cursor::new(data);
+        tape.set_position(start);
+        let error = tape.take::<FontSet>().unwrap_err();
+        let located = ok!(ErrorWithLocation::find(&error));
+        (located.location, located.offset)
+    }
+}
+
+mod merge {
+    use postscript::compact1::font_set::{Builder, Record};
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{FontSet, GlyphID, Number, Operator};
+
+    use crate::support::{
+        code, interpret, setup_character_id_keyed, setup_character_name_keyed, setup_font_set,
+        write, Fixture,
+    };
+
+    #[test]
+    fn accents() {
+        use postscript::type2::{generalize, Operator::*};
+
+        let one = setup_character_name_keyed();
+        let set = write(&ok!(FontSet::merge("Merged", &[(&one, 0)])));
+        let (operations, width) = interpret(&set, 3);
+        assert_eq!(
+            ok!(generalize(&operations)),
+            [
+                (RMoveTo, vec![0.0, 0.0]),
+                (RLineTo, vec![400.0, 0.0]),
+                (RLineTo, vec![0.0, 600.0]),
+                (RMoveTo, vec![-300.0, 100.0]),
+                (RLineTo, vec![100.0, 100.0]),
+            ],
+        );
+        assert_eq!(width, Some(500.0));
+    }
+
+    #[test]
+    fn matrices() {
+        let matrix = |value: f32| {
+            let mut operands = vec![Number::Integer(0); 6];
+            operands[0] = Number::Real(value);
+            operands[3] = Number::Real(value);
+            operands
+        };
+        let mut one = setup_character_id_keyed();
+        one.operations[0].insert(Operator::FontMatrix, matrix(1.0));
+        match one.records[0] {
+            Record::CharacterIDKeyed(ref mut record) => {
+                for operations in record.operations.iter_mut() {
+                    operations.insert(Operator::FontMatrix, matrix(0.001));
+                }
+            }
+            _ => unreachable!(),
+        }
+        let other = setup_character_name_keyed();
+        let set = write(&ok!(FontSet::merge("Merged", &[(&one, 0), (&other, 0)])));
+        assert!(!set.operations[0].0.contains_key(&Operator::FontMatrix));
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.operations.len(), 3);
+                for operations in record.operations.iter() {
+                    assert!(!operations.0.contains_key(&Operator::FontMatrix));
+                }
+            }
+            _ => unreachable!(),
+        }
+
+        match one.records[0] {
+            Record::CharacterIDKeyed(ref mut record) => {
+                record.operations[1].insert(Operator::FontMatrix, matrix(0.002));
+            }
+            _ => unreachable!(),
+        }
+        assert!(FontSet::merge("Merged", &[(&one, 0), (&other, 0)]).is_err());
+    }
+
+    #[test]
+    fn source_serif() {
+        let mut builder =
+            Builder::new_character_id_keyed("Test", "Adobe", "Identity", Number::Integer(0));
+        let bounding_box = vec![(-10).into(), (-300).into(), 2000.into(), 1000.into()];
+        ok!(builder.set_operation(Operator::FontBBox, bounding_box));
+        ok!(builder.set_dictionary_string(0, Operator::FontName, "Test-Zero"));
+        for name in [".notdef", "cid00001", "cid01000"] {
+            ok!(builder.add_glyph(name, vec![0x0e]));
+        }
+        let mut other = ok!(builder.build());
+        other.character_strings[0].replace(1, code("0 0 rmoveto -107 callsubr endchar"));
+        other.character_strings[0].replace(2, code("0 0 rmoveto -107 callgsubr endchar"));
+        other.subroutines = Subroutines(vec![code("40 40 rlineto return")].into());
+        if let Record::CharacterIDKeyed(ref mut record) = other.records[0] {
+            record.records[0].subroutines = Subroutines(vec![code("-107 callgsubr return")].into());
+        }
+        let one = setup_font_set(Fixture::SourceSerifPro);
+        let set = write(&ok!(FontSet::merge(
+            "Merged",
+            &[(&one, 0), (&other, 0), (&one, 0)]
+        )));
+        assert_eq!(&set.names[0][..], b"Merged");
+        assert_eq!(set.character_strings[0].len(), 547 + 2 + 546);
+        assert_eq!(set.subroutines.len(), 2 * one.subroutines.len() + 1);
+        let operations = &set.operations[0];
+        assert_eq!(
+            operations.get(Operator::FontBBox),
+            Some(&[(-178).into(), (-335).into(), 2000.into(), 1000.into()][..]),
+        );
+        assert_eq!(
+            operations.get(Operator::CIDCount),
+            Some(&[Number::Integer(1096)][..]),
+        );
+        let character_set = &set.character_sets[0];
+        assert_eq!(character_set.get_string_id(546), Some(546));
+        assert_eq!(character_set.get_string_id(547), Some(547));
+        assert_eq!(character_set.get_string_id(548), Some(1000));
+        assert_eq!(character_set.get_string_id(549), Some(549));
+        assert_eq!(character_set.get_string_id(1000), Some(1001));
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.records.len(), 3);
+                assert_eq!(record.encoding.get(546), Some(0));
+                assert_eq!(record.encoding.get(547), Some(1));
+                assert_eq!(record.encoding.get(549), Some(2));
+                let name = |i: usize| match record.operations[i].get_single(Operator::FontName) {
+                    Some(Number::Integer(string_id)) => set.strings.get(string_id as _),
+                    _ => None,
+                };
+                assert_eq!(name(0).as_deref(), Some("SourceSerifPro-Regular"));
+                assert_eq!(name(1).as_deref(), Some("Test-Zero"));
+            }
+            _ => unreachable!(),
+        }
+        let glyphs = (0..547)
+            .map(|glyph_id| (&one, glyph_id))
+            .chain((1..3).map(|glyph_id| (&other, glyph_id)))
+            .chain((1..547).map(|glyph_id| (&one, glyph_id)));
+        for (i, (source, glyph_id)) in glyphs.enumerate() {
+            assert_eq!(interpret(source, glyph_id), interpret(&set, i as GlyphID));
+        }
+    }
+}
+
 mod noto_sans_direct {
     use postscript::tape::Read;
 
@@ -156,21 +1063,489 @@ mod noto_sans_indirect {
     }
 }
 
-mod source_serif {
-    use crate::support::{setup_font_set, Fixture};
+mod optimize {
+    use postscript::compact1::font_set::{Builder, Record};
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{Number, Operator};
+    use postscript::tape::Write;
+
+    use crate::support::{code, interpret, setup_font_set, write, Fixture};
 
     #[test]
-    fn header() {
-        let set = setup_font_set(Fixture::SourceSerifPro);
-        let table = &set.header;
-        assert_eq!(table.major, 1);
-        assert_eq!(table.minor, 0);
-        assert_eq!(table.header_size, 4);
-        assert_eq!(table.offset_size, 2);
+    fn character_id_keyed() {
+        let mut builder =
+            Builder::new_character_id_keyed("Test", "Adobe", "Identity", Number::Integer(0));
+        for (dictionary_id, name) in ["Test-Zero", "Test-One", "Test-Zero"].iter().enumerate() {
+            if dictionary_id > 0 {
+                ok!(builder.add_dictionary());
+            }
+            ok!(builder.set_dictionary_string(dictionary_id as u8, Operator::FontName, name));
+        }
+        for (name, dictionary_id) in [
+            (".notdef", 0),
+            ("cid00001", 0),
+            ("cid00002", 1),
+            ("cid00003", 1),
+            ("cid00004", 2),
+        ] {
+            let glyph_id = ok!(builder.add_glyph(name, vec![0x0e]));
+            ok!(builder.set_dictionary(glyph_id, dictionary_id));
+        }
+        let mut set = ok!(builder.build());
+        let curves = "100 200 rmoveto 300 400 500 600 700 800 rrcurveto \
+                      -900 -1000 -1100 -1200 -1300 -1400 rrcurveto -105 callgsubr endchar";
+        for (glyph_id, text) in [
+            (
+                1,
+                "0 0 rmoveto -107 callsubr -106 callsubr -107 callgsubr endchar",
+            ),
+            (2, curves),
+            (3, curves),
+            (4, "0 0 rmoveto -106 callsubr endchar"),
+        ] {
+            set.character_strings[0].replace(glyph_id, code(text));
+        }
+        set.subroutines = Subroutines(
+            vec![
+                code("20 20 rlineto return"),
+                code("30 30 rlineto return"),
+                code("20 20 rlineto return"),
+            ]
+            .into(),
+        );
+        if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+            for j in [0, 2] {
+                record.records[j].subroutines =
+                    Subroutines(vec![code("10 10 rlineto return"); 2].into());
+            }
+        }
+        let mut other = set.clone();
+        other.strings.insert("Unused");
+        ok!(other.optimize());
+        let other = write(&other);
+        assert_eq!(other.strings.len(), 4);
+        assert_eq!(other.subroutines.len(), 2);
+        assert_eq!(other.character_strings[0][2], other.character_strings[0][3]);
+        assert!(other.character_strings[0][2].len() < 4);
+        match &other.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.records.len(), 2);
+                assert_eq!(record.records[0].subroutines.len(), 1);
+                assert_eq!(record.records[1].subroutines.len(), 0);
+                assert_eq!(record.encoding.get(4), Some(0));
+            }
+            _ => unreachable!(),
+        }
+        for glyph_id in 0..5 {
+            assert_eq!(interpret(&set, glyph_id), interpret(&other, glyph_id));
+        }
     }
 
     #[test]
-    fn names() {
+    fn deterministic() {
+        let mut set = setup_font_set(Fixture::SourceSerifPro);
+        for glyph_id in 1..41 {
+            let code = set.character_strings[0][glyph_id % 20 + 1].clone();
+            set.character_strings[0].replace(glyph_id, code);
+        }
+        let mut one = set.clone();
+        ok!(one.optimize());
+        for _ in 0..4 {
+            let mut other = set.clone();
+            ok!(other.optimize());
+            assert_eq!(other.subroutines.data, one.subroutines.data);
+            assert_eq!(
+                other.character_strings[0].data,
+                one.character_strings[0].data
+            );
+        }
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.optimize());
+        let mut one = vec![];
+        ok!(one.give(&set));
+        let mut data = vec![];
+        ok!(data.give(&other));
+        assert!(data.len() <= one.len());
+        let other = write(&other);
+        assert_eq!(other.strings.len(), set.strings.len());
+        for glyph_id in 0..547 {
+            assert_eq!(interpret(&set, glyph_id), interpret(&other, glyph_id));
+        }
+    }
+}
+
+mod recovery {
+    use std::io::Cursor;
+
+    use postscript::compact1::font_set::character_id_keyed::Encoding;
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::recovery::Recovered;
+    use postscript::compact1::validator::Report;
+    use postscript::compact1::{CharacterSet, FontSet, Location, Number, Operator};
+    use postscript::tape::{Read, Write};
+    use postscript::type2::Program;
+
+    use crate::support::{setup_character_id_keyed, setup_font_set, Fixture};
+
+    #[test]
+    fn character_id_keyed() {
+        let mut tape = Cursor::new(vec![]);
+        ok!(tape.give(&setup_character_id_keyed()));
+        let mut data = tape.into_inner();
+        let set: FontSet = ok!(Cursor::new(&data).take());
+        let encoding = integer(&set, Operator::FDSelect);
+        let private = match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                match ok!(record.operations[1].get(Operator::Private)) {
+                    [_, Number::Integer(offset)] => *offset as usize,
+                    _ => unreachable!(),
+                }
+            }
+            _ => unreachable!(),
+        };
+        data[encoding] = 7;
+        data[private] = 0xff;
+        assert!(Cursor::new(&data).take::<FontSet>().is_err());
+
+        let Recovered {
+            mut font_set,
+            omissions,
+        } = ok!(Recovered::new(&data));
+        let locations = omissions
+            .iter()
+            .map(|omission| omission.location)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            locations,
+            [Location::DictionaryEncoding(0), Location::Private(0, 1)],
+        );
+        let record = match &mut font_set.records[0] {
+            Record::CharacterIDKeyed(ref mut record) => record,
+            _ => unreachable!(),
+        };
+        assert!(matches!(record.encoding, Encoding::Missing));
+        assert_eq!(record.encoding.get(1), None);
+        assert_eq!(record.records.len(), 2);
+        assert!(record.records[1].operations.is_empty());
+        assert!(record.records[1].subroutines.is_empty());
+        assert_eq!(font_set.character_strings[0].len(), 2);
+        assert!(Cursor::new(vec![]).give(&font_set).is_err());
+        assert!(Report::new(&font_set)
+            .findings
+            .iter()
+            .any(|finding| finding.location == Location::DictionaryEncoding(0)));
+
+        if let Record::CharacterIDKeyed(ref mut record) = font_set.records[0] {
+            record.encoding = Encoding::new(&[0, 0]);
+        }
+        let other = recover(&font_set);
+        assert_eq!(other.character_strings[0].len(), 2);
+    }
+
+    #[test]
+    fn complete() {
+        let data = ok!(std::fs::read(Fixture::SourceSerifPro.path()));
+        let start = Fixture::SourceSerifPro.offset() as usize;
+        let recovered = ok!(Recovered::new(&data[start..]));
+        assert!(recovered.is_complete());
+        assert_eq!(recovered.font_set.character_strings[0].len(), 547);
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let character_set = integer(&set, Operator::CharSet);
+        let private = match ok!(set.operations[0].get(Operator::Private)) {
+            [_, Number::Integer(offset)] => *offset as usize,
+            _ => unreachable!(),
+        };
+        let data = ok!(std::fs::read(Fixture::SourceSerifPro.path()));
+        let start = Fixture::SourceSerifPro.offset() as usize;
+        let mut data = data[start..].to_vec();
+        data[character_set] = 7;
+        data[private] = 0xff;
+        assert!(Cursor::new(&data).take::<FontSet>().is_err());
+
+        let Recovered {
+            mut font_set,
+            omissions,
+        } = ok!(Recovered::new(&data));
+        assert_eq!(omissions[0].location, Location::CharacterSet(0));
+        assert_eq!(omissions[0].offset, character_set as u64);
+        assert_eq!(omissions[1].location, Location::Private(0, 0));
+        assert_eq!(omissions[1].offset, private as u64);
+        assert!(omissions[2..]
+            .iter()
+            .all(|omission| matches!(omission.location, Location::Glyph(0, _))));
+        assert_eq!(font_set.character_strings[0].len(), 547);
+        assert!(matches!(font_set.character_sets[0], CharacterSet::Missing));
+        assert_eq!(font_set.character_sets[0].get_string_id(42), None);
+        let record = match &font_set.records[0] {
+            Record::CharacterNameKeyed(ref record) => record,
+            _ => unreachable!(),
+        };
+        assert!(record.operations.is_empty());
+        assert!(record.subroutines.is_empty());
+        let decoded = font_set.character_strings[0]
+            .iter()
+            .filter(|code| {
+                let mut program = Program::new(code, &font_set.subroutines, &record.subroutines);
+                loop {
+                    match program.next() {
+                        Ok(Some(_)) => {}
+                        Ok(None) => break true,
+                        Err(_) => break false,
+                    }
+                }
+            })
+            .count();
+        assert!(decoded > 0);
+        assert!(Cursor::new(vec![]).give(&font_set).is_err());
+
+        font_set.character_sets[0] = CharacterSet::new(&[0; 547]);
+        let other = recover(&font_set);
+        assert_eq!(other.character_strings[0].len(), 547);
+    }
+
+    fn integer(set: &FontSet, operator: Operator) -> usize {
+        match ok!(set.operations[0].get_single(operator)) {
+            Number::Integer(value) => value as usize,
+            _ => unreachable!(),
+        }
+    }
+
+    fn recover(set: &FontSet) -> FontSet {
+        let mut tape = Cursor::new(vec![]);
+        ok!(tape.give(set));
+        ok!(Recovered::new(&tape.into_inner())).font_set
+    }
+}
+
+mod sanitizer {
+    use std::fs::File;
+    use std::io::{Read as _, Seek, SeekFrom};
+
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::sanitizer::{Limits, Sanitized};
+    use postscript::tape::Write;
+
+    use crate::support::{code, interpret, setup_builder, setup_font_set, Fixture};
+
+    #[test]
+    fn character_name_keyed() {
+        let glyphs = [".notdef", "A", "B", "C"].map(|name| (name, "endchar"));
+        let mut set = ok!(setup_builder(&glyphs).build());
+        for (glyph_id, text) in [
+            (1, "0 0 rmoveto -107 callsubr endchar"),
+            (2, "0 0 rmoveto 10 rlineto endchar"),
+            (3, "0 0 rmoveto -100 callsubr endchar"),
+        ] {
+            set.character_strings[0].replace(glyph_id, code(text));
+        }
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            record.subroutines = Subroutines(
+                vec![code("10 10 rlineto return"), code("20 20 rlineto return")].into(),
+            );
+        }
+        let mut data = vec![];
+        ok!(data.give(&set));
+        data.extend_from_slice(&[0xde, 0xad, 0xbe, 0xef]);
+        let sanitized = ok!(Sanitized::new(&data));
+        let glyph_ids = sanitized
+            .replacements
+            .iter()
+            .map(|replacement| (replacement.font, replacement.glyph_id))
+            .collect::<Vec<_>>();
+        assert_eq!(glyph_ids, [(0, 2), (0, 3)]);
+        let other = &sanitized.font_set;
+        assert_eq!(other.character_strings[0][2], [0x0e]);
+        assert_eq!(other.character_strings[0][3], [0x0e]);
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => assert_eq!(record.subroutines.len(), 1),
+            _ => unreachable!(),
+        }
+        let mut again = vec![];
+        ok!(again.give(other));
+        assert_eq!(again, sanitized.data);
+
+        let limits = Limits {
+            glyph_count: 3,
+            ..Default::default()
+        };
+        assert!(Sanitized::with_limits(&data, limits).is_err());
+        let limits = Limits {
+            subroutine_count: 1,
+            ..Default::default()
+        };
+        assert!(Sanitized::with_limits(&data, limits).is_err());
+        assert!(Sanitized::new(&data[..data.len() / 2]).is_err());
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut file = ok!(File::open(Fixture::SourceSerifPro.path()));
+        ok!(file.seek(SeekFrom::Start(Fixture::SourceSerifPro.offset())));
+        let mut data = vec![];
+        ok!(file.read_to_end(&mut data));
+        let sanitized = ok!(Sanitized::new(&data));
+        assert!(sanitized.replacements.is_empty());
+        for glyph_id in 0..547 {
+            assert_eq!(
+                interpret(&set, glyph_id),
+                interpret(&sanitized.font_set, glyph_id),
+            );
+        }
+    }
+}
+
+mod scale {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{FontSet, GlyphID, Number, Operator};
+    use postscript::type2::{generalize, Operand};
+
+    use crate::support::{code, interpret, setup_builder, setup_font_set, write, Fixture};
+
+    #[test]
+    fn character_name_keyed() {
+        let mut set = ok!(setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "endchar"),
+            ("Aacute", "0 100 200 65 194 endchar"),
+        ])
+        .build());
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            let code = code("10 20 30 40 50 60 70 80 90 100 110 120 50 return");
+            record.subroutines = Subroutines(vec![code].into());
+        }
+        set.character_strings[0].replace(1, code("100 0 0 rmoveto -107 callsubr flex endchar"));
+        ok!(set.rescale(2000));
+        let mut set = write(&set);
+        ok!(set.desubroutinize());
+        assert_eq!(
+            set.character_strings[0][1],
+            code("200 0 0 rmoveto 20 40 60 80 100 120 140 160 180 200 220 240 50 flex endchar"),
+        );
+        assert_eq!(
+            set.character_strings[0][2],
+            code("0 200 400 65 194 endchar")
+        );
+    }
+
+    #[test]
+    fn rounding() {
+        let mut set = ok!(setup_builder(&[(".notdef", "endchar"), ("A", "endchar"),]).build());
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            let code = code("1 1 1 1 rlineto return");
+            record.subroutines = Subroutines(vec![code].into());
+        }
+        let code =
+            code("100 0 rmoveto -107 callsubr -107 callsubr 1 1 rlineto -5 -5 rlineto endchar");
+        set.character_strings[0].replace(1, code);
+        ok!(set.rescale(1500));
+        let set = write(&set);
+        let (operations, _) = interpret(&set, 1);
+        let mut position = (0.0, 0.0);
+        for (_, operands) in ok!(generalize(&operations[1..])) {
+            position = (position.0 + operands[0], position.1 + operands[1]);
+        }
+        assert_eq!(position, (0.0, 0.0));
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.rescale(2048));
+        let other = write(&other);
+        let matrix = ok!(other.operations[0].get(Operator::FontMatrix));
+        assert_eq!(f32::from(matrix[0]), 1.0 / 2048.0);
+        assert_eq!(f32::from(matrix[3]), 1.0 / 2048.0);
+        let factor = 2.048;
+        let one = ok!(set.operations[0].get(Operator::FontBBox));
+        let other_box = ok!(other.operations[0].get(Operator::FontBBox));
+        for (&one, &other) in one.iter().zip(other_box) {
+            assert!((f32::from(one) * factor - f32::from(other)).abs() <= 0.5);
+        }
+        match (&set.records[0], &other.records[0]) {
+            (Record::CharacterNameKeyed(ref one), Record::CharacterNameKeyed(ref other)) => {
+                let one = ok!(one.operations.get(Operator::BlueValues));
+                let other = ok!(other.operations.get(Operator::BlueValues));
+                let (mut one_value, mut other_value) = (0.0, 0.0);
+                for (&one, &other) in one.iter().zip(other) {
+                    one_value += f32::from(one);
+                    other_value += f32::from(other);
+                    assert!((one_value * factor - other_value).abs() <= 0.5);
+                    assert!(matches!(other, Number::Integer(_)));
+                }
+            }
+            _ => unreachable!(),
+        }
+        for glyph_id in 0..547 {
+            compare(&set, &other, glyph_id, factor);
+        }
+    }
+
+    fn compare(one: &FontSet, other: &FontSet, glyph_id: GlyphID, factor: Operand) {
+        use postscript::type2::Operator::*;
+
+        let close = |one: Operand, other: Operand| (one * factor - other).abs() <= 0.5;
+        let (one_operations, one_width) = interpret(one, glyph_id);
+        let (other_operations, other_width) = interpret(other, glyph_id);
+        let one_operations = ok!(generalize(&one_operations));
+        let other_operations = ok!(generalize(&other_operations));
+        assert_eq!(one_operations.len(), other_operations.len());
+        let mut one_position = [0.0; 2];
+        let mut other_position = [0.0; 2];
+        for ((one_operator, one), (other_operator, other)) in
+            one_operations.iter().zip(other_operations.iter())
+        {
+            assert_eq!(one_operator, other_operator);
+            assert_eq!(one.len(), other.len());
+            let path = matches!(one_operator, RMoveTo | RLineTo | RRCurveTo);
+            let (mut one_value, mut other_value) = (0.0, 0.0);
+            for (i, (&one, &other)) in one.iter().zip(other.iter()).enumerate() {
+                let (one_value, other_value) = match path {
+                    true => (&mut one_position[i % 2], &mut other_position[i % 2]),
+                    _ => (&mut one_value, &mut other_value),
+                };
+                *one_value += one;
+                *other_value += other;
+                assert!(close(*one_value, *other_value));
+            }
+        }
+        match (one_width, other_width) {
+            (Some(one), Some(other)) => assert!(close(one, other)),
+            (None, None) => {}
+            _ => unreachable!(),
+        }
+    }
+}
+
+mod source_serif {
+    use postscript::compact1::font_set::Record;
+
+    use crate::support::{setup_font_set, Fixture};
+
+    #[test]
+    fn header() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let table = &set.header;
+        assert_eq!(table.major, 1);
+        assert_eq!(table.minor, 0);
+        assert_eq!(table.header_size, 4);
+        assert_eq!(table.offset_size, 2);
+    }
+
+    #[test]
+    fn names() {
         let set = setup_font_set(Fixture::SourceSerifPro);
         let table: Vec<_> = ok!(set.names.try_into());
         assert_eq!(table.len(), 1);
@@ -214,6 +1589,28 @@ mod source_serif {
         assert_eq!(table.len(), 181);
     }
 
+    #[test]
+    fn analysis() {
+        use postscript::compact1::analysis::{Analysis, Caller, Subroutine};
+        use postscript::type2::Operator;
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let analysis = ok!(Analysis::new(&set));
+        assert_eq!(analysis.subroutines.len(), 181 + 180);
+        let usage = &analysis.subroutines[&Subroutine::Local(0, 0, 78)];
+        assert_eq!(usage.callers.len(), 6);
+        assert_eq!(usage.callers[0], Caller::Glyph(0, 134));
+        assert_eq!(
+            usage.callers[5],
+            Caller::Subroutine(Subroutine::Local(0, 0, 86)),
+        );
+        assert_eq!(usage.depth, Some(2));
+        assert_eq!(usage.size, 9);
+        assert_eq!(analysis.unused(), [Subroutine::Local(0, 0, 53)]);
+        assert_eq!(analysis.operators[&Operator::EndChar], 357);
+        assert_eq!(analysis.operators[&Operator::CallGSubr], 695);
+    }
+
     #[test]
     fn character_strings() {
         let set = setup_font_set(Fixture::SourceSerifPro);
@@ -235,6 +1632,169 @@ mod source_serif {
         }
     }
 
+    #[test]
+    fn desubroutinize() {
+        use postscript::type2::{tokenize, Operator, Program, Token};
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.desubroutinize());
+        assert_eq!(other.subroutines.len(), 0);
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert_eq!(record.subroutines.len(), 0);
+                let operator = postscript::compact1::Operator::Subrs;
+                assert!(!record.operations.contains_key(&operator));
+            }
+            _ => unreachable!(),
+        }
+        let local = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        for (one, other) in set.character_strings[0]
+            .iter()
+            .zip(other.character_strings[0].iter())
+        {
+            let tokens = ok!(tokenize(other, &[], &[]));
+            assert!(tokens.iter().all(|token| !matches!(
+                token,
+                Token::Operator(Operator::CallSubr | Operator::CallGSubr | Operator::Return),
+            )));
+            let mut one = Program::new(one, &set.subroutines, local);
+            let mut other = Program::new(other, &[], &[]);
+            loop {
+                let operation = ok!(one.next());
+                assert_eq!(operation, ok!(other.next()));
+                if operation.is_none() {
+                    break;
+                }
+            }
+            assert_eq!(one.width(), other.width());
+        }
+    }
+
+    #[test]
+    fn subroutinize() {
+        use postscript::type2::Program;
+
+        macro_rules! size(($set:expr) => ({
+            let local = match &$set.records[0] {
+                Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+                _ => unreachable!(),
+            };
+            $set.character_strings[0]
+                .iter()
+                .chain($set.subroutines.iter())
+                .chain(local.iter())
+                .map(|code| code.len())
+                .sum::<usize>()
+        }));
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut flat = set.clone();
+        ok!(flat.desubroutinize());
+        let mut other = set.clone();
+        ok!(other.subroutinize());
+        assert!(size!(other) < size!(flat) * 3 / 4);
+        let local = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        let other_local = match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        for (one, other_code) in set.character_strings[0]
+            .iter()
+            .zip(other.character_strings[0].iter())
+        {
+            let mut one = Program::new(one, &set.subroutines, local);
+            let mut other = Program::new(other_code, &other.subroutines, other_local);
+            loop {
+                let operation = ok!(one.next());
+                assert_eq!(operation, ok!(other.next()));
+                if operation.is_none() {
+                    break;
+                }
+            }
+            assert_eq!(one.width(), other.width());
+        }
+    }
+
+    #[test]
+    fn replace() {
+        use postscript::compact1::validator::Report;
+
+        use crate::support::write;
+
+        let mut set = setup_font_set(Fixture::SourceSerifPro);
+        let count = set.character_strings[0].len();
+        for glyph_id in 1..count {
+            set.character_strings[0].replace(glyph_id, vec![0x0e]);
+        }
+        let report = Report::new(&set);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+        let other = write(&set);
+        assert_eq!(other.character_strings[0].len(), count);
+        assert_eq!(
+            other.character_strings[0].data,
+            set.character_strings[0].data
+        );
+        assert_eq!(other.character_strings[0].offsets.len(), count + 1);
+    }
+
+    #[test]
+    fn write() {
+        use std::io::Cursor;
+
+        use postscript::compact1::{Encoding, FontSet, Number, Operator};
+        use postscript::tape::{Read, Write};
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut tape = Cursor::new(vec![]);
+        ok!(tape.give(&set));
+        let size = tape.get_ref().len();
+        tape.set_position(0);
+        let other = ok!(tape.take::<FontSet>());
+        assert_eq!(other.header.offset_size, 2);
+        assert_eq!(other.strings.len(), 322);
+        assert_eq!(other.subroutines.len(), 181);
+        assert_eq!(
+            other.character_strings[0].data,
+            set.character_strings[0].data
+        );
+        assert!(matches!(other.encodings[0], Encoding::Standard));
+        let operations = &other.operations[0];
+        assert_eq!(
+            operations.get(Operator::FontBBox),
+            set.operations[0].get(Operator::FontBBox),
+        );
+        let (private_size, private_offset) = match operations.get(Operator::Private) {
+            Some(&[Number::Integer(size), Number::Integer(offset)]) => (size, offset),
+            _ => unreachable!(),
+        };
+        match (&set.records[0], &other.records[0]) {
+            (Record::CharacterNameKeyed(ref one), Record::CharacterNameKeyed(ref other)) => {
+                assert_eq!(other.subroutines.data, one.subroutines.data);
+                assert_eq!(
+                    other.operations.get(Operator::Subrs),
+                    Some(&[Number::Integer(private_size)][..]),
+                );
+                let mut subroutines = vec![];
+                ok!(subroutines.give(&other.subroutines));
+                assert_eq!(
+                    private_offset as usize + private_size as usize + subroutines.len(),
+                    size,
+                );
+            }
+            _ => unreachable!(),
+        }
+        let mut again = Cursor::new(vec![]);
+        ok!(again.give(&other));
+        assert_eq!(again.into_inner(), tape.into_inner());
+    }
+
     #[test]
     fn encodings() {
         use postscript::compact1::Encoding;
@@ -254,8 +1814,6 @@ mod source_serif {
 
     #[test]
     fn records() {
-        use postscript::compact1::font_set::Record;
-
         let set = setup_font_set(Fixture::SourceSerifPro);
         let tables = &set.records;
         assert_eq!(tables.len(), 1);
@@ -283,3 +1841,615 @@ mod source_serif {
         }
     }
 }
+
+mod subset {
+    use postscript::compact1::editor::Editor;
+    use postscript::compact1::font_set::{Builder, Record};
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{Encoding, FontSet, GlyphID, Number, Operator};
+
+    use crate::support::{code, interpret, setup_builder, setup_font_set, write, Fixture};
+
+    #[test]
+    fn character_id_keyed() {
+        let mut builder =
+            Builder::new_character_id_keyed("Test", "Adobe", "Identity", Number::Integer(0));
+        for (dictionary_id, name) in ["Test-Zero", "Test-One", "Test-Two"].iter().enumerate() {
+            if dictionary_id > 0 {
+                ok!(builder.add_dictionary());
+            }
+            ok!(builder.set_dictionary_string(dictionary_id as u8, Operator::FontName, name));
+        }
+        for (name, dictionary_id) in [(".notdef", 0), ("cid00001", 1), ("cid00002", 2)] {
+            let glyph_id = ok!(builder.add_glyph(name, vec![0x0e]));
+            ok!(builder.set_dictionary(glyph_id, dictionary_id));
+        }
+        let mut set = ok!(builder.build());
+        set.character_strings[0].replace(1, code("0 0 rmoveto -107 callsubr endchar"));
+        set.character_strings[0]
+            .replace(2, code("0 0 rmoveto -106 callsubr -107 callgsubr endchar"));
+        set.subroutines = Subroutines(vec![code("40 40 rlineto return")].into());
+        if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+            record.records[1].subroutines = Subroutines(vec![code("10 10 rlineto return")].into());
+            record.records[2].subroutines = Subroutines(
+                vec![code("20 20 rlineto return"), code("30 30 rlineto return")].into(),
+            );
+        }
+        assert!(set.subset(1, &[2], true).is_err());
+        assert!(set.subset(0, &[3], true).is_err());
+
+        let other = write(&ok!(set.subset(0, &[2], true)));
+        assert_eq!(other.character_strings[0].len(), 2);
+        assert_eq!(other.character_sets[0].get_string_id(1), Some(2));
+        assert_eq!(other.subroutines.len(), 1);
+        match &other.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.records.len(), 2);
+                assert_eq!(record.records[1].subroutines.len(), 1);
+                assert_eq!(record.encoding.get(0), Some(0));
+                assert_eq!(record.encoding.get(1), Some(1));
+                let string_id = ok!(record.operations[1].get_single(Operator::FontName));
+                assert_eq!(
+                    ok!(other.strings.get(string_id.try_into().unwrap())),
+                    "Test-Two",
+                );
+                assert_eq!(ok!(other.strings.get(record.registry)), "Adobe");
+                assert_eq!(ok!(other.strings.get(record.ordering)), "Identity");
+            }
+            _ => unreachable!(),
+        }
+        compare(&set, &other, &[0, 2]);
+
+        let other = write(&ok!(set.subset(0, &[1], false)));
+        assert_eq!(other.character_strings[0].len(), 3);
+        assert_eq!(other.character_strings[0][2], [0x0e]);
+        assert_eq!(other.subroutines.len(), 0);
+        match &other.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(record.records.len(), 2);
+                assert_eq!(record.encoding.get(1), Some(1));
+                assert_eq!(record.encoding.get(2), Some(0));
+            }
+            _ => unreachable!(),
+        }
+        compare(&set, &other, &[0, 1]);
+    }
+
+    #[test]
+    fn character_name_keyed() {
+        let set = ok!(setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "0 0 rmoveto 100 100 rlineto endchar"),
+            ("B", "0 0 rmoveto 200 200 rlineto endchar"),
+            ("ring", "0 0 rmoveto 300 300 rlineto endchar"),
+            ("Aring", "0 0 0 65 202 endchar"),
+        ])
+        .build());
+        let mut other = write(&ok!(set.subset(0, &[4], true)));
+        assert_eq!(other.strings.len(), 0);
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_count(), 4);
+        assert_eq!(editor.glyph_id("A"), Some(1));
+        assert_eq!(editor.glyph_id("ring"), Some(2));
+        assert_eq!(editor.glyph_id("Aring"), Some(3));
+        assert_eq!(editor.glyph_id("B"), None);
+        compare(&set, &other, &[0, 1, 3, 4]);
+    }
+
+    #[test]
+    fn encoding_supplemental() {
+        use postscript::compact1::encoding::{EncodingSupplemental, Supplement};
+
+        let mut set = ok!(setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "10 endchar"),
+            ("B", "20 endchar"),
+        ])
+        .build());
+        let string_id = ok!(set.character_sets[0].get_string_id(1));
+        set.encodings[0] = Encoding::FormatSupplemental(EncodingSupplemental {
+            format: 0x80,
+            count: 2,
+            codes: vec![0x41, 0x42],
+            ranges: vec![],
+            supplement_count: 1,
+            supplements: vec![Supplement {
+                code: 0x61,
+                glyph: string_id,
+            }],
+        });
+        let other = write(&ok!(set.subset(0, &[1], true)));
+        match &other.encodings[0] {
+            Encoding::FormatSupplemental(ref encoding) => {
+                assert_eq!(encoding.codes, [0x41]);
+                assert_eq!(encoding.supplements.len(), 1);
+                assert_eq!(encoding.supplements[0].glyph, string_id);
+            }
+            _ => unreachable!(),
+        }
+        let other = write(&ok!(set.subset(0, &[2], true)));
+        match &other.encodings[0] {
+            Encoding::Format0(ref encoding) => assert_eq!(encoding.codes, [0x42]),
+            _ => unreachable!(),
+        }
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut copy = set.clone();
+        let editor = ok!(Editor::new(&mut copy, 0));
+        let glyph_ids = ["A", "Aring", "a", "o"]
+            .iter()
+            .map(|name| ok!(editor.glyph_id(name)))
+            .collect::<Vec<_>>();
+
+        let mut other = write(&ok!(set.subset(0, &glyph_ids, true)));
+        assert_eq!(other.character_strings[0].len(), 5);
+        assert!(other.subroutines.len() < set.subroutines.len());
+        assert!(matches!(other.encodings[0], Encoding::Standard));
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert!(record.subroutines.len() < 180);
+            }
+            _ => unreachable!(),
+        }
+        let mut all = vec![0];
+        all.extend_from_slice(&glyph_ids);
+        all.sort();
+        compare(&set, &other, &all);
+        let editor = ok!(Editor::new(&mut other, 0));
+        assert_eq!(editor.glyph_name(1).as_deref(), Some("A"));
+        assert!(editor.glyph_id("Aring").is_some());
+
+        let other = write(&ok!(set.subset(0, &glyph_ids, false)));
+        assert_eq!(other.character_strings[0].len(), 547);
+        for glyph_id in [1, 100, 546] {
+            let name = |set: &FontSet| {
+                set.strings
+                    .get(ok!(set.character_sets[0].get_string_id(glyph_id)))
+            };
+            assert_eq!(name(&other), name(&set));
+        }
+        for (glyph_id, code) in other.character_strings[0].iter().enumerate() {
+            if !all.contains(&(glyph_id as GlyphID)) {
+                assert_eq!(code, &[0x0e]);
+            }
+        }
+        compare(&set, &other, &all);
+    }
+
+    fn compare(one: &FontSet, other: &FontSet, glyph_ids: &[GlyphID]) {
+        let renumber = other.character_strings[0].len() == glyph_ids.len();
+        for (i, &glyph_id) in glyph_ids.iter().enumerate() {
+            let j = if renumber { i as GlyphID } else { glyph_id };
+            assert_eq!(interpret(one, glyph_id), interpret(other, j));
+        }
+    }
+}
+
+mod synthesize {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{FontSet, GlyphID, Number, Operator};
+    use postscript::type2::{assemble, generalize, Operand};
+
+    use crate::support::{code, interpret, setup_builder, setup_font_set, write, Fixture};
+
+    #[test]
+    fn embolden() {
+        let mut set = setup();
+        ok!(set.embolden(20.0));
+        let set = write(&set);
+        assert!(set.subroutines.is_empty());
+        assert_eq!(
+            ok!(set.operations[0].get(Operator::FontBBox)),
+            &[0, 0, 120, 120].map(Number::Integer),
+        );
+        match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert!(record.subroutines.is_empty());
+                assert!(record.operations.get(Operator::BlueValues).is_none());
+                assert!(record.operations.get(Operator::StdVW).is_none());
+                assert_eq!(
+                    record.operations.get_single(Operator::DefaultWidthX),
+                    Some(Number::Integer(20)),
+                );
+                assert_eq!(
+                    record.operations.get_single(Operator::NominalWidthX),
+                    Some(Number::Integer(20)),
+                );
+            }
+            _ => unreachable!(),
+        }
+        let code = ok!(assemble(
+            "500 0 hmoveto 120 120 -120 hlineto endchar",
+            &[],
+            &[]
+        ));
+        assert_eq!(set.character_strings[0][1], code);
+        let code = ok!(assemble("500 0 100 65 194 endchar", &[], &[]));
+        assert_eq!(set.character_strings[0][2], code);
+    }
+
+    #[test]
+    fn slant() {
+        let angle = 0.2f32.atan().to_degrees();
+        let mut set = setup();
+        ok!(set.slant(angle));
+        let set = write(&set);
+        assert_eq!(
+            ok!(set.operations[0].get(Operator::FontBBox)),
+            &[0, 0, 120, 100].map(Number::Integer),
+        );
+        let italic_angle = ok!(set.operations[0].get_single(Operator::ItalicAngle));
+        assert!((f32::from(italic_angle) + angle).abs() < 1e-3);
+        let code = ok!(assemble(
+            "500 0 hmoveto 100 hlineto 20 100 rlineto -100 hlineto endchar",
+            &[],
+            &[]
+        ));
+        assert_eq!(set.character_strings[0][1], code);
+        let code = ok!(assemble("500 20 100 65 194 endchar", &[], &[]));
+        assert_eq!(set.character_strings[0][2], code);
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.embolden(10.0));
+        let other = write(&other);
+        for glyph_id in 0..547 {
+            assert_eq!(width(&set, glyph_id) + 10.0, width(&other, glyph_id));
+            check(&other, glyph_id);
+        }
+        let mut other = set.clone();
+        ok!(other.slant(12.0));
+        let other = write(&other);
+        let italic_angle = ok!(other.operations[0].get_single(Operator::ItalicAngle));
+        assert_eq!(italic_angle, Number::Integer(-12));
+        for glyph_id in 0..547 {
+            assert_eq!(width(&set, glyph_id), width(&other, glyph_id));
+            check(&other, glyph_id);
+        }
+    }
+
+    fn check(set: &FontSet, glyph_id: GlyphID) {
+        let bbox = ok!(set.operations[0].get(Operator::FontBBox))
+            .iter()
+            .map(|&value| f32::from(value))
+            .collect::<Vec<_>>();
+        let (operations, _) = interpret(set, glyph_id);
+        let mut position = (0.0, 0.0);
+        for (_, operands) in ok!(generalize(&operations)) {
+            for chunk in operands.chunks_exact(2) {
+                position = (position.0 + chunk[0], position.1 + chunk[1]);
+            }
+            assert!(bbox[0] <= position.0 && position.0 <= bbox[2]);
+            assert!(bbox[1] <= position.1 && position.1 <= bbox[3]);
+        }
+    }
+
+    fn width(set: &FontSet, glyph_id: GlyphID) -> Operand {
+        let operations = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &record.operations,
+            _ => unreachable!(),
+        };
+        let value = |operator: Operator| {
+            operations
+                .get_single(operator)
+                .map(Operand::from)
+                .unwrap_or(0.0)
+        };
+        match interpret(set, glyph_id).1 {
+            Some(difference) => value(Operator::NominalWidthX) + difference,
+            _ => value(Operator::DefaultWidthX),
+        }
+    }
+
+    fn setup() -> FontSet {
+        let mut builder = setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "endchar"),
+            ("Aacute", "500 0 100 65 194 endchar"),
+        ]);
+        ok!(builder.set_operation(
+            Operator::FontBBox,
+            vec![0.into(), 0.into(), 100.into(), 100.into()]
+        ));
+        ok!(builder.set_private_operation(0, Operator::BlueValues, vec![(-10).into(), 0.into()]));
+        ok!(builder.set_private_operation(0, Operator::StdVW, vec![50.into()]));
+        let mut set = ok!(builder.build());
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            let code = code("100 0 rlineto 0 100 rlineto -100 0 rlineto return");
+            record.subroutines = Subroutines(vec![code].into());
+        }
+        let code = code("500 0 100 hstem 0 0 rmoveto -107 callsubr endchar");
+        set.character_strings[0].replace(1, code);
+        set
+    }
+}
+
+mod ttx {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::ttx::{export, import};
+
+    use crate::support::{setup_font_set, write, Fixture};
+
+    #[test]
+    fn character_id_keyed() {
+        let text = r#"<?xml version="1.0" encoding="UTF-8"?>
+<ttFont sfntVersion="OTTO">
+  <CFF>
+    <major value="1"/>
+    <minor value="0"/>
+    <CFFFont name="Test">
+      <ROS Registry="Adobe" Order="Identity" Supplement="0"/>
+      <FontBBox value="0 0 1000 1000"/>
+      <CIDCount value="6"/>
+      <FDSelect format="3"/>
+      <FDArray>
+        <FontDict index="0">
+          <FontName value="Test-One"/>
+          <Private>
+            <BlueValues value="-10 0 500 510"/>
+            <nominalWidthX value="500"/>
+            <Subrs>
+              <CharString index="0">
+                100 100 rlineto
+                return
+              </CharString>
+            </Subrs>
+          </Private>
+        </FontDict>
+        <FontDict index="1">
+          <FontName value="Test-Two"/>
+          <Private>
+          </Private>
+        </FontDict>
+      </FDArray>
+      <CharStrings>
+        <CharString name=".notdef" fdSelectIndex="0">
+          endchar
+        </CharString>
+        <CharString name="cid00001" fdSelectIndex="0">
+          0 0 rmoveto
+          -107 callsubr
+          endchar
+        </CharString>
+        <CharString name="cid00005" fdSelectIndex="1">
+          10 20 rmoveto
+          endchar
+        </CharString>
+      </CharStrings>
+    </CFFFont>
+    <GlobalSubrs>
+    </GlobalSubrs>
+  </CFF>
+</ttFont>
+"#;
+        let set = ok!(import(text));
+        assert_eq!(set.character_strings[0].len(), 3);
+        assert_eq!(set.character_sets[0].get_string_id(2), Some(5));
+        assert_eq!(
+            set.character_strings[0][1],
+            [0x8b, 0x8b, 0x15, 0x20, 0x0a, 0x0e],
+        );
+        match &set.records[0] {
+            Record::CharacterIDKeyed(ref record) => {
+                assert_eq!(ok!(set.strings.get(record.registry)), "Adobe");
+                assert_eq!(ok!(set.strings.get(record.ordering)), "Identity");
+                assert_eq!(record.encoding.get(0), Some(0));
+                assert_eq!(record.encoding.get(2), Some(1));
+                assert_eq!(record.encoding.get(3), None);
+                assert_eq!(record.records.len(), 2);
+                assert_eq!(record.records[0].subroutines.len(), 1);
+                let operations = operations!(
+                    BlueValues: [-10, 10, 500, 10],
+                    NominalWidthX: [500],
+                );
+                assert_eq!(record.records[0].operations.0, operations.0);
+            }
+            _ => unreachable!(),
+        }
+        let text = ok!(export(&set));
+        assert!(text.contains("<BlueValues value=\"-10 0 500 510\"/>"));
+        assert!(text.contains("<CharString name=\"cid00005\" fdSelectIndex=\"1\">"));
+        assert_eq!(ok!(export(&ok!(import(&text)))), text);
+        assert_eq!(ok!(export(&write(&set))), text);
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let text = ok!(export(&set));
+        assert!(text.contains("<FullName value=\"Source Serif Pro\"/>"));
+        assert!(text.contains("<StemSnapH value=\"41 56\"/>"));
+        assert!(text.contains("<Encoding name=\"StandardEncoding\"/>"));
+        assert!(text.contains("<CharString name=\"Aring\">"));
+        let other = ok!(import(&text));
+        assert_eq!(other.character_strings[0].len(), 547);
+        for (one, other) in set.character_strings[0]
+            .iter()
+            .zip(other.character_strings[0].iter())
+        {
+            assert_eq!(one, other);
+        }
+        assert_eq!(ok!(export(&other)), text);
+        assert_eq!(ok!(export(&write(&other))), text);
+    }
+}
+
+mod validator {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::validator::{Report, Severity};
+    use postscript::compact1::{CharacterSet, Location, Number, Operator};
+
+    use crate::support::{code, setup_builder, setup_font_set, Fixture};
+
+    #[test]
+    fn character_name_keyed() {
+        let mut builder = setup_builder(&[
+            (".notdef", "endchar"),
+            ("A", "0 10 hstem endchar"),
+            ("B", "endchar"),
+        ]);
+        let values = [-10, 0, 500, 510].map(Number::Integer).to_vec();
+        ok!(builder.set_private_operation(0, Operator::BlueValues, values));
+        let set = ok!(builder.build());
+        let report = Report::new(&set);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+        assert!(report.is_valid());
+
+        let mut set = set;
+        set.character_strings[0].0.offsets.swap(1, 2);
+        set.character_strings[0].0.data[1] = code("0 10 hstem hintmask 01000000 endchar");
+        set.character_strings[0].0.data[2] = code("callsubr endchar");
+        set.character_sets[0] = CharacterSet::new(&[0, 34]);
+        set.operations[0].insert(Operator::FontBBox, vec![Number::Integer(0); 3]);
+        set.operations[0].insert(Operator::StdVW, vec![Number::Integer(50)]);
+        match set.records[0] {
+            Record::CharacterNameKeyed(ref mut record) => {
+                record
+                    .operations
+                    .insert(Operator::BlueValues, vec![Number::Integer(10); 15]);
+            }
+            _ => unreachable!(),
+        }
+        let report = Report::new(&set);
+        assert!(!report.is_valid());
+        let findings = report
+            .findings
+            .iter()
+            .map(|finding| (finding.severity, finding.location))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            findings,
+            [
+                (Severity::Error, Location::CharacterStrings(0)),
+                (Severity::Error, Location::CharacterSet(0)),
+                (Severity::Error, Location::Top(0)),
+                (Severity::Warning, Location::Top(0)),
+                (Severity::Error, Location::Private(0, 0)),
+                (Severity::Error, Location::Private(0, 0)),
+                (Severity::Error, Location::Glyph(0, 1)),
+                (Severity::Error, Location::Glyph(0, 2)),
+            ],
+        );
+        assert_eq!(
+            report.findings[0].message,
+            "found an index with non-monotonic offsets (1)",
+        );
+        assert_eq!(
+            report.findings[6].message,
+            "found a mask inconsistent with the stems (HintMask)",
+        );
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let report = Report::new(&set);
+        assert!(report.findings.is_empty(), "{:?}", report.findings);
+    }
+}
+
+mod width {
+    use postscript::compact1::font_set::Record;
+    use postscript::compact1::index::Subroutines;
+    use postscript::compact1::{FontSet, GlyphID, Number, Operator};
+    use postscript::type2::Operand;
+
+    use crate::support::{
+        code, interpret, setup_builder, setup_character_name_keyed, setup_font_set, write, Fixture,
+    };
+
+    #[test]
+    fn character_name_keyed() {
+        let set = ok!(setup_builder(&[
+            (".notdef", "500 endchar"),
+            ("A", "600 0 0 rmoveto 10 10 rlineto endchar"),
+            ("B", "600 endchar"),
+            ("C", "700 endchar"),
+        ])
+        .build());
+        let mut other = set.clone();
+        ok!(other.optimize_widths());
+        let other = write(&other);
+        match &other.records[0] {
+            Record::CharacterNameKeyed(ref record) => {
+                assert_eq!(
+                    record.operations.get_single(Operator::DefaultWidthX),
+                    Some(Number::Integer(600)),
+                );
+            }
+            _ => unreachable!(),
+        }
+        assert_eq!(other.character_strings[0][1].len(), 7);
+        assert_eq!(other.character_strings[0][2], [0x0e]);
+        for glyph_id in 0..4 {
+            assert_eq!(width(&set, glyph_id), width(&other, glyph_id));
+        }
+    }
+
+    #[test]
+    fn subroutines() {
+        let mut set = setup_character_name_keyed();
+        if let Record::CharacterNameKeyed(ref mut record) = set.records[0] {
+            record.subroutines = Subroutines(vec![code("-107 20 20 rmoveto return")].into());
+        }
+        set.character_strings[0].replace(2, code("-107 callsubr endchar"));
+        let mut other = set.clone();
+        ok!(other.optimize_widths());
+        let other = write(&other);
+        assert_eq!(other.character_strings[0][2], code("-107 callsubr endchar"));
+        for glyph_id in 0..4 {
+            assert_eq!(width(&set, glyph_id), width(&other, glyph_id));
+        }
+    }
+
+    #[test]
+    fn dot_section() {
+        let mut set = setup_character_name_keyed();
+        set.character_strings[0].replace(0, code("dotsection 0 0 rmoveto endchar"));
+        let mut other = set.clone();
+        ok!(other.optimize_widths());
+        let other = write(&other);
+        assert_ne!(other.character_strings[0][0], set.character_strings[0][0]);
+        assert!(other.character_strings[0][0].starts_with(&code("dotsection")));
+        for glyph_id in 0..4 {
+            assert_eq!(width(&set, glyph_id), width(&other, glyph_id));
+        }
+    }
+
+    #[test]
+    fn source_serif() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let mut other = set.clone();
+        ok!(other.optimize_widths());
+        let other = write(&other);
+        let size = |set: &FontSet| -> usize { set.character_strings[0].iter().map(Vec::len).sum() };
+        assert!(size(&other) <= size(&set));
+        for glyph_id in 0..547 {
+            assert_eq!(width(&set, glyph_id), width(&other, glyph_id));
+        }
+    }
+
+    fn width(set: &FontSet, glyph_id: GlyphID) -> Operand {
+        let operations = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &record.operations,
+            _ => unreachable!(),
+        };
+        let value = |operator: Operator| {
+            operations
+                .get_single(operator)
+                .map(Operand::from)
+                .unwrap_or(0.0)
+        };
+        match interpret(set, glyph_id).1 {
+            Some(difference) => value(Operator::NominalWidthX) + difference,
+            _ => value(Operator::DefaultWidthX),
+        }
+    }
+}
@@ -1,10 +1,14 @@
 #![allow(dead_code)]
 
 use std::fs::File;
-use std::io::{Seek, SeekFrom};
+use std::io::{Cursor, Seek, SeekFrom};
 use std::path::PathBuf;
 
-use postscript::compact1::FontSet;
+use postscript::compact1::font_set::{Builder, Record};
+use postscript::compact1::index::Subroutines;
+use postscript::compact1::{FontSet, GlyphID, Number};
+use postscript::tape::{Read as _, Write as _};
+use postscript::type2::{assemble, Operand, Operation, Program};
 use postscript::value::Read;
 
 macro_rules! ok(($result:expr) => ($result.unwrap()));
@@ -49,3 +53,63 @@ pub fn setup_font_set(fixture: Fixture) -> FontSet {
     assert_eq!(table.operations.len(), count);
     table
 }
+
+pub fn setup_builder(glyphs: &[(&str, &str)]) -> Builder {
+    let mut builder = Builder::new("Test");
+    for (name, text) in glyphs {
+        ok!(builder.add_glyph(name, code(text)));
+    }
+    builder
+}
+
+pub fn setup_character_name_keyed() -> FontSet {
+    ok!(setup_builder(&[
+        (".notdef", "endchar"),
+        ("A", "500 0 0 rmoveto 400 0 rlineto 0 600 rlineto endchar"),
+        ("acute", "200 0 0 rmoveto 100 100 rlineto endchar"),
+        ("Aacute", "500 100 700 65 194 endchar"),
+    ])
+    .build())
+}
+
+pub fn setup_character_id_keyed() -> FontSet {
+    let mut builder =
+        Builder::new_character_id_keyed("Test", "Adobe", "Identity", Number::Integer(0));
+    ok!(builder.add_dictionary());
+    ok!(builder.add_glyph(".notdef", vec![0x0e]));
+    let glyph_id = ok!(builder.add_glyph("cid00001", vec![0x0e]));
+    ok!(builder.set_dictionary(glyph_id, 1));
+    let mut set = ok!(builder.build());
+    // -107 callsubr endchar
+    set.character_strings[0].replace(glyph_id as usize, vec![0x20, 0x0a, 0x0e]);
+    if let Record::CharacterIDKeyed(ref mut record) = set.records[0] {
+        // return
+        record.records[1].subroutines = Subroutines(vec![vec![0x0b]].into());
+    }
+    set
+}
+
+pub fn write(set: &FontSet) -> FontSet {
+    let mut tape = Cursor::new(vec![]);
+    ok!(tape.give(set));
+    tape.set_position(0);
+    ok!(tape.take())
+}
+
+pub fn code(text: &str) -> Vec<u8> {
+    ok!(assemble(text, &[], &[]))
+}
+
+pub fn interpret(set: &FontSet, glyph_id: GlyphID) -> (Vec<Operation>, Option<Operand>) {
+    let local = ok!(set.records[0].subroutines(glyph_id));
+    let mut program = Program::new(
+        &set.character_strings[0][glyph_id as usize],
+        &set.subroutines,
+        local,
+    );
+    let mut operations = vec![];
+    while let Some(operation) = ok!(program.next()) {
+        operations.push(operation);
+    }
+    (operations, program.width())
+}
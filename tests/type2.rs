@@ -44,7 +44,7 @@ mod hirakatana {
 
 mod source_serif {
     use postscript::compact1::font_set::Record;
-    use postscript::type2::Program;
+    use postscript::type2::{Operator, Program};
 
     use crate::support::{setup_font_set, Fixture};
 
@@ -62,6 +62,102 @@ mod source_serif {
         }
     }
 
+    #[test]
+    fn assemble() {
+        use postscript::type2::{assemble, disassemble};
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let global = &set.subroutines;
+        let local = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        for code in set.character_strings[0].iter() {
+            let text = ok!(disassemble(code, global, local));
+            assert_eq!(&ok!(assemble(&text, global, local)), code);
+        }
+        let text = ok!(disassemble(&set.character_strings[0][134], global, local));
+        assert!(text.starts_with(
+            "callsubr#78\n\
+             82 63 20 62 hstemhm\n\
+             45 89 -58 36 212 84 -38 36 hintmask 1010101100000000\n\
+             112 585 rmoveto\n\
+             callgsubr#103\n\
+             hintmask 1011101010000000\n"
+        ));
+    }
+
+    #[test]
+    fn encode() {
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let global = &set.subroutines;
+        let local = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        for code in set.character_strings[0].iter() {
+            let mut program = Program::new(code, global, local).with_masks();
+            let mut operations = vec![];
+            while let Some(operation) = ok!(program.next()) {
+                operations.push(operation);
+            }
+            let width = program.width();
+            operations.push((Operator::EndChar, vec![]));
+            let code = ok!(postscript::type2::encode(&operations, width));
+            let mut program = Program::new(&code, &[], &[]).with_masks();
+            let mut other_operations = vec![];
+            while let Some(operation) = ok!(program.next()) {
+                other_operations.push(operation);
+            }
+            other_operations.push((Operator::EndChar, vec![]));
+            assert_eq!(program.width(), width);
+            assert_eq!(other_operations, operations);
+        }
+    }
+
+    #[test]
+    fn transform() {
+        use postscript::type2::{encode, generalize, specialize};
+
+        let set = setup_font_set(Fixture::SourceSerifPro);
+        let global = &set.subroutines;
+        let local = match &set.records[0] {
+            Record::CharacterNameKeyed(ref record) => &*record.subroutines,
+            _ => unreachable!(),
+        };
+        for code in set.character_strings[0].iter() {
+            let mut program = Program::new(code, global, local).with_masks();
+            let mut operations = vec![];
+            while let Some(operation) = ok!(program.next()) {
+                operations.push(operation);
+            }
+            let general_operations = ok!(generalize(&operations));
+            for (operator, operands) in general_operations.iter() {
+                match operator {
+                    Operator::RMoveTo | Operator::RLineTo => assert_eq!(operands.len(), 2),
+                    Operator::RRCurveTo => assert_eq!(operands.len(), 6),
+                    Operator::HStem | Operator::VStem => {}
+                    Operator::HStemHM | Operator::VStemHM => {}
+                    Operator::HintMask | Operator::CntrMask => {}
+                    operator => unreachable!("{operator:?}"),
+                }
+            }
+            let mut special_operations = ok!(specialize(&operations));
+            assert!(special_operations
+                .iter()
+                .all(|(_, operands)| operands.len() <= 48));
+            assert_eq!(ok!(generalize(&special_operations)), general_operations);
+            special_operations.push((Operator::EndChar, vec![]));
+            let code = ok!(encode(&special_operations, program.width()));
+            let mut program = Program::new(&code, &[], &[]).with_masks();
+            let mut other_operations = vec![];
+            while let Some(operation) = ok!(program.next()) {
+                other_operations.push(operation);
+            }
+            assert_eq!(ok!(generalize(&other_operations)), general_operations);
+        }
+    }
+
     #[test]
     fn one() {
         let set = setup_font_set(Fixture::SourceSerifPro);
@@ -123,3 +219,442 @@ mod source_serif {
         );
     }
 }
+
+mod profile {
+    use postscript::type2::{Diagnostic, Operator, Profile, Program};
+
+    macro_rules! run(
+        ($code:expr, $profile:expr) => ({
+            let code: &[u8] = &$code;
+            let mut program = Program::with_profile(code, &[], &[], $profile);
+            let mut operations = vec![];
+            loop {
+                match program.next() {
+                    Ok(Some(operation)) => operations.push(operation),
+                    Ok(None) => break Ok((operations, program.width(), program.diagnostics().to_vec())),
+                    Err(error) => break Err(error),
+                }
+            }
+        })
+    );
+
+    #[test]
+    fn default() {
+        let code = [0x95, 0x8b, 0x0c, 0x0c, 0x16, 0x0e];
+        let mut program = Program::new(&code, &[], &[]);
+        assert_eq!(ok!(program.next()), Some((Operator::HMoveTo, vec![0.0])));
+        assert_eq!(ok!(program.next()), None);
+        assert_eq!(program.diagnostics(), &[Diagnostic::DivisionByZero]);
+    }
+
+    #[test]
+    fn deprecated_operator() {
+        let code = [0xbd, 0x95, 0x9f, 0x15, 0x0c, 0x00, 0x0e];
+        assert!(run!(code, Profile::Strict).is_err());
+        let (operations, width, diagnostics) = ok!(run!(code, Profile::Compatible));
+        assert_eq!(operations, operations!(RMoveTo: [10, 20],));
+        assert_eq!(width, Some(50.0));
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::DeprecatedOperator(Operator::DotSection)],
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let code = [0x95, 0x8b, 0x0c, 0x0c, 0x16, 0x0e];
+        assert!(run!(code, Profile::Strict).is_err());
+        let (operations, _, diagnostics) = ok!(run!(code, Profile::Compatible));
+        assert_eq!(operations, operations!(HMoveTo: [0],));
+        assert_eq!(diagnostics, vec![Diagnostic::DivisionByZero]);
+    }
+
+    #[test]
+    fn excess_operands() {
+        let code = [0x95, 0x95, 0x95, 0x0e];
+        assert!(run!(code, Profile::Strict).is_err());
+        let (operations, width, diagnostics) = ok!(run!(code, Profile::Compatible));
+        assert!(operations.is_empty());
+        assert_eq!(width, Some(10.0));
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::ExcessOperands(Operator::EndChar, 2)],
+        );
+    }
+
+    #[test]
+    fn masks() {
+        let code = [0xbd, 0x95, 0x9f, 0x12, 0xa9, 0xb3, 0x13, 0xc0, 0x0e];
+        let mut program = Program::new(&code, &[], &[]);
+        let mut operations = vec![];
+        while let Some(operation) = ok!(program.next()) {
+            operations.push(operation);
+        }
+        assert_eq!(operations, operations!(HStemHM: [10, 20], HintMask: [],));
+        let mut program = Program::new(&code, &[], &[]).with_masks();
+        let mut operations = vec![];
+        while let Some(operation) = ok!(program.next()) {
+            operations.push(operation);
+        }
+        assert_eq!(
+            operations,
+            operations!(HStemHM: [10, 20], VStemHM: [30, 40], HintMask: [0xc0],),
+        );
+        assert_eq!(program.width(), Some(50.0));
+    }
+
+    #[test]
+    fn misplaced_width() {
+        let code = [0x95, 0x9f, 0x12, 0xbd, 0x13, 0x80, 0x0e];
+        assert!(run!(code, Profile::Strict).is_err());
+        let (operations, width, diagnostics) = ok!(run!(code, Profile::Compatible));
+        assert_eq!(operations, operations!(HStemHM: [10, 20], HintMask: [],));
+        assert_eq!(width, None);
+        assert_eq!(
+            diagnostics,
+            vec![Diagnostic::MisplacedWidth(Operator::HintMask)],
+        );
+    }
+
+    #[test]
+    fn width() {
+        let code = [0xbd, 0x95, 0x9f, 0x12, 0x13, 0x80, 0x0e];
+        let (operations, width, diagnostics) = ok!(run!(code, Profile::Strict));
+        assert_eq!(operations, operations!(HStemHM: [10, 20], HintMask: [],));
+        assert_eq!(width, Some(50.0));
+        assert!(diagnostics.is_empty());
+    }
+}
+
+mod encoder {
+    use postscript::type2::encode;
+
+    #[test]
+    fn encode_compactly() {
+        let operations = operations!(
+            HStemHM: [10, 20],
+            VStemHM: [30, 40, 1000, -1000],
+            HintMask: [0xe0],
+            RMoveTo: [0.5, 10000],
+            CallSubr: [-107],
+            Flex1: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+            EndChar: [],
+        );
+        assert_eq!(
+            ok!(encode(&operations, Some(-100.0))),
+            vec![
+                0x27, 0x95, 0x9f, 0x12, 0xa9, 0xb3, 0xfa, 0x7c, 0xfe, 0x7c, 0x13, 0xe0, 0xff, 0x00,
+                0x00, 0x80, 0x00, 0x1c, 0x27, 0x10, 0x15, 0x20, 0x0a, 0x8c, 0x8d, 0x8e, 0x8f, 0x90,
+                0x91, 0x92, 0x93, 0x94, 0x95, 0x96, 0x0c, 0x25, 0x0e,
+            ],
+        );
+    }
+
+    #[test]
+    fn reject_malformed_masks() {
+        let operations = operations!(HStemHM: [10, 20], HintMask: [0x80, 0x00],);
+        assert!(encode(&operations, None).is_err());
+    }
+}
+
+mod builder {
+    use postscript::type2::{Builder, Operator, Program};
+
+    #[test]
+    fn curves() {
+        let mut builder = Builder::new();
+        builder.move_to(250.0, 0.0);
+        builder.curve_to(388.0, 0.0, 500.0, 112.0, 500.0, 250.0);
+        builder.curve_to(500.0, 388.0, 388.0, 500.0, 250.0, 500.0);
+        builder.curve_to(112.0, 500.0, 0.0, 388.0, 0.0, 250.0);
+        builder.curve_to(0.0, 112.0, 112.0, 0.0, 250.0, 0.0);
+        builder.close();
+        assert_eq!(
+            ok!(builder.operations()),
+            operations!(
+                HMoveTo: [250],
+                HVCurveTo: [138, 112, 112, 138, 138, -112, 112, -138, -138, -112, -112, -138, -138, 112, -112, 138],
+                EndChar: [],
+            ),
+        );
+    }
+
+    #[test]
+    fn stems() {
+        let mut builder = Builder::new();
+        builder.set_width(-100.0);
+        for i in 0..30 {
+            builder.add_horizontal_stem(100.0 * i as f32, 20.0);
+        }
+        builder.add_vertical_stem(100.0, 20.0);
+        builder.move_to(100.0, 0.0);
+        let operations = ok!(builder.operations());
+        let counts = operations
+            .iter()
+            .map(|(operator, operands)| (*operator, operands.len()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            counts[..3],
+            [
+                (Operator::HStem, 46),
+                (Operator::HStem, 14),
+                (Operator::VStem, 2)
+            ],
+        );
+        let code = ok!(builder.encode());
+        let mut program = Program::new(&code, &[], &[]);
+        let mut stems = vec![];
+        while let Some((operator, operands)) = ok!(program.next()) {
+            if operator == Operator::HStem {
+                let mut last = 0.0;
+                for chunk in operands.chunks(2) {
+                    stems.push((last + chunk[0], chunk[1]));
+                    last += chunk[0] + chunk[1];
+                }
+            }
+        }
+        assert_eq!(program.width(), Some(-100.0));
+        assert_eq!(
+            stems,
+            (0..30)
+                .map(|i| (100.0 * i as f32, 20.0))
+                .collect::<Vec<_>>(),
+        );
+    }
+
+    #[test]
+    fn lines() {
+        let mut builder = Builder::new();
+        builder.set_width(-100.0);
+        builder.add_horizontal_stem(700.0, -20.0);
+        builder.add_horizontal_stem(0.0, 20.0);
+        builder.add_vertical_stem(100.0, 20.0);
+        builder.move_to(100.0, 0.0);
+        builder.line_to(500.0, 0.0);
+        builder.line_to(500.0, 700.0);
+        builder.line_to(100.0, 700.0);
+        builder.line_to(100.0, 0.0);
+        builder.close();
+        builder.move_to(200.0, 100.0);
+        builder.line_to(250.0, 150.0);
+        builder.line_to(300.0, 100.0);
+        builder.close();
+        let operations = ok!(builder.operations());
+        assert_eq!(
+            operations,
+            operations!(
+                HStem: [0, 20, 680, -20],
+                VStem: [100, 20],
+                HMoveTo: [100],
+                HLineTo: [400, 700, -400],
+                RMoveTo: [100, -600],
+                RLineTo: [50, 50, 50, -50],
+                EndChar: [],
+            ),
+        );
+        let code = ok!(builder.encode());
+        let mut program = Program::new(&code, &[], &[]);
+        let mut other_operations = vec![];
+        while let Some(operation) = ok!(program.next()) {
+            other_operations.push(operation);
+        }
+        assert_eq!(program.width(), Some(-100.0));
+        assert_eq!(other_operations, operations[..operations.len() - 1]);
+    }
+}
+
+mod transform {
+    use postscript::type2::{embolden, generalize, slant, specialize, Operator};
+
+    #[test]
+    fn embolden_square() {
+        let operations = operations!(
+            HStem: [0, 100],
+            RMoveTo: [0, 0],
+            RLineTo: [100, 0],
+            VLineTo: [100],
+            HLineTo: [-100],
+        );
+        assert_eq!(
+            ok!(embolden(&operations, 20.0)),
+            operations!(
+                RMoveTo: [0, 0],
+                RLineTo: [120, 0],
+                RLineTo: [0, 120],
+                RLineTo: [-120, 0],
+            ),
+        );
+        let operations = operations!(
+            RMoveTo: [0, 0],
+            HLineTo: [-100],
+            VLineTo: [100],
+            RLineTo: [100, 0],
+        );
+        assert_eq!(
+            ok!(embolden(&operations, 20.0)),
+            operations!(
+                RMoveTo: [20, 0],
+                RLineTo: [-120, 0],
+                RLineTo: [0, 120],
+                RLineTo: [120, 0],
+            ),
+        );
+    }
+
+    #[test]
+    fn slant_lines() {
+        let operations = operations!(
+            VStem: [0, 10],
+            RMoveTo: [10, 0],
+            VLineTo: [100],
+            EndChar: [0, 100, 65, 194],
+        );
+        let operations = ok!(slant(&operations, 45.0));
+        assert_eq!(operations.len(), 3);
+        assert_eq!(operations[0], (Operator::RMoveTo, vec![10.0, 0.0]));
+        assert_eq!(operations[1].0, Operator::RLineTo);
+        assert!((operations[1].1[0] - 100.0).abs() < 1e-3);
+        assert_eq!(operations[1].1[1], 100.0);
+        assert_eq!(operations[2].0, Operator::EndChar);
+        assert!((operations[2].1[0] - 100.0).abs() < 1e-3);
+        assert_eq!(operations[2].1[1..], [100.0, 65.0, 194.0]);
+    }
+
+    #[test]
+    fn generalize_flex() {
+        let operations = operations!(
+            HFlex: [1, 2, 3, 4, 5, 6, 7],
+            HFlex1: [1, 2, 3, 4, 5, 6, 7, 8, 9],
+            Flex1: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11],
+        );
+        assert_eq!(
+            ok!(generalize(&operations)),
+            operations!(
+                RRCurveTo: [1, 0, 2, 3, 4, 0],
+                RRCurveTo: [5, 0, 6, -3, 7, 0],
+                RRCurveTo: [1, 2, 3, 4, 5, 0],
+                RRCurveTo: [6, 0, 7, 8, 9, -14],
+                RRCurveTo: [1, 2, 3, 4, 5, 6],
+                RRCurveTo: [7, 8, 9, 10, -25, 11],
+            ),
+        );
+    }
+
+    #[test]
+    fn specialize_lines() {
+        let operations = operations!(
+            RMoveTo: [10, 0],
+            HLineTo: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48, 49, 50],
+            RLineTo: [1, 1, 2, 2],
+        );
+        assert_eq!(
+            ok!(specialize(&operations)),
+            operations!(
+                HMoveTo: [10],
+                HLineTo: [1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16, 17, 18, 19, 20, 21, 22, 23, 24, 25, 26, 27, 28, 29, 30, 31, 32, 33, 34, 35, 36, 37, 38, 39, 40, 41, 42, 43, 44, 45, 46, 47, 48],
+                HLineTo: [49, 50],
+                RLineTo: [1, 1, 2, 2],
+            ),
+        );
+    }
+
+    #[test]
+    fn reject_malformed_operands() {
+        let operations = operations!(HFlex: [1, 2, 3],);
+        assert!(generalize(&operations).is_err());
+    }
+}
+
+mod assembly {
+    use postscript::type2::{assemble, disassemble, tokenize, Operator, Token};
+
+    #[test]
+    fn assemble_calls() {
+        let global = vec![vec![0x0b]; 2];
+        let local = vec![vec![0x8b, 0x0b]; 1240];
+        let text = "10 20 hstemhm 1 0.5 callgsubr#1 callsubr#1239 hintmask 11000000 endchar";
+        let code = ok!(assemble(text, &global, &local));
+        assert_eq!(
+            code,
+            vec![
+                0x95, 0x9f, 0x12, 0x8c, 0xff, 0x00, 0x00, 0x80, 0x00, 0x21, 0x1d, 0xf7, 0x00, 0x0a,
+                0x13, 0xc0, 0x0e,
+            ],
+        );
+        assert_eq!(
+            ok!(tokenize(&code, &global, &local))[7..],
+            [
+                Token::Operand(108.0),
+                Token::Operator(Operator::CallSubr),
+                Token::Operator(Operator::HintMask),
+                Token::Mask(vec![0xc0]),
+                Token::Operator(Operator::EndChar),
+            ],
+        );
+        assert_eq!(
+            ok!(disassemble(&code, &global, &local)),
+            "10 20 hstemhm\n1 0.5 callgsubr#1\ncallsubr#1239\nhintmask 11000000\nendchar",
+        );
+    }
+
+    #[test]
+    fn reject_unknown_tokens() {
+        assert!(assemble("10 rmove", &[], &[]).is_err());
+        assert!(assemble("callsubr#0", &[], &[]).is_err());
+        assert!(assemble("10 20 hstemhm hintmask 101", &[], &[]).is_err());
+    }
+
+    #[test]
+    fn round_trip_empty_masks() {
+        let code = [0x13, 0x95, 0x9f, 0x15, 0x0e];
+        let text = ok!(disassemble(&code, &[], &[]));
+        assert_eq!(text, "hintmask\n10 20 rmoveto\nendchar");
+        assert_eq!(ok!(assemble(&text, &[], &[])), code);
+    }
+
+    #[test]
+    fn round_trip_unresolved_calls() {
+        let local = vec![vec![0x0b]; 1];
+        let text = "0.5 callsubr\n-106 callsubr\n5 callgsubr\nendchar\n10 hmoveto";
+        let code = ok!(assemble(text, &[], &local));
+        assert_eq!(ok!(disassemble(&code, &[], &local)), text);
+        assert_eq!(
+            ok!(tokenize(&code, &[], &local))[7..],
+            [Token::Operand(10.0), Token::Operator(Operator::HMoveTo)],
+        );
+    }
+}
+
+mod error {
+    use postscript::type2::{ErrorWithLocation, Location, Program};
+
+    #[test]
+    fn locate_subroutines() {
+        let global = vec![vec![0x8b, 0x20, 0x0a, 0x0b]];
+        let local = vec![vec![0x0a]];
+        let code = [0x20, 0x1d, 0x0e];
+        let mut program = Program::new(&code, &global, &local);
+        let error = program.next().unwrap_err();
+        let error = ErrorWithLocation::find(&error).unwrap();
+        assert_eq!(error.location, Location::LocalSubroutine(0));
+        assert_eq!(error.offset, 1);
+        assert_eq!(
+            error.to_string(),
+            "found no subroutine in local subroutine 0 at offset 1",
+        );
+    }
+
+    #[test]
+    fn reject_recursion() {
+        let local = vec![vec![0x20, 0x0a]];
+        let code = [0x20, 0x0a, 0x0e];
+        let mut program = Program::new(&code, &[], &local);
+        let error = program.next().unwrap_err();
+        let error = ErrorWithLocation::find(&error).unwrap();
+        assert_eq!(error.location, Location::LocalSubroutine(0));
+        assert_eq!(
+            error.to_string(),
+            "found too many nested subroutine calls in local subroutine 0 at offset 2",
+        );
+    }
+}